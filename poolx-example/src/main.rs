@@ -2,9 +2,11 @@ use std::fmt::Debug;
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use tokio::io::AsyncWriteExt;
 
-use tokio::net::TcpStream;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
 
 use poolx::{Connection, ConnectOptions, Error, Pool, PoolOptions};
 use poolx::futures_core::future::BoxFuture;
@@ -18,7 +20,7 @@ pub struct MyConn {
 impl Connection for MyConn {
     type Options = MyConnOption;
 
-    fn close(mut self) -> BoxFuture<'static, Result<(), Error>> {
+    fn close(self) -> BoxFuture<'static, Result<(), Error>> {
         Box::pin(async move {
             Ok(())
         })
@@ -31,9 +33,22 @@ impl Connection for MyConn {
         })
     }
 
+    /// Check that the peer hasn't hung up, without sending anything.
+    ///
+    /// A connection sitting idle in the pool never has anything to read, so if the socket is
+    /// readable, the peer either closed it (a read returns 0 bytes) or sent something we didn't
+    /// ask for. `peek()` doesn't consume any of it, and wrapping it in a zero timeout polls it
+    /// exactly once, so a live connection isn't held up waiting for data that never comes.
     fn ping(&mut self) -> BoxFuture<'_, Result<(), Error>> {
         Box::pin(async move {
-            Ok(())
+            let mut buf = [0u8; 1];
+            match tokio::time::timeout(Duration::ZERO, self.inner.peek(&mut buf)).await {
+                // nothing to read, the connection is alive
+                Err(_elapsed) => Ok(()),
+                Ok(Ok(0)) => Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+                Ok(Ok(_)) => Err(Error::ResponseError),
+                Ok(Err(e)) => Err(e.into()),
+            }
         })
     }
 }
@@ -45,15 +60,6 @@ pub struct MyConnOption {
     counter: AtomicU64,
 }
 
-impl Clone for MyConnOption {
-    fn clone(&self) -> Self {
-        MyConnOption {
-            addr: self.addr,
-            counter: Default::default(),
-        }
-    }
-}
-
 impl FromStr for MyConnOption {
     type Err = Error;
 
@@ -80,31 +86,68 @@ impl ConnectOptions for MyConnOption {
 
     fn connect(&self) -> BoxFuture<'_, Result<Self::Connection, Error>> where Self::Connection: Sized {
         Box::pin(async move {
-            let conn = TcpStream::connect(self.addr).await?;
+            // keep the error kind, the pool retries refused connections
+            let conn = TcpStream::connect(self.addr).await.map_err(|e| {
+                std::io::Error::new(e.kind(), format!("failed to connect to {}: {e}", self.addr))
+            })?;
             Ok(MyConn { id: self.counter.fetch_add(1, Ordering::Relaxed), inner: conn })
         })
     }
 }
 
 
+/// A TCP server that accepts connections and just holds on to them.
+///
+/// Aborting the returned task "kills" the server: every connection it accepted is closed.
+fn start_server(listener: TcpListener) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut accepted = vec![];
+        while let Ok((stream, _)) = listener.accept().await {
+            accepted.push(stream);
+        }
+    })
+}
+
 #[tokio::main]
 async fn main() {
-    let conn_option = "tcp://127.0.0.1:6379".parse::<MyConnOption>().unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = start_server(listener);
+
+    let conn_option = format!("tcp://{addr}").parse::<MyConnOption>().unwrap();
     let pool: Pool<MyConn> = PoolOptions::new()
-        .idle_timeout(std::time::Duration::from_secs(3))
-        .min_connections(3)
-        .max_connections(100)
+        .max_connections(3)
+        // the default, `ping()` is called on idle connections before handing them out
+        .test_before_acquire(true)
         .connect_lazy_with(conn_option);
 
-    let mut vec = vec![];
-    for _i in 0..10 {
+    let mut conns = vec![];
+    for _ in 0..3 {
         let conn = pool.acquire().await.unwrap();
-        println!("conn: {}", conn.id);
-        vec.push(conn);
+        println!("acquired conn {}", conn.id);
+        conns.push(conn);
+    }
+    for conn in conns.drain(..) {
+        conn.return_now().await;
     }
-    println!("release 10 connections");
-    for _i in 0..10 {
-        vec.pop();
+    println!("{} idle connections", pool.num_idle());
+
+    println!("killing the server, and starting it again on {addr}");
+    server.abort();
+    let _ = server.await;
+    let server = start_server(TcpListener::bind(addr).await.unwrap());
+
+    // give the FINs a moment to arrive
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // `ping()` notices the idle connections are dead, so they're closed and replaced by new ones
+    for _ in 0..3 {
+        let conn = pool.acquire().await.unwrap();
+        println!("acquired conn {}", conn.id);
+        conns.push(conn);
     }
-    tokio::time::sleep(tokio::time::Duration::from_secs(10000)).await;
-}
\ No newline at end of file
+
+    drop(conns);
+    pool.close().await;
+    server.abort();
+}
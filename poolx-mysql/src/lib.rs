@@ -0,0 +1,12 @@
+//! A [`poolx`] adapter for MySQL, built on [`mysql_async`].
+//!
+//! The adapter is behind the `mysql` feature, which is enabled by default.
+
+#[cfg(feature = "mysql")]
+pub use mysql_async;
+
+#[cfg(feature = "mysql")]
+pub use self::mysql::{MySqlConnection, MySqlConnectionOption};
+
+#[cfg(feature = "mysql")]
+mod mysql;
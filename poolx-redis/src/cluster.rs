@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+
+use redis::Value;
+
+use poolx::url::Url;
+use poolx::{ConnectOptions, Connection, Pool, PoolConnection, PoolOptions};
+
+use crate::{RedisConnection, RedisConnectionOption};
+
+/// The number of hash slots in a Redis Cluster.
+pub const SLOT_COUNT: u16 = 16384;
+
+/// Get the hash slot of `key`, honoring hash tags: only the part between the first `{` and the
+/// next `}` is hashed, if that's not empty, so related keys can be put on the same node.
+pub fn key_slot(key: &[u8]) -> u16 {
+    let hashed = key
+        .iter()
+        .position(|&b| b == b'{')
+        .and_then(|open| {
+            let tag = &key[open + 1..];
+            let close = tag.iter().position(|&b| b == b'}')?;
+            (close > 0).then(|| &tag[..close])
+        })
+        .unwrap_or(key);
+
+    crc16(hashed) % SLOT_COUNT
+}
+
+/// CRC16-CCITT (XMODEM), the checksum Redis Cluster uses for key slots.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// The error returned for a slot that no known node serves, wrapped in [`poolx::Error::Other`].
+///
+/// The cluster's topology changed, or was never loaded: call
+/// [`ClusterPool::refresh_topology()`] and try again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownSlot(pub u16);
+
+impl fmt::Display for UnknownSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no known node serves slot {}, refresh the topology with `ClusterPool::refresh_topology()`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownSlot {}
+
+/// A pool of connections to each primary of a Redis Cluster, with acquires routed to the node
+/// that owns a given slot.
+///
+/// A connection from [`acquire_for_slot()`][Self::acquire_for_slot] or
+/// [`acquire_for_key()`][Self::acquire_for_key] is a plain connection to that node, so a
+/// sequence of commands on it, like a `MULTI`/`EXEC` or a Lua script, all run on the same
+/// node. The commands must only touch keys in slots owned by that node; the connection doesn't
+/// follow `MOVED` redirects.
+///
+/// The topology is only loaded by [`refresh_topology()`][Self::refresh_topology]. Call it once
+/// after creating the pool, and again when an acquire fails with [`UnknownSlot`] or a command
+/// fails with a `MOVED` error.
+///
+/// Requires the `cluster` feature.
+///
+/// ```rust,ignore
+/// let cluster = ClusterPool::new(PoolOptions::new().max_connections(10), "redis://127.0.0.1:7000".parse()?);
+/// cluster.refresh_topology().await?;
+///
+/// let mut conn = cluster.acquire_for_key("{user:1000}.following").await?;
+/// ```
+pub struct ClusterPool {
+    options: PoolOptions<RedisConnection>,
+    seed: RedisConnectionOption,
+    topology: RwLock<Topology>,
+}
+
+#[derive(Default)]
+struct Topology {
+    /// The slot ranges, inclusive, and the address of the node that owns each.
+    slots: Vec<SlotRange>,
+    /// A pool for each node in `slots`, by address.
+    nodes: HashMap<String, Pool<RedisConnection>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SlotRange {
+    start: u16,
+    end: u16,
+    node: String,
+}
+
+impl ClusterPool {
+    /// Create a cluster pool opening a pool with `options` for each node.
+    ///
+    /// `seed` is the node the topology is first loaded from. Its credentials, database and
+    /// health check are used for every node, with only the host and port replaced.
+    pub fn new(options: PoolOptions<RedisConnection>, seed: RedisConnectionOption) -> Self {
+        Self {
+            options,
+            seed,
+            topology: RwLock::new(Topology::default()),
+        }
+    }
+
+    /// Load the cluster's slot to node mapping with `CLUSTER SLOTS`, from one of the known
+    /// nodes or else the seed node.
+    ///
+    /// Pools are opened lazily for new nodes. The pools of nodes that no longer own any slot are
+    /// dropped, which closes their connections once they're returned.
+    pub async fn refresh_topology(&self) -> Result<(), poolx::Error> {
+        let known = self.nodes();
+
+        let mut last_error = None;
+        // along with the host of the node that sent it
+        let mut reply = None;
+        for node in &known {
+            let Some(pool) = self.pool_for_node(node) else {
+                continue;
+            };
+            match cluster_slots(pool.acquire().await).await {
+                Ok(value) => {
+                    reply = Some((value, node_host(node)));
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        let (reply, host) = match reply {
+            Some(reply) => reply,
+            // none of the known nodes answered, or none are known yet
+            None => match self.seed.connect().await {
+                Ok(mut conn) => {
+                    let reply = query_cluster_slots(&mut conn).await;
+                    let _ = conn.close().await;
+                    (reply?, self.seed.url.host_str().unwrap_or_default())
+                }
+                Err(e) => return Err(last_error.unwrap_or(e)),
+            },
+        };
+
+        // an empty host in the reply stands for the node that sent it
+        let slots = parse_cluster_slots(&reply, host)?;
+
+        let mut topology = self.topology.write().expect("BUG: panicked while holding a lock");
+        let mut nodes = HashMap::new();
+        for range in &slots {
+            if nodes.contains_key(&range.node) {
+                continue;
+            }
+
+            let pool = match topology.nodes.remove(&range.node) {
+                Some(pool) => pool,
+                None => self
+                    .options
+                    .clone()
+                    .connect_lazy_with(self.node_options(&range.node)?),
+            };
+            nodes.insert(range.node.clone(), pool);
+        }
+
+        *topology = Topology { slots, nodes };
+        Ok(())
+    }
+
+    /// Get the addresses, as `host:port`, of the nodes owning at least one slot.
+    pub fn nodes(&self) -> Vec<String> {
+        let topology = self.topology.read().expect("BUG: panicked while holding a lock");
+        topology.nodes.keys().cloned().collect()
+    }
+
+    /// Get the pool of the node owning `slot`, if known.
+    pub fn pool_for_slot(&self, slot: u16) -> Option<Pool<RedisConnection>> {
+        let topology = self.topology.read().expect("BUG: panicked while holding a lock");
+        let range = topology
+            .slots
+            .iter()
+            .find(|range| (range.start..=range.end).contains(&slot))?;
+        topology.nodes.get(&range.node).cloned()
+    }
+
+    fn pool_for_node(&self, node: &str) -> Option<Pool<RedisConnection>> {
+        let topology = self.topology.read().expect("BUG: panicked while holding a lock");
+        topology.nodes.get(node).cloned()
+    }
+
+    /// Retrieves a connection to the node owning `slot`.
+    ///
+    /// Returns [`poolx::Error::Other`] wrapping an [`UnknownSlot`] if no known node owns it.
+    pub async fn acquire_for_slot(&self, slot: u16) -> Result<PoolConnection<RedisConnection>, poolx::Error> {
+        let pool = self
+            .pool_for_slot(slot)
+            .ok_or_else(|| poolx::Error::Other(UnknownSlot(slot).into()))?;
+        pool.acquire().await
+    }
+
+    /// Retrieves a connection to the node owning the slot of `key`, see [`key_slot()`].
+    pub async fn acquire_for_key(&self, key: impl AsRef<[u8]>) -> Result<PoolConnection<RedisConnection>, poolx::Error> {
+        self.acquire_for_slot(key_slot(key.as_ref())).await
+    }
+
+    /// Close the pools of all the nodes.
+    pub async fn close(&self) {
+        let pools: Vec<_> = {
+            let topology = self.topology.read().expect("BUG: panicked while holding a lock");
+            topology.nodes.values().cloned().collect()
+        };
+        for pool in pools {
+            pool.close().await;
+        }
+    }
+
+    /// The seed's options with the host and port of `node`.
+    fn node_options(&self, node: &str) -> Result<RedisConnectionOption, poolx::Error> {
+        let (host, port) = node
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+            .ok_or_else(|| poolx::Error::Configuration(format!("invalid cluster node address `{node}`").into()))?;
+
+        // IPv6 addresses need brackets in a URL
+        let host = if host.contains(':') { format!("[{host}]") } else { host.to_string() };
+
+        let mut url: Url = self.seed.url.clone();
+        url.set_host(Some(&host))
+            .map_err(|e| poolx::Error::Configuration(format!("invalid cluster node address `{node}`: {e}").into()))?;
+        let _ = url.set_port(Some(port));
+
+        let mut option = RedisConnectionOption::from_url(&url)?;
+        option.health_check = self.seed.health_check.clone();
+        Ok(option)
+    }
+}
+
+impl fmt::Debug for ClusterPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClusterPool")
+            .field("seed", &self.seed)
+            .field("nodes", &self.nodes())
+            .finish_non_exhaustive()
+    }
+}
+
+async fn cluster_slots(
+    conn: Result<PoolConnection<RedisConnection>, poolx::Error>,
+) -> Result<Value, poolx::Error> {
+    let mut conn = conn?;
+    let reply = query_cluster_slots(&mut conn).await;
+    conn.return_now().await;
+    reply
+}
+
+async fn query_cluster_slots(conn: &mut RedisConnection) -> Result<Value, poolx::Error> {
+    redis::cmd("CLUSTER")
+        .arg("SLOTS")
+        .query_async(conn)
+        .await
+        .map_err(|e| poolx::Error::Other(e.into()))
+}
+
+/// The host of a node address as kept in the topology, `host:port`.
+fn node_host(node: &str) -> &str {
+    node.rsplit_once(':').map_or(node, |(host, _)| host)
+}
+
+/// Parse a `CLUSTER SLOTS` reply into the primary of each slot range.
+///
+/// An empty host means the node the command was sent to, for which `default_host` is used.
+fn parse_cluster_slots(reply: &Value, default_host: &str) -> Result<Vec<SlotRange>, poolx::Error> {
+    let invalid = || poolx::Error::ResponseError;
+
+    let Value::Array(ranges) = reply else {
+        return Err(invalid());
+    };
+
+    ranges
+        .iter()
+        .map(|range| {
+            let Value::Array(range) = range else {
+                return Err(invalid());
+            };
+            let [Value::Int(start), Value::Int(end), Value::Array(primary), ..] = range.as_slice() else {
+                return Err(invalid());
+            };
+            let [Value::BulkString(host), Value::Int(port), ..] = primary.as_slice() else {
+                return Err(invalid());
+            };
+
+            let host = std::str::from_utf8(host).map_err(|_| invalid())?;
+            let host = if host.is_empty() { default_host } else { host };
+
+            Ok(SlotRange {
+                start: u16::try_from(*start).map_err(|_| invalid())?,
+                end: u16::try_from(*end).map_err(|_| invalid())?,
+                node: format!("{host}:{port}"),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::Value;
+
+    use poolx::PoolOptions;
+
+    use super::{key_slot, node_host, parse_cluster_slots, ClusterPool, SlotRange, UnknownSlot};
+
+    #[test]
+    fn test_key_slot() {
+        // values from the Redis Cluster specification and `CLUSTER KEYSLOT`
+        assert_eq!(super::crc16(b"123456789"), 0x31C3);
+        assert_eq!(key_slot(b"foo"), 12182);
+        assert_eq!(key_slot(b"bar"), 5061);
+
+        // hash tags
+        assert_eq!(key_slot(b"{user1000}.following"), key_slot(b"user1000"));
+        // an empty first tag means the whole key is hashed
+        assert_eq!(key_slot(b"foo{}{bar}"), 8363);
+        assert_ne!(key_slot(b"foo{}{bar}"), key_slot(b"bar"));
+        assert_eq!(key_slot(b"foo{{bar}}zap"), key_slot(b"{bar"));
+        assert_eq!(key_slot(b"foo{bar}{zap}"), key_slot(b"bar"));
+    }
+
+    #[test]
+    fn test_parse_cluster_slots() {
+        let node = |host: &str, port| {
+            Value::Array(vec![
+                Value::BulkString(host.as_bytes().to_vec()),
+                Value::Int(port),
+                Value::BulkString(b"09dbe9720cda62f7865eabc5fd8857c5d2678366".to_vec()),
+            ])
+        };
+        let reply = Value::Array(vec![
+            Value::Array(vec![Value::Int(0), Value::Int(5460), node("10.0.0.1", 7000), node("10.0.0.4", 7003)]),
+            Value::Array(vec![Value::Int(5461), Value::Int(16383), node("", 7001)]),
+        ]);
+
+        let slots = parse_cluster_slots(&reply, "10.0.0.9").unwrap();
+        assert_eq!(
+            slots,
+            [
+                SlotRange { start: 0, end: 5460, node: "10.0.0.1:7000".into() },
+                SlotRange { start: 5461, end: 16383, node: "10.0.0.9:7001".into() },
+            ]
+        );
+
+        assert!(parse_cluster_slots(&Value::Okay, "10.0.0.9").is_err());
+    }
+
+    #[test]
+    fn test_node_options() {
+        let cluster = ClusterPool::new(PoolOptions::new(), "redis://:secret@10.0.0.9:7000/0".parse().unwrap());
+
+        let option = cluster.node_options("10.0.0.1:7001").unwrap();
+        assert_eq!(option.url.as_str(), "redis://:secret@10.0.0.1:7001/0");
+
+        let option = cluster.node_options("::1:7002").unwrap();
+        assert_eq!(option.url.as_str(), "redis://:secret@[::1]:7002/0");
+
+        assert!(cluster.node_options("10.0.0.1").is_err());
+    }
+
+    #[test]
+    fn test_empty_host_is_the_answering_node() {
+        let reply = Value::Array(vec![Value::Array(vec![
+            Value::Int(0),
+            Value::Int(16383),
+            Value::Array(vec![Value::BulkString(Vec::new()), Value::Int(7001)]),
+        ])]);
+
+        // refreshed from a known node rather than the seed
+        let slots = parse_cluster_slots(&reply, node_host("10.0.0.2:7001")).unwrap();
+        assert_eq!(slots[0].node, "10.0.0.2:7001");
+        assert_eq!(node_host("::1:7002"), "::1");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_slot() {
+        let cluster = ClusterPool::new(PoolOptions::new(), "redis://127.0.0.1:7000".parse().unwrap());
+        assert!(cluster.pool_for_slot(0).is_none());
+
+        let err = cluster.acquire_for_key("foo").await.unwrap_err();
+        let poolx::Error::Other(e) = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(e.downcast_ref::<UnknownSlot>(), Some(&UnknownSlot(12182)));
+        assert!(e.to_string().contains("refresh_topology"), "{e}");
+    }
+
+    /// Needs a running cluster: set `POOLX_REDIS_CLUSTER_URL` to one of its nodes, e.g.
+    /// `redis://127.0.0.1:7000`.
+    #[tokio::test]
+    async fn test_cluster_pool() {
+        let Ok(url) = std::env::var("POOLX_REDIS_CLUSTER_URL") else {
+            eprintln!("POOLX_REDIS_CLUSTER_URL not set, skipping");
+            return;
+        };
+
+        let cluster = ClusterPool::new(PoolOptions::new().max_connections(2), url.parse().unwrap());
+        cluster.refresh_topology().await.unwrap();
+        assert!(!cluster.nodes().is_empty());
+
+        // every key with the same hash tag is on the same node, so a transaction over them works
+        let mut conn = cluster.acquire_for_key("{poolx}").await.unwrap();
+        let (a, b): (String, String) = redis::pipe()
+            .atomic()
+            .set("{poolx}.a", "1").ignore()
+            .set("{poolx}.b", "2").ignore()
+            .get("{poolx}.a")
+            .get("{poolx}.b")
+            .query_async(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!((a.as_str(), b.as_str()), ("1", "2"));
+
+        cluster.close().await;
+    }
+}
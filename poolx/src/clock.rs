@@ -0,0 +1,28 @@
+//! The pool's source of time.
+//!
+//! All of the pool's timekeeping goes through here: connection ages and idle times, acquire
+//! deadlines, and the sleeps of the maintenance tasks. It reads Tokio's clock, which is the
+//! system clock unless a test pauses it with [`tokio::time::pause()`] (needs Tokio's `test-util`
+//! feature, which the pool's `test-util` feature turns on). Then tests can fast-forward it with
+//! [`tokio::time::advance()`] rather than sleeping for real until an `idle_timeout` or
+//! `max_lifetime` passes.
+//!
+//! There's no switch that picks a clock at compile time: only a runtime that a test paused
+//! ever stops following the system clock, whatever features end up enabled.
+
+use std::time::{Duration, Instant};
+
+use tokio::time::Sleep;
+
+pub(crate) fn now() -> Instant {
+    tokio::time::Instant::now().into_std()
+}
+
+pub(crate) fn sleep(duration: Duration) -> Sleep {
+    tokio::time::sleep(duration)
+}
+
+/// Like [`Instant::elapsed()`], but measured against the pool's clock.
+pub(crate) fn elapsed(since: Instant) -> Duration {
+    now().saturating_duration_since(since)
+}
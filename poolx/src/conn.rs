@@ -1,3 +1,5 @@
+use std::any::Any;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::str::FromStr;
 
@@ -6,7 +8,13 @@ use url::Url;
 
 use crate::error::Error;
 
+/// A connection that can be managed by a [`Pool`][crate::Pool].
+///
+/// The pool has no notion of a database or of queries: an adapter only needs to say how to
+/// open a connection (through its [`Options`][Self::Options]), how to close it, and how to check
+/// that it is still alive.
 pub trait Connection: 'static + Send {
+    /// The options used to open new connections of this type.
     type Options: ConnectOptions<Connection=Self>;
 
     /// Explicitly close this database connection.
@@ -25,13 +33,97 @@ pub trait Connection: 'static + Send {
     ///
     /// Therefore it is recommended to call `.close()` on a connection when you are done using it
     /// and to `.await` the result to ensure the termination message is sent.
+    ///
+    /// The returned future owns the connection, so implementations must release all local
+    /// resources (sockets, buffers) even when the graceful shutdown fails; callers cannot fall back
+    /// to [`close_hard`][Self::close_hard] after the connection has been handed over.
     fn close(self) -> BoxFuture<'static, Result<(), Error>>;
 
+    /// Immediately close the connection without sending a graceful shutdown.
+    ///
+    /// This should be used when the connection is known to be broken or in an unknown state.
     fn close_hard(self) -> BoxFuture<'static, Result<(), Error>>;
+
+    /// Check that the connection is still alive, e.g. by sending a no-op command.
     fn ping(&mut self) -> BoxFuture<'_, Result<(), Error>>;
+
+    /// Abort any scope left open on the connection, like a transaction or a Redis `MULTI`, so
+    /// the next user doesn't inherit it.
+    ///
+    /// Called when the connection is returned to the pool, if
+    /// [`rollback_on_release`][crate::PoolOptions::rollback_on_release] is enabled (the
+    /// default), before [`after_release`][crate::PoolOptions::after_release]. It should succeed
+    /// when there's nothing to roll back. If it returns an error, the connection is closed with
+    /// [`close_hard`][Self::close_hard].
+    ///
+    /// Defaults to doing nothing.
+    fn rollback_pending(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Keep an idle connection alive, e.g. by sending an application-level keepalive.
+    ///
+    /// Called on each connection that has been idle in the pool for
+    /// [`keepalive_interval`][crate::PoolOptions::keepalive_interval], if set, and every
+    /// `keepalive_interval` after that. Useful where middleboxes like NAT gateways silently drop
+    /// connections that have been quiet for too long. If it returns an error, the connection is
+    /// closed with [`close_hard`][Self::close_hard].
+    ///
+    /// Defaults to doing nothing.
+    fn on_idle(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Whether the connection must not be reused, checked when it is returned to the pool.
+    ///
+    /// Return `true` if the connection learned that it shouldn't be handed out again even
+    /// though the last operation succeeded, e.g. from a protocol error or a server notice that
+    /// it's shutting down. The pool then closes it with [`close`][Self::close] instead of putting
+    /// it back in the idle queue.
+    ///
+    /// This is called synchronously on every return, so it should only read local state.
+    /// Defaults to `false`.
+    fn should_discard(&self) -> bool {
+        false
+    }
+
+    /// Get the connection as [`Any`], to downcast it to its concrete type in code that is generic
+    /// over `C: Connection`.
+    ///
+    /// This is an escape hatch for the rare command only one kind of connection supports:
+    ///
+    /// ```rust,ignore
+    /// async fn flush<C: Connection>(conn: &mut PoolConnection<C>) {
+    ///     if let Some(redis) = conn.as_any_mut().downcast_mut::<RedisConnection>() {
+    ///         redis::cmd("FLUSHDB").query_async::<()>(redis).await.ok();
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Returns `self`, which works for any connection type as they're all `'static`. There's no
+    /// need to override it.
+    fn as_any_mut(&mut self) -> &mut dyn Any
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Like [`as_any_mut()`][Self::as_any_mut], for a shared reference.
+    fn as_any(&self) -> &dyn Any
+    where
+        Self: Sized,
+    {
+        self
+    }
 }
 
-pub trait ConnectOptions: 'static + Send + Sync + FromStr<Err=Error> + Debug + Clone {
+/// Options for opening new connections of a [`Connection`] type.
+///
+/// A pool keeps a single instance of its options behind an `Arc` and opens every connection
+/// from it, so the options don't need to be `Clone`. This means state in the options, like a
+/// counter or a TLS session cache, is shared by all connections of the pool.
+pub trait ConnectOptions: 'static + Send + Sync + FromStr<Err=Error> + Debug {
     type Connection: Connection + ?Sized;
 
     /// Parse the `ConnectOptions` from a URL.
@@ -41,4 +133,16 @@ pub trait ConnectOptions: 'static + Send + Sync + FromStr<Err=Error> + Debug + C
     fn connect(&self) -> BoxFuture<'_, Result<Self::Connection, Error>>
         where
             Self::Connection: Sized;
+
+    /// Adapter-specific settings, as plain key-value pairs, for logging or diffing the
+    /// configuration, e.g. whether TLS or a statement cache is enabled.
+    ///
+    /// [`Pool::describe()`][crate::Pool::describe] includes these next to the pool's own
+    /// options. They are never read by the pool, so never put credentials in here.
+    ///
+    /// Defaults to an empty map.
+    fn extra(&self) -> &BTreeMap<String, String> {
+        static EMPTY: BTreeMap<String, String> = BTreeMap::new();
+        &EMPTY
+    }
 }
\ No newline at end of file
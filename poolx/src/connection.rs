@@ -1,12 +1,18 @@
+use std::any::Any;
 use std::fmt::{self, Debug, Formatter};
 use std::future::Future;
 use std::ops::{Deref, DerefMut};
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crate::conn::{Connection, ConnectOptions};
+use futures_core::future::BoxFuture;
+use futures_util::FutureExt;
+
+use crate::clock;
+use crate::conn::Connection;
 use crate::error::Error;
-use crate::PoolConnectionMetadata;
+use crate::{PoolConnectionMetadata, ReturnMode};
 use crate::sync::AsyncSemaphoreReleaser;
 
 use super::inner::{DecrementSizeGuard, PoolInner};
@@ -16,17 +22,39 @@ use super::inner::{DecrementSizeGuard, PoolInner};
 /// Will be returned to the pool on-drop.
 pub struct PoolConnection<C: Connection> {
     live: Option<Live<C>>,
+    /// The id of the connection, kept for after `live` was taken.
+    id: u64,
+    /// Set by [`.return_now()`][Self::return_now], which maintains `min_connections` itself.
+    returned: bool,
     pub(crate) pool: Arc<PoolInner<C>>,
 }
 
 pub(super) struct Live<C: Connection> {
     pub(super) raw: C,
+    /// Unique per pool, assigned when the connection is opened.
+    pub(super) id: u64,
     pub(super) created_at: Instant,
+    /// Set by `options.connection_extension`.
+    pub(super) extension: Option<Arc<dyn Any + Send + Sync>>,
+    /// The errors reported for the connection while it stayed in use, checked against
+    /// `options.max_connection_errors` when it's returned.
+    pub(super) errors: u32,
+    /// When the last of `errors` was reported, for `options.prefer_healthy`.
+    pub(super) last_error_at: Option<Instant>,
+    /// Whether the checkout holds a permit from the pool's `normal_permits`, kept here while
+    /// the connection is attached to a `PoolConnection` and in its guard otherwise.
+    pub(super) normal_permit: bool,
+    /// Set by `PoolConnection::on_return()` for the current checkout only.
+    pub(super) on_return: Vec<OnReturn<C>>,
 }
 
+type OnReturn<C> = Box<dyn for<'c> FnOnce(&'c mut C) -> BoxFuture<'c, Result<(), Error>> + Send>;
+
 pub(super) struct Idle<C: Connection> {
     pub(super) live: Live<C>,
     pub(super) idle_since: Instant,
+    /// When `Connection::on_idle()` was last called for the connection, or `idle_since`.
+    pub(super) keepalive_at: Instant,
 }
 
 /// RAII wrapper for connections being handled by functions that may drop them
@@ -76,11 +104,126 @@ impl<C: Connection> PoolConnection<C> {
     /// Equivalent to calling [`.detach()`] then [`.close()`], but the connection permit is retained
     /// for the duration so that the pool may not exceed `max_connections`.
     ///
+    /// If the graceful close fails, the error is returned but the connection's local resources
+    /// are still released, as [`Connection::close`] takes ownership of the connection and drops
+    /// it when finished. There is nothing left to fall back to [`Connection::close_hard`] with.
+    ///
     /// [`.detach()`]: PoolConnection::detach
     /// [`.close()`]: Connection::close
     pub async fn close(mut self) -> Result<(), Error> {
         let floating = self.take_live().float(self.pool.clone());
-        floating.inner.raw.close().await
+        self.pool.notify_close(floating.metadata());
+
+        let Floating { inner, guard } = floating;
+
+        let res = inner.raw.close().await;
+
+        if let Err(error) = &res {
+            log_event!(
+                self.pool.options.log_settings.close_level,
+                %error,
+                "error occurred while closing the pool connection"
+            );
+        }
+
+        // The permit is only given back once the connection is really gone,
+        // whichever way the close went.
+        drop(guard);
+
+        res
+    }
+
+    /// Report an error on this connection that it recovered from, like a command that timed out.
+    ///
+    /// A connection with more errors than
+    /// [`PoolOptions::max_connection_errors`][crate::PoolOptions::max_connection_errors] is
+    /// closed when it's returned instead of going back to the pool, as a connection that keeps
+    /// failing is often about to die for good. The count sticks with the connection across
+    /// checkouts.
+    pub fn mark_error(&mut self) {
+        let live = self.live.as_mut().expect(EXPECT_MSG);
+        live.errors = live.errors.saturating_add(1);
+        live.last_error_at = Some(clock::now());
+    }
+
+    /// Get the number of errors reported for this connection, see
+    /// [`.mark_error()`][Self::mark_error].
+    pub fn error_count(&self) -> u32 {
+        self.live.as_ref().expect(EXPECT_MSG).errors
+    }
+
+    /// [Ping][Connection::ping] the connection, counting a failure as with
+    /// [`.mark_error()`][Self::mark_error].
+    ///
+    /// `conn.ping()` still calls [`Connection::ping`] through `Deref`, without counting anything.
+    pub async fn ping_tracked(&mut self) -> Result<(), Error> {
+        let res = self.live.as_mut().expect(EXPECT_MSG).raw.ping().await;
+        if res.is_err() {
+            self.mark_error();
+        }
+        res
+    }
+
+    /// Get the value stored for this connection by
+    /// [`PoolOptions::connection_extension`][crate::PoolOptions::connection_extension].
+    ///
+    /// Returns `None` if no value was stored or if it is not a `T`.
+    pub fn extension<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        let extension = self.live.as_ref().expect(EXPECT_MSG).extension.clone()?;
+        extension.downcast().ok()
+    }
+
+    /// Close this connection without a graceful shutdown, allowing the pool to open a replacement.
+    ///
+    /// Use this instead of [`.close()`][Self::close] when the connection is known to be broken or
+    /// wedged, to skip the round-trip to the server.
+    ///
+    /// Like `.close()`, the connection permit is retained until the connection is closed so that
+    /// the pool may not exceed `max_connections`. Errors from [`Connection::close_hard`] are
+    /// logged and otherwise ignored.
+    pub async fn close_hard(mut self) {
+        let floating = self.take_live().float(self.pool.clone());
+        self.pool.notify_close(floating.metadata());
+
+        let Floating { inner, guard } = floating;
+
+        if let Err(error) = inner.raw.close_hard().await {
+            log_event!(
+                self.pool.options.log_settings.close_level,
+                %error,
+                "error occurred while hard-closing the pool connection"
+            );
+        }
+
+        drop(guard);
+    }
+
+    /// Hard-close this connection and acquire another one from the same pool, e.g. to retry
+    /// a command that failed with a connection-level error.
+    ///
+    /// The broken connection never goes back to the pool: it is closed like with
+    /// [`.close_hard()`][Self::close_hard], freeing its slot, before a connection is acquired as
+    /// with [`Pool::acquire()`][crate::Pool::acquire]. That may be an idle connection rather
+    /// than a new one, tested if [`test_before_acquire`][crate::PoolOptions::test_before_acquire]
+    /// is set.
+    ///
+    /// ```rust,ignore
+    /// let value = match conn.get("key").await {
+    ///     Err(e) if e.is_io_error() => {
+    ///         conn = conn.discard_and_reacquire().await?;
+    ///         conn.get("key").await?
+    ///     }
+    ///     res => res?,
+    /// };
+    /// ```
+    pub fn discard_and_reacquire(
+        self,
+    ) -> impl Future<Output=Result<PoolConnection<C>, Error>> + Send + 'static {
+        let pool = crate::Pool(self.pool.clone());
+        async move {
+            self.close_hard().await;
+            pool.acquire().await
+        }
     }
 
     /// Detach this connection from the pool, allowing it to open a replacement.
@@ -108,10 +251,50 @@ impl<C: Connection> PoolConnection<C> {
         self.take_live().raw
     }
 
+    /// Run `f` on the connection when this checkout is returned to the pool, like an
+    /// [`after_release`][crate::PoolOptions::after_release] for this checkout only, e.g. to
+    /// always `UNWATCH` after an optimistic locking sequence:
+    ///
+    /// ```rust,ignore
+    /// let conn = pool.acquire().await?.on_return(|conn| Box::pin(async move {
+    ///     redis::cmd("UNWATCH").query_async::<()>(conn).await.map_err(|e| poolx::Error::Other(e.into()))
+    /// }));
+    /// ```
+    ///
+    /// `f` runs after every other check on release, right before the connection goes back to
+    /// the idle queue, so the next checkout always sees its effect. If the connection is closed
+    /// or detached instead, `f` is dropped without running. Closures registered several times
+    /// run in the order they were registered.
+    ///
+    /// Like `after_release`, the future is bounded by
+    /// [`release_timeout`][crate::PoolOptions::release_timeout]. If it fails, times out or
+    /// panics, the connection is closed, as it may have been left halfway through.
+    pub fn on_return<F>(mut self, f: F) -> Self
+    where
+        F: for<'c> FnOnce(&'c mut C) -> BoxFuture<'c, Result<(), Error>> + Send + 'static,
+    {
+        self.live.as_mut().expect(EXPECT_MSG).on_return.push(Box::new(f));
+        self
+    }
+
     fn take_live(&mut self) -> Live<C> {
         self.live.take().expect(EXPECT_MSG)
     }
 
+    /// Return this connection to the pool right away, in the caller's task.
+    ///
+    /// This runs the same release path as dropping the connection, including
+    /// [`after_release`][crate::PoolOptions::after_release], but instead of spawning a task
+    /// for it, the connection is back in the pool (or closed) once the returned future completes.
+    ///
+    /// If the future is dropped before it completes, the connection is closed and its permit
+    /// given back to the pool.
+    pub fn return_now(mut self) -> impl Future<Output=()> + Send + 'static {
+        let return_to_pool = self.return_to_pool();
+        self.returned = true;
+        return_to_pool
+    }
+
     /// Test the connection to make sure it is still live before returning it to the pool.
     ///
     /// This effectively runs the drop handler eagerly instead of spawning a task to do it.
@@ -144,26 +327,95 @@ impl<C: Connection> PoolConnection<C> {
 /// Returns the connection to the [`Pool`][crate::pool::Pool] it was checked-out from.
 impl<C: Connection> Drop for PoolConnection<C> {
     fn drop(&mut self) {
-        // We still need to spawn a task to maintain `min_connections`.
-        if self.live.is_some() || self.pool.options.min_connections > 0 {
-            tokio::spawn(self.return_to_pool());
+        // However the connection left our hands, it's no longer checked out.
+        self.pool.end_checkout(self.id);
+
+        // Even without a connection to return, `min_connections` may need maintaining.
+        if !(self.live.is_some() || (self.pool.options.min_connections > 0 && !self.returned)) {
+            return;
+        }
+
+        let mode = match self.pool.options.return_on_drop {
+            ReturnMode::Block if std::thread::panicking() => ReturnMode::Detach,
+            mode => mode,
+        };
+
+        match mode {
+            ReturnMode::Spawn => {
+                // Outside of any runtime, e.g. after `Pool::blocking_acquire()`, the task goes to
+                // the runtime the pool was created on.
+                //
+                // `return_to_pool()` floats the connection first, so if that runtime is shutting
+                // down or already gone and the task is dropped, so is the connection, giving its
+                // permit back to the pool.
+                let return_to_pool = self.return_to_pool();
+                match tokio::runtime::Handle::try_current() {
+                    Ok(handle) => handle.spawn(return_to_pool),
+                    Err(_) => self.pool.runtime.spawn(return_to_pool),
+                };
+            }
+            ReturnMode::Block => {
+                let floating = self.live.take().map(|live| live.float(self.pool.clone()));
+                let release = async move {
+                    match floating {
+                        Some(floating) => floating.return_to_pool().await,
+                        None => false,
+                    }
+                };
+
+                let handle = tokio::runtime::Handle::try_current();
+                let returned_to_pool = match &handle {
+                    Ok(handle) => {
+                        assert!(
+                            handle.runtime_flavor() != tokio::runtime::RuntimeFlavor::CurrentThread,
+                            "`ReturnMode::Block` can't block a current-thread runtime, \
+                             use `PoolConnection::return_now()` instead"
+                        );
+                        tokio::task::block_in_place(|| handle.block_on(release))
+                    }
+                    Err(_) => self.pool.runtime.block_on(release),
+                };
+
+                if !returned_to_pool {
+                    // Topping up `min_connections` waits for a connect, maybe for as long as the
+                    // connect retries last, which the drop shouldn't.
+                    let pool = self.pool.clone();
+                    let maintenance = async move { pool.min_connections_maintenance(None).await };
+                    match handle {
+                        Ok(handle) => handle.spawn(maintenance),
+                        Err(_) => self.pool.runtime.spawn(maintenance),
+                    };
+                }
+            }
+            ReturnMode::Detach => {
+                // The maintenance task tops up `min_connections` later.
+                if let Some(live) = self.live.take() {
+                    let floating = live.float(self.pool.clone());
+                    self.pool.notify_close(floating.metadata());
+                    drop(floating);
+                }
+            }
         }
     }
 }
 
 impl<C: Connection> Live<C> {
-    pub fn float(self, pool: Arc<PoolInner<C>>) -> Floating<C, Self> {
-        Floating {
-            inner: self,
-            // create a new guard from a previously leaked permit
-            guard: DecrementSizeGuard::new_permit(pool),
-        }
+    pub fn float(mut self, pool: Arc<PoolInner<C>>) -> Floating<C, Self> {
+        // create a new guard from a previously leaked permit
+        let mut guard = DecrementSizeGuard::new_permit(pool);
+        guard.normal_permit = std::mem::take(&mut self.normal_permit);
+        guard.open = true;
+
+        Floating { inner: self, guard }
     }
 
     pub fn into_idle(self) -> Idle<C> {
+        let now = clock::now();
+
         Idle {
             live: self,
-            idle_since: Instant::now(),
+            idle_since: now,
+            keepalive_at: now,
         }
     }
 }
@@ -183,24 +435,39 @@ impl<C: Connection> DerefMut for Idle<C> {
 }
 
 impl<C: Connection> Floating<C, Live<C>> {
-    pub fn new_live(conn: C, guard: DecrementSizeGuard<C>) -> Self {
+    pub fn new_live(
+        conn: C,
+        extension: Option<Arc<dyn Any + Send + Sync>>,
+        mut guard: DecrementSizeGuard<C>,
+    ) -> Self {
+        guard.mark_open();
         Self {
             inner: Live {
                 raw: conn,
-                created_at: Instant::now(),
+                id: guard.pool.next_connection_id(),
+                created_at: clock::now(),
+                extension,
+                errors: 0,
+                last_error_at: None,
+                normal_permit: false,
+                on_return: Vec::new(),
             },
             guard,
         }
     }
 
     pub fn reattach(self) -> PoolConnection<C> {
-        let Floating { inner, guard } = self;
+        let Floating { mut inner, guard } = self;
 
         let pool = Arc::clone(&guard.pool);
+        inner.normal_permit = guard.normal_permit;
 
         guard.cancel();
+        pool.begin_checkout(inner.id);
         PoolConnection {
+            id: inner.id,
             live: Some(inner),
+            returned: false,
             pool,
         }
     }
@@ -214,23 +481,101 @@ impl<C: Connection> Floating<C, Live<C>> {
     /// Returns `true` if the connection was successfully returned, `false` if it was closed.
     async fn return_to_pool(mut self) -> bool {
         // Immediately close the connection.
-        if self.guard.pool.is_closed() {
+        if self.guard.pool.is_closed() || self.guard.pool.options.disable_reuse {
+            self.close().await;
+            return false;
+        }
+
+        // The pool was shrunk by `Pool::resize()` and still has too many connections. Like the
+        // reaper, this stops at `min_connections`.
+        //
+        // Concurrent returns may all see the same size and close one connection too many, which
+        // isn't worth a compare-and-swap here: a connection that isn't put back is followed by
+        // `min_connections_maintenance()`, which opens it again.
+        let size = self.guard.pool.size();
+        if size > self.guard.pool.max_connections() && size > self.guard.pool.options.min_connections {
             self.close().await;
             return false;
         }
 
+        // The connection itself asked not to be reused.
+        if self.inner.raw.should_discard() {
+            self.close().await;
+            return false;
+        }
+
+        // The connection keeps failing, and is likely to die for good soon.
+        if let Some(max) = self.guard.pool.options.max_connection_errors {
+            if self.inner.errors > max {
+                log_event!(
+                    self.guard.pool.options.log_settings.close_level,
+                    id = self.inner.id,
+                    errors = self.inner.errors,
+                    "connection exceeded `max_connection_errors`"
+                );
+                self.close().await;
+                return false;
+            }
+        }
+
+        if self.guard.pool.options.rollback_on_release {
+            let timeout = self.guard.pool.options.release_timeout;
+            match tokio::time::timeout(timeout, self.inner.raw.rollback_pending()).await {
+                Ok(Ok(())) => (),
+                Ok(Err(error)) => {
+                    log_event!(
+                        self.guard.pool.options.log_settings.release_test_failure_level,
+                        %error,
+                        "error from `rollback_pending`"
+                    );
+                    // The scope may still be open, so the connection can't be reused.
+                    self.guard.pool.record_release_test_failure();
+                    self.close_hard().await;
+                    return false;
+                }
+                Err(_) => {
+                    log_event!(
+                        self.guard.pool.options.log_settings.release_test_failure_level,
+                        ?timeout,
+                        "`rollback_pending` timed out"
+                    );
+                    self.guard.pool.record_release_test_failure();
+                    self.close_hard().await;
+                    return false;
+                }
+            }
+        }
+
         if let Some(test) = &self.guard.pool.options.after_release {
             let meta = self.metadata();
-            match (test)(&mut self.inner.raw, meta).await {
-                Ok(true) => (),
-                Ok(false) => {
+            let timeout = self.guard.pool.options.release_timeout;
+            match tokio::time::timeout(timeout, (test)(&mut self.inner.raw, meta)).await {
+                Ok(Ok(true)) => (),
+                Ok(Ok(false)) => {
+                    self.guard.pool.record_release_test_failure();
                     self.close().await;
                     return false;
                 }
-                Err(error) => {
-                    tracing::warn!(%error, "error from `after_release`");
+                Ok(Err(error)) => {
+                    log_event!(
+                        self.guard.pool.options.log_settings.release_test_failure_level,
+                        %error,
+                        "error from `after_release`"
+                    );
                     // Connection is broken, don't try to gracefully close as
                     // something weird might happen.
+                    self.guard.pool.record_release_test_failure();
+                    self.close_hard().await;
+                    return false;
+                }
+                Err(_) => {
+                    log_event!(
+                        self.guard.pool.options.log_settings.release_test_failure_level,
+                        ?timeout,
+                        "`after_release` timed out"
+                    );
+                    // The hook may have left the connection halfway through a command.
+                    self.guard.pool.record_release_test_failure();
                     self.close_hard().await;
                     return false;
                 }
@@ -255,12 +600,47 @@ impl<C: Connection> Floating<C, Live<C>> {
         //     false
         // } else {
             // if the connection is still viable, release it to the pool
+            for f in std::mem::take(&mut self.inner.on_return) {
+                let timeout = self.guard.pool.options.release_timeout;
+                let raw = &mut self.inner.raw;
+                let cleanup = AssertUnwindSafe(async move { f(raw).await }).catch_unwind();
+                match tokio::time::timeout(timeout, cleanup).await {
+                    Ok(Ok(Ok(()))) => (),
+                    Ok(Ok(Err(error))) => {
+                        log_event!(
+                            self.guard.pool.options.log_settings.release_test_failure_level,
+                            %error,
+                            "error from `on_return`"
+                        );
+                        self.guard.pool.record_release_test_failure();
+                        self.close_hard().await;
+                        return false;
+                    }
+                    Ok(Err(_)) => {
+                        tracing::error!("`on_return` callback panicked");
+                        self.close_hard().await;
+                        return false;
+                    }
+                    Err(_) => {
+                        log_event!(
+                            self.guard.pool.options.log_settings.release_test_failure_level,
+                            ?timeout,
+                            "`on_return` timed out"
+                        );
+                        self.guard.pool.record_release_test_failure();
+                        self.close_hard().await;
+                        return false;
+                    }
+                }
+            }
             self.release();
             true
         // }
     }
 
     pub async fn close(self) {
+        self.guard.pool.notify_close(self.metadata());
+
         // This isn't used anywhere that we care about the return value
         let _ = self.inner.raw.close().await;
 
@@ -268,6 +648,8 @@ impl<C: Connection> Floating<C, Live<C>> {
     }
 
     pub async fn close_hard(self) {
+        self.guard.pool.notify_close(self.metadata());
+
         let _ = self.inner.raw.close_hard().await;
     }
 
@@ -284,7 +666,7 @@ impl<C: Connection> Floating<C, Live<C>> {
 
     pub fn metadata(&self) -> PoolConnectionMetadata {
         PoolConnectionMetadata {
-            age: self.created_at.elapsed(),
+            age: clock::elapsed(self.created_at),
             idle_for: Duration::ZERO,
         }
     }
@@ -296,10 +678,9 @@ impl<C: Connection> Floating<C, Idle<C>> {
         pool: Arc<PoolInner<C>>,
         permit: AsyncSemaphoreReleaser<'_>,
     ) -> Self {
-        Self {
-            inner: idle,
-            guard: DecrementSizeGuard::from_permit(pool, permit),
-        }
+        let mut guard = DecrementSizeGuard::from_permit(pool, permit);
+        guard.open = true;
+        Self { inner: idle, guard }
     }
 
     pub async fn ping(&mut self) -> Result<(), Error> {
@@ -314,21 +695,33 @@ impl<C: Connection> Floating<C, Idle<C>> {
     }
 
     pub async fn close(self) -> DecrementSizeGuard<C> {
+        self.guard.pool.notify_close(self.metadata());
+
         if let Err(error) = self.inner.live.raw.close().await {
-            tracing::debug!(%error, "error occurred while closing the pool connection");
+            log_event!(
+                self.guard.pool.options.log_settings.close_level,
+                %error,
+                "error occurred while closing the pool connection"
+            );
         }
-        self.guard
+        let mut guard = self.guard;
+        guard.mark_closed();
+        guard
     }
 
     pub async fn close_hard(self) -> DecrementSizeGuard<C> {
+        self.guard.pool.notify_close(self.metadata());
+
         let _ = self.inner.live.raw.close_hard().await;
 
-        self.guard
+        let mut guard = self.guard;
+        guard.mark_closed();
+        guard
     }
 
     pub fn metadata(&self) -> PoolConnectionMetadata {
         // Use a single `now` value for consistency.
-        let now = Instant::now();
+        let now = clock::now();
 
         PoolConnectionMetadata {
             // NOTE: the receiver is the later `Instant` and the arg is the earlier
@@ -5,7 +5,7 @@ pub type BoxDynError = Box<dyn StdError + 'static + Send + Sync>;
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum Error {
-    /// Error occurred while parsing a connection string.
+    /// Error occurred while parsing a connection string, or the pool options are invalid.
     #[error("error with configuration: {0}")]
     Configuration(#[source] BoxDynError),
     /// Error communicating with the database backend.
@@ -26,9 +26,86 @@ pub enum Error {
     #[error("attempted to acquire a connection on a closed pool")]
     PoolClosed,
 
+    /// A [`Pool::acquire`] was rejected because too many tasks were already waiting for a
+    /// connection.
+    ///
+    /// See [`PoolOptions::max_pending_acquires`].
+    ///
+    /// [`Pool::acquire`]: crate::Pool::acquire
+    /// [`PoolOptions::max_pending_acquires`]: crate::PoolOptions::max_pending_acquires
+    #[error("too many tasks waiting for a connection")]
+    PoolExhausted,
+
+    /// [`Pool::blocking_acquire`] was called from within an async runtime, where blocking the
+    /// thread could deadlock it.
+    ///
+    /// [`Pool::blocking_acquire`]: crate::Pool::blocking_acquire
+    #[error("`blocking_acquire()` called from within an async runtime, use `acquire().await` instead")]
+    BlockingInRuntime,
+
+    /// New connections kept failing [`PoolOptions::validate_on_connect`], which usually means
+    /// the backend is misconfigured.
+    ///
+    /// [`PoolOptions::validate_on_connect`]: crate::PoolOptions::validate_on_connect
+    #[error("new connections repeatedly failed `validate_on_connect`")]
+    ConnectionRejected,
+
+    /// New connections kept returning an error from [`PoolOptions::validate_on_connect`], e.g.
+    /// because the backend accepts connections but fails every command, as with an
+    /// authentication problem. Holds the error from the last attempt.
+    ///
+    /// [`PoolOptions::validate_on_connect`]: crate::PoolOptions::validate_on_connect
+    #[error("new connections repeatedly failed `validate_on_connect`: {0}")]
+    ConnectFailed(#[source] Box<Error>),
+
+    /// The server returned an unexpected response, e.g. to a [`Connection::ping`].
+    ///
+    /// [`Connection::ping`]: crate::Connection::ping
     #[error("error response from server")]
     ResponseError,
 
+    /// Any other error raised by a connection implementation.
     #[error("error with connection")]
     Other(#[from] anyhow::Error),
 }
+
+impl Error {
+    /// Returns `true` if retrying the operation that failed with this error may succeed.
+    ///
+    /// * Transient: [`PoolTimedOut`][Self::PoolTimedOut] and
+    ///   [`PoolExhausted`][Self::PoolExhausted], as the pool may have room again later, and
+    ///   [`Io`][Self::Io] errors of a kind that comes from a dropped or overloaded connection:
+    ///   `ConnectionRefused`, `ConnectionReset`, `ConnectionAborted`, `NotConnected`,
+    ///   `BrokenPipe`, `UnexpectedEof`, `TimedOut` and `Interrupted`.
+    /// * Terminal: everything else. [`PoolClosed`][Self::PoolClosed],
+    ///   [`Configuration`][Self::Configuration], [`ConnectionRejected`][Self::ConnectionRejected],
+    ///   [`ConnectFailed`][Self::ConnectFailed] and [`BlockingInRuntime`][Self::BlockingInRuntime]
+    ///   fail the same way every time, and
+    ///   [`ResponseError`][Self::ResponseError], [`Other`][Self::Other] and other I/O errors
+    ///   can't be assumed to be transient.
+    pub fn is_retryable(&self) -> bool {
+        use std::io::ErrorKind;
+
+        match self {
+            Error::PoolTimedOut | Error::PoolExhausted => true,
+            Error::Io(e) => matches!(
+                e.kind(),
+                ErrorKind::ConnectionRefused
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::NotConnected
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof
+                    | ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+            ),
+            Error::Configuration(_)
+            | Error::PoolClosed
+            | Error::BlockingInRuntime
+            | Error::ConnectionRejected
+            | Error::ConnectFailed(_)
+            | Error::ResponseError
+            | Error::Other(_) => false,
+        }
+    }
+}
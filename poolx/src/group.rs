@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use crate::conn::Connection;
+use crate::{Acquire, Pool, PoolStatistics};
+
+/// A fixed set of [`Pool`]s with acquires routed between them by key, e.g. one pool per shard.
+///
+/// The shard function maps a key to the index of the pool to use; it's taken modulo the number
+/// of pools, so a plain hash of the key works too. Each pool keeps its own limits and options;
+/// this type only routes acquires between them.
+///
+/// ```rust,ignore
+/// let group = PoolGroup::new(vec![pool_a, pool_b], |user_id: &u64| *user_id as usize);
+/// let conn = group.acquire_for(&user_id).await?;
+/// ```
+pub struct PoolGroup<C: Connection, K: ?Sized> {
+    pools: Vec<Pool<C>>,
+    shard: Arc<dyn Fn(&K) -> usize + Send + Sync>,
+}
+
+impl<C: Connection, K: ?Sized> PoolGroup<C, K> {
+    /// Create a new `PoolGroup` routing keys between `pools` with `shard`.
+    ///
+    /// # Panics
+    /// If `pools` is empty.
+    pub fn new<F>(pools: Vec<Pool<C>>, shard: F) -> Self
+    where
+        F: Fn(&K) -> usize + Send + Sync + 'static,
+    {
+        assert!(!pools.is_empty(), "a `PoolGroup` needs at least one pool");
+
+        Self {
+            pools,
+            shard: Arc::new(shard),
+        }
+    }
+
+    /// Get all the pools in the group, in the order the shard function indexes them.
+    pub fn pools(&self) -> &[Pool<C>] {
+        &self.pools
+    }
+
+    /// Get the pool that `key` is routed to.
+    pub fn pool_for(&self, key: &K) -> &Pool<C> {
+        &self.pools[(self.shard)(key) % self.pools.len()]
+    }
+
+    /// Retrieves a connection from the pool that `key` is routed to.
+    pub fn acquire_for(&self, key: &K) -> Acquire<C> {
+        self.pool_for(key).acquire()
+    }
+
+    /// Returns the statistics of all the pools in the group added together.
+    pub fn statistics(&self) -> PoolStatistics {
+        self.pools
+            .iter()
+            .map(Pool::statistics)
+            .fold(PoolStatistics::default(), |total, stats| PoolStatistics {
+                size: total.size + stats.size,
+                num_idle: total.num_idle + stats.num_idle,
+                num_pending: total.num_pending + stats.num_pending,
+                max_connections: total.max_connections + stats.max_connections,
+                release_test_failures: total.release_test_failures + stats.release_test_failures,
+                reused: total.reused + stats.reused,
+                created: total.created + stats.created,
+            })
+    }
+
+    /// Close all the pools in the group, concurrently.
+    pub async fn close_all(&self) {
+        futures_util::future::join_all(self.pools.iter().map(Pool::close)).await;
+    }
+}
+
+impl<C: Connection, K: ?Sized> Clone for PoolGroup<C, K> {
+    fn clone(&self) -> Self {
+        Self {
+            pools: self.pools.clone(),
+            shard: self.shard.clone(),
+        }
+    }
+}
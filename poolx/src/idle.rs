@@ -0,0 +1,362 @@
+//! Storage for the pool's idle connections.
+//!
+//! Acquires and releases only need a queue, but the reaper needs to find the connections that
+//! are past their `idle_timeout` or `max_lifetime`. With a plain FIFO queue it has to take out
+//! every idle connection to check it and put back the ones it keeps, which is fine for a few
+//! dozen connections but shows with thousands. [`IdleQueueKind::Deadline`] keeps them ordered by
+//! when they expire instead, so the reaper only takes out the ones that are due.
+//!
+//! Neither is a channel that acquires receive from. The pool's semaphore already queues the
+//! waiting acquires fairly and bounds the pool's size, so the idle queue only has to hand over a
+//! connection once a permit is granted, which a lock-free queue does for less than a channel's
+//! send and receive. A channel would also leave the pool no say in which connection goes out,
+//! or a way to open one instead of waiting.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crossbeam_queue::SegQueue;
+
+use crate::connection::Idle;
+use crate::inner::jitter_fraction;
+use crate::{clock, Connection, PoolOptions};
+
+/// How a pool stores its idle connections, set with
+/// [`PoolOptions::idle_queue`][crate::PoolOptions::idle_queue].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum IdleQueueKind {
+    /// A FIFO queue, built on a lock-free queue.
+    ///
+    /// Acquires and releases never take a lock, but each run of the reaper, and each call to
+    /// [`Pool::idle_connections_info()`][crate::Pool::idle_connections_info], takes out and
+    /// checks every idle connection, which takes time linear in the number of idle connections
+    /// and briefly hides them from acquires.
+    #[default]
+    Fifo,
+    /// A queue ordered by when each connection expires, by
+    /// [`idle_timeout`][crate::PoolOptions::idle_timeout] or
+    /// [`max_lifetime`][crate::PoolOptions::max_lifetime], behind a mutex.
+    ///
+    /// The reaper only takes out the connections that are due, in logarithmic time each, and
+    /// leaves the others alone. Acquires take the connection closest to expiring. Pushing and
+    /// popping take a short lock, so prefer this for pools with many connections, where the
+    /// reaper's scans cost more than the lock.
+    Deadline,
+}
+
+/// The idle connections of a pool.
+///
+/// Implementations don't track the number of connections they hold: the pool counts them in
+/// `num_idle`, which has to stay in step with the semaphore anyway.
+pub(crate) trait IdleQueue<C: Connection>: Send + Sync {
+    fn push(&self, idle: Idle<C>);
+
+    /// Take the next connection for an acquire.
+    fn pop(&self) -> Option<Idle<C>>;
+
+    /// Take a connection that's past its deadline at `now`, if there's one and the queue can
+    /// tell without scanning.
+    ///
+    /// Returns `None` if the queue isn't ordered by deadline, see
+    /// [`is_deadline_ordered()`][Self::is_deadline_ordered].
+    fn pop_expired(&self, now: Instant) -> Option<Idle<C>>;
+
+    /// Whether [`pop_expired()`][Self::pop_expired] finds every expired connection, so the
+    /// reaper doesn't need to scan the queue.
+    fn is_deadline_ordered(&self) -> bool;
+
+    /// Take a connection whose last keepalive (or going idle) was at or before `due`, if there's
+    /// one and the queue can find it without taking out the others.
+    fn pop_keepalive_due(&self, due: Instant) -> Option<Idle<C>>;
+
+    fn is_empty(&self) -> bool;
+
+    /// List the connections in the queue, in about the order they'd be popped, without taking
+    /// any out.
+    ///
+    /// Returns `None` if the queue can't look at its connections without taking them out.
+    fn snapshot(&self) -> Option<Vec<IdleInfo>>;
+}
+
+/// What [`IdleQueue::snapshot()`] reports about an idle connection.
+pub(crate) struct IdleInfo {
+    pub(crate) id: u64,
+    pub(crate) created_at: Instant,
+    pub(crate) idle_since: Instant,
+}
+
+impl IdleInfo {
+    pub(crate) fn of<C: Connection>(idle: &Idle<C>) -> Self {
+        IdleInfo {
+            id: idle.live.id,
+            created_at: idle.live.created_at,
+            idle_since: idle.idle_since,
+        }
+    }
+}
+
+pub(crate) fn new_queue<C: Connection>(options: &PoolOptions<C>) -> Box<dyn IdleQueue<C>> {
+    let queue: Box<dyn IdleQueue<C>> = match options.idle_queue {
+        IdleQueueKind::Fifo => Box::new(FifoQueue {
+            conns: SegQueue::new(),
+        }),
+        IdleQueueKind::Deadline => Box::new(DeadlineQueue {
+            conns: Mutex::new(DeadlineConns {
+                by_deadline: BTreeMap::new(),
+                by_keepalive: options.keepalive_interval.map(|_| BTreeMap::new()),
+            }),
+            idle_timeout: options.idle_timeout,
+            idle_timeout_jitter: options.idle_timeout_jitter,
+            max_lifetime: options.max_lifetime,
+        }),
+    };
+
+    if !options.prefer_healthy {
+        return queue;
+    }
+
+    Box::new(HealthyFirstQueue {
+        healthy: queue,
+        failed: Mutex::new(BTreeMap::new()),
+        window: options.prefer_healthy_window,
+    })
+}
+
+/// The `idle_timeout` of the connection with `id`, shortened by the jitter if any.
+pub(crate) fn jittered_idle_timeout(timeout: Duration, jitter: f64, id: u64) -> Duration {
+    if jitter > 0.0 {
+        timeout.mul_f64(1.0 - jitter * jitter_fraction(id))
+    } else {
+        timeout
+    }
+}
+
+struct FifoQueue<C: Connection> {
+    conns: SegQueue<Idle<C>>,
+}
+
+impl<C: Connection> IdleQueue<C> for FifoQueue<C> {
+    fn push(&self, idle: Idle<C>) {
+        self.conns.push(idle);
+    }
+
+    fn pop(&self) -> Option<Idle<C>> {
+        self.conns.pop()
+    }
+
+    fn pop_expired(&self, _now: Instant) -> Option<Idle<C>> {
+        None
+    }
+
+    fn is_deadline_ordered(&self) -> bool {
+        false
+    }
+
+    fn pop_keepalive_due(&self, due: Instant) -> Option<Idle<C>> {
+        // Connections go in when they go idle or get a keepalive, so the front is almost always
+        // the one waiting longest. When it isn't due, it goes to the back rather than digging
+        // further: any due ones behind it are left for the next run.
+        let idle = self.pop()?;
+        if idle.keepalive_at <= due {
+            return Some(idle);
+        }
+
+        self.push(idle);
+        None
+    }
+
+    fn is_empty(&self) -> bool {
+        self.conns.is_empty()
+    }
+
+    fn snapshot(&self) -> Option<Vec<IdleInfo>> {
+        // a `SegQueue` can only be looked into by popping
+        None
+    }
+}
+
+struct DeadlineQueue<C: Connection> {
+    conns: Mutex<DeadlineConns<C>>,
+    idle_timeout: Option<Duration>,
+    idle_timeout_jitter: f64,
+    max_lifetime: Option<Duration>,
+}
+
+impl<C: Connection> DeadlineQueue<C> {
+    /// When `idle` is due for reaping, or when it went idle if it never is.
+    fn deadline(&self, idle: &Idle<C>) -> Instant {
+        let idle_deadline = self.idle_timeout.map(|timeout| {
+            let timeout = jittered_idle_timeout(timeout, self.idle_timeout_jitter, idle.live.id);
+            idle.idle_since + timeout
+        });
+        let lifetime_deadline = self.max_lifetime.map(|max| idle.live.created_at + max);
+
+        match (idle_deadline, lifetime_deadline) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(deadline), None) | (None, Some(deadline)) => deadline,
+            (None, None) => idle.idle_since,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DeadlineConns<C>> {
+        self.conns.lock().expect("BUG: panicked while holding a lock")
+    }
+}
+
+struct DeadlineConns<C: Connection> {
+    /// Keyed by deadline, and then by id to tell apart connections expiring at the same time.
+    by_deadline: BTreeMap<(Instant, u64), Idle<C>>,
+    /// The deadline of each connection keyed by `keepalive_at` and then by id, if the pool has
+    /// a `keepalive_interval`.
+    by_keepalive: Option<BTreeMap<(Instant, u64), Instant>>,
+}
+
+impl<C: Connection> DeadlineConns<C> {
+    fn remove(&mut self, deadline: Instant, id: u64) -> Option<Idle<C>> {
+        let idle = self.by_deadline.remove(&(deadline, id))?;
+        if let Some(by_keepalive) = &mut self.by_keepalive {
+            by_keepalive.remove(&(idle.keepalive_at, id));
+        }
+        Some(idle)
+    }
+}
+
+impl<C: Connection> IdleQueue<C> for DeadlineQueue<C> {
+    fn push(&self, idle: Idle<C>) {
+        let deadline = self.deadline(&idle);
+        let mut conns = self.lock();
+        if let Some(by_keepalive) = &mut conns.by_keepalive {
+            by_keepalive.insert((idle.keepalive_at, idle.live.id), deadline);
+        }
+        conns.by_deadline.insert((deadline, idle.live.id), idle);
+    }
+
+    fn pop(&self) -> Option<Idle<C>> {
+        let mut conns = self.lock();
+        let &(deadline, id) = conns.by_deadline.keys().next()?;
+        conns.remove(deadline, id)
+    }
+
+    fn pop_expired(&self, now: Instant) -> Option<Idle<C>> {
+        if self.idle_timeout.is_none() && self.max_lifetime.is_none() {
+            return None;
+        }
+
+        let mut conns = self.lock();
+        let &(deadline, id) = conns.by_deadline.keys().next()?;
+        // strictly past it, like `is_beyond_idle_timeout()`
+        if deadline < now {
+            conns.remove(deadline, id)
+        } else {
+            None
+        }
+    }
+
+    fn is_deadline_ordered(&self) -> bool {
+        true
+    }
+
+    fn pop_keepalive_due(&self, due: Instant) -> Option<Idle<C>> {
+        let mut conns = self.lock();
+        let (&(keepalive_at, id), &deadline) = conns.by_keepalive.as_ref()?.iter().next()?;
+        if keepalive_at <= due {
+            conns.remove(deadline, id)
+        } else {
+            None
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lock().by_deadline.is_empty()
+    }
+
+    fn snapshot(&self) -> Option<Vec<IdleInfo>> {
+        Some(self.lock().by_deadline.values().map(IdleInfo::of).collect())
+    }
+}
+
+/// Hands out the connections that never reported an error first, from the queue of the
+/// configured kind, for `PoolOptions::prefer_healthy`.
+struct HealthyFirstQueue<C: Connection> {
+    healthy: Box<dyn IdleQueue<C>>,
+    /// Keyed by when the connection last reported an error, and then by id.
+    failed: Mutex<BTreeMap<(Instant, u64), Idle<C>>>,
+    /// `PoolOptions::prefer_healthy_window`: how long an error keeps a connection in `failed`.
+    window: Option<Duration>,
+}
+
+impl<C: Connection> HealthyFirstQueue<C> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<(Instant, u64), Idle<C>>> {
+        self.failed.lock().expect("BUG: panicked while holding a lock")
+    }
+
+    /// Whether an error reported at `at` still counts against a connection.
+    fn is_recent(&self, at: Instant) -> bool {
+        self.window.is_none_or(|window| clock::elapsed(at) < window)
+    }
+
+    /// Move the connections whose last error fell out of the window back to `healthy`.
+    fn forgive(&self) {
+        if self.window.is_none() {
+            return;
+        }
+
+        let mut failed = self.lock();
+        // ordered by when the error was reported, so the ones to move come first
+        while let Some(entry) = failed.first_entry() {
+            if self.is_recent(entry.key().0) {
+                break;
+            }
+            self.healthy.push(entry.remove());
+        }
+    }
+}
+
+impl<C: Connection> IdleQueue<C> for HealthyFirstQueue<C> {
+    fn push(&self, idle: Idle<C>) {
+        match idle.live.last_error_at {
+            Some(at) if self.is_recent(at) => {
+                let key = (at, idle.live.id);
+                self.lock().insert(key, idle);
+            }
+            _ => self.healthy.push(idle),
+        }
+    }
+
+    fn pop(&self) -> Option<Idle<C>> {
+        self.forgive();
+        self.healthy
+            .pop()
+            .or_else(|| self.lock().pop_first().map(|(_, idle)| idle))
+    }
+
+    fn pop_expired(&self, _now: Instant) -> Option<Idle<C>> {
+        // the failed connections aren't ordered by deadline
+        None
+    }
+
+    fn is_deadline_ordered(&self) -> bool {
+        false
+    }
+
+    fn pop_keepalive_due(&self, due: Instant) -> Option<Idle<C>> {
+        self.healthy.pop_keepalive_due(due).or_else(|| {
+            let mut failed = self.lock();
+            // there are only ever a few of these, a scan is fine
+            let key = *failed.iter().find(|(_, idle)| idle.keepalive_at <= due)?.0;
+            failed.remove(&key)
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.healthy.is_empty() && self.lock().is_empty()
+    }
+
+    fn snapshot(&self) -> Option<Vec<IdleInfo>> {
+        let mut info = self.healthy.snapshot()?;
+        info.extend(self.lock().values().map(IdleInfo::of));
+        Some(info)
+    }
+}
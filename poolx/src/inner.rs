@@ -1,38 +1,112 @@
 use super::connection::{Floating, Idle, Live};
 use crate::error::Error;
-use crossbeam_queue::ArrayQueue;
+use crate::idle::{self, IdleInfo, IdleQueue};
 
 use crate::sync::{AsyncSemaphore, AsyncSemaphoreReleaser};
 
 use std::cmp;
+use std::panic::AssertUnwindSafe;
 use std::future::Future;
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+#[cfg(debug_assertions)]
+use std::sync::atomic::AtomicIsize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock, Weak};
 use std::task::Poll;
 
 use futures_util::future::{self};
-use futures_util::FutureExt;
+use futures_util::{FutureExt, StreamExt};
 use std::time::{Duration, Instant};
-use crate::{CloseEvent, deadline_as_timeout, Pool, PoolConnectionMetadata, PoolOptions};
+use crate::{AcquirePriority, AcquireSource, AcquireTiming, CloseEvent, ConnectionInfo, deadline_as_timeout, Pool, PoolConnectionMetadata, PoolOptions};
+use crate::clock;
 use crate::conn::{Connection, ConnectOptions};
 
+/// How many new connections in a row may fail `validate_on_connect` before `connect()` gives up.
+const MAX_CONNECT_REJECTIONS: u32 = 3;
+
 pub(crate) struct PoolInner<C: Connection> {
     pub(super) connect_options: RwLock<Arc<<C as Connection>::Options>>,
-    pub(super) idle_conns: ArrayQueue<Idle<C>>,
+    pub(super) idle_conns: Box<dyn IdleQueue<C>>,
     pub(super) semaphore: AsyncSemaphore,
+    /// With `options.reserved_connections`, the connections normal-priority acquires may have
+    /// checked out at once, which leaves the rest of `semaphore` to high-priority acquires.
+    normal_permits: Option<AsyncSemaphore>,
     pub(super) size: AtomicU32,
+    /// Starts out as `options.max_connections` but can be changed with `Pool::resize()`.
+    ///
+    /// With a shared semaphore, this is the semaphore's capacity.
+    max_connections: AtomicU32,
+    /// Permits still to be taken out of `semaphore` after shrinking `max_connections`,
+    /// paid off as checked-out connections release theirs.
+    permit_debt: AtomicU32,
     pub(super) num_idle: AtomicUsize,
+    num_pending: AtomicUsize,
+    /// The number of connections open right now. Unlike `size`, this leaves out the slots of
+    /// connections still being opened.
+    num_open: AtomicU32,
+    /// The number of returned connections closed because they failed a check on release.
+    release_test_failures: AtomicU64,
+    /// The number of acquires served from the idle queue, and by opening a new connection.
+    acquires_reused: AtomicU64,
+    acquires_created: AtomicU64,
+    /// What was last reported to `options.on_saturation_change`, and since when it no longer
+    /// holds, if it doesn't.
+    saturation: Mutex<Saturation>,
+    next_connection_id: AtomicU64,
+    /// When each checked-out connection was acquired, by id, if `options.max_checkout_duration`
+    /// is set.
+    checkouts: Mutex<HashMap<u64, Checkout>>,
     is_closed: AtomicBool,
+    /// Set once `close()` has drained the pool. Concurrent `close()` calls all wait on the same
+    /// drain, and if the task running it is cancelled, one of the others takes over.
+    drained: tokio::sync::OnceCell<()>,
+    /// Set by `Pool::pause_reaping()`.
+    reaping_paused: AtomicBool,
+    /// Set when the most recent attempt to open a connection failed.
+    connect_failing: AtomicBool,
+    /// The error from the most recent failed attempt to open a connection, and when it
+    /// happened, if it hasn't succeeded since.
+    last_connect_error: Mutex<Option<(String, Instant)>>,
     pub(super) on_closed: event_listener::Event,
+    /// Notified whenever `num_open` changes, and when the pool is closed.
+    num_open_changed: tokio::sync::Notify,
+    /// Notified whenever a connection is returned to the idle queue, for acquires waiting on
+    /// `options.shared_semaphore`.
+    connection_released: tokio::sync::Notify,
+    /// Notified whenever a connection gives up its place in `size` or `max_connections` is
+    /// raised, for acquires holding a permit they couldn't open a connection with.
+    slot_freed: tokio::sync::Notify,
+    /// The number of `DecrementSizeGuard`s alive, to catch accounting bugs in tests.
+    #[cfg(debug_assertions)]
+    pub(super) outstanding_guards: AtomicIsize,
+    /// The runtime the pool was created on, for use from outside of it.
+    pub(super) runtime: tokio::runtime::Handle,
+    /// The instruments reporting to `options.meter`, if set.
+    #[cfg(feature = "opentelemetry")]
+    metrics: Option<crate::metrics::PoolMetrics>,
+    /// This pool, for the tasks it spawns on its own, like the ones reporting a saturation
+    /// change after `options.saturation_debounce`.
+    weak: Weak<Self>,
     pub(super) options: PoolOptions<C>,
 }
 
+#[derive(Default)]
+struct Saturation {
+    reported: bool,
+    changed_at: Option<Instant>,
+}
+
 impl<C: Connection> PoolInner<C> {
     pub(super) fn new_arc(
         options: PoolOptions<C>,
         connect_options: <C as Connection>::Options,
     ) -> Arc<Self> {
-        let capacity = options.max_connections as usize;
+        // `max_connections` is ignored with a shared semaphore, which caps the pool instead.
+        let max_connections = match &options.shared_semaphore {
+            Some(shared) => u32::try_from(shared.capacity()).unwrap_or(u32::MAX),
+            None => options.max_connections,
+        };
+        let capacity = max_connections as usize;
 
         let semaphore_capacity = if let Some(parent) = &options.parent_pool {
             assert!(options.max_connections <= parent.options().max_connections);
@@ -43,20 +117,50 @@ impl<C: Connection> PoolInner<C> {
             capacity
         };
 
-        let pool = Self {
+        // Cyclic so the metrics' gauges and the pool's own tasks can refer to the pool without
+        // keeping it alive.
+        let pool = Arc::new_cyclic(|weak| Self {
             connect_options: RwLock::new(Arc::new(connect_options)),
-            idle_conns: ArrayQueue::new(capacity),
+            idle_conns: idle::new_queue(&options),
             semaphore: AsyncSemaphore::new(semaphore_capacity),
+            normal_permits: (options.reserved_connections > 0)
+                .then(|| AsyncSemaphore::new(capacity - options.reserved_connections as usize)),
             size: AtomicU32::new(0),
+            max_connections: AtomicU32::new(max_connections),
+            permit_debt: AtomicU32::new(0),
             num_idle: AtomicUsize::new(0),
+            num_pending: AtomicUsize::new(0),
+            num_open: AtomicU32::new(0),
+            release_test_failures: AtomicU64::new(0),
+            acquires_reused: AtomicU64::new(0),
+            acquires_created: AtomicU64::new(0),
+            saturation: Mutex::new(Saturation::default()),
+            next_connection_id: AtomicU64::new(0),
+            checkouts: Mutex::new(HashMap::new()),
             is_closed: AtomicBool::new(false),
+            drained: tokio::sync::OnceCell::new(),
+            reaping_paused: AtomicBool::new(false),
+            connect_failing: AtomicBool::new(false),
+            last_connect_error: Mutex::new(None),
             on_closed: event_listener::Event::new(),
+            num_open_changed: tokio::sync::Notify::new(),
+            connection_released: tokio::sync::Notify::new(),
+            slot_freed: tokio::sync::Notify::new(),
+            #[cfg(debug_assertions)]
+            outstanding_guards: AtomicIsize::new(0),
+            runtime: tokio::runtime::Handle::current(),
+            #[cfg(feature = "opentelemetry")]
+            metrics: options
+                .meter
+                .as_ref()
+                .map(|meter| crate::metrics::PoolMetrics::new(meter, weak)),
+            weak: weak.clone(),
             options,
-        };
-
-        let pool = Arc::new(pool);
+        });
 
         spawn_maintenance_tasks(&pool);
+        spawn_leak_detection(&pool);
+        spawn_keepalive(&pool);
 
         pool
     }
@@ -65,6 +169,97 @@ impl<C: Connection> PoolInner<C> {
         self.size.load(Ordering::Acquire)
     }
 
+    pub(super) fn max_connections(&self) -> u32 {
+        self.max_connections.load(Ordering::Acquire)
+    }
+
+    pub(super) fn resize(&self, new_max: u32) {
+        // Capped by the shared semaphore instead.
+        if self.options.shared_semaphore.is_some() {
+            return;
+        }
+
+        let old_max = self.max_connections.swap(new_max, Ordering::AcqRel);
+        if new_max > old_max {
+            self.slot_freed.notify_waiters();
+        }
+
+        // A child pool has no permits of its own, it steals them from the parent as needed.
+        if self.parent().is_some() || self.is_closed() {
+            return;
+        }
+
+        if new_max > old_max {
+            let mut grow = new_max - old_max;
+
+            // Cancel out a shrink that hasn't finished yet first.
+            if let Ok(debt) = self.permit_debt.fetch_update(Ordering::AcqRel, Ordering::Acquire, |debt| {
+                Some(debt.saturating_sub(grow))
+            }) {
+                grow -= debt.min(grow);
+            }
+
+            self.semaphore.release(grow as usize);
+        } else {
+            let mut shrink = old_max - new_max;
+
+            // Take whatever permits are free right now...
+            while shrink > 0 {
+                let Some(permit) = self.semaphore.try_acquire(1) else {
+                    break;
+                };
+                permit.disarm();
+                shrink -= 1;
+            }
+
+            // ...and the rest as checked-out connections come back.
+            self.permit_debt.fetch_add(shrink, Ordering::AcqRel);
+        }
+
+        self.update_saturation();
+    }
+
+    /// Close idle connections until the pool has `target` connections, for `Pool::shrink_to()`.
+    pub(super) async fn shrink_to(self: &Arc<Self>, target: u32) -> usize {
+        let target = target.max(self.options.min_connections);
+
+        let mut closed = 0;
+        while self.size() > target {
+            let Some(conn) = self.try_acquire() else {
+                break;
+            };
+            // the guard decrements the size once the connection is closed
+            let _ = conn.close().await;
+            closed += 1;
+        }
+
+        closed
+    }
+
+    /// Count a connection as closed, giving back its permit from `options.shared_semaphore`.
+    fn decrement_size(&self) {
+        self.size.fetch_sub(1, Ordering::AcqRel);
+
+        if let Some(shared) = &self.options.shared_semaphore {
+            shared.release(1);
+        }
+
+        self.slot_freed.notify_waiters();
+        self.update_saturation();
+    }
+
+    /// Return a permit to `self.semaphore`, unless it's owed because the pool was shrunk.
+    fn release_permit(&self) {
+        if self
+            .permit_debt
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |debt| debt.checked_sub(1))
+            .is_err()
+        {
+            self.semaphore.release(1);
+        }
+        self.update_saturation();
+    }
+
     pub(super) fn num_idle(&self) -> usize {
         // We don't use `self.idle_conns.len()` as it waits for the internal
         // head and tail pointers to stop changing for a moment before calculating the length,
@@ -74,31 +269,217 @@ impl<C: Connection> PoolInner<C> {
         self.num_idle.load(Ordering::Acquire)
     }
 
+    pub(super) fn num_pending(&self) -> usize {
+        self.num_pending.load(Ordering::Acquire)
+    }
+
+    pub(super) fn release_test_failures(&self) -> u64 {
+        self.release_test_failures.load(Ordering::Relaxed)
+    }
+
+    /// Count a returned connection that is about to be closed for failing a check on release.
+    pub(super) fn record_release_test_failure(&self) {
+        self.release_test_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub(super) fn is_closed(&self) -> bool {
         self.is_closed.load(Ordering::Acquire)
     }
 
+    pub(super) fn next_connection_id(&self) -> u64 {
+        self.next_connection_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Take a snapshot of the metadata of the connections currently in the idle queue.
+    ///
+    /// If the queue can't look at them in place, they're taken out and put back in the same
+    /// order, like the reaper does, so acquires wait for them in the meantime.
+    pub(super) fn idle_connections_info(self: &Arc<Self>) -> Vec<ConnectionInfo> {
+        let snapshot = self.idle_conns.snapshot().unwrap_or_else(|| {
+            // only connections waiting in the queue
+            let conns: Vec<_> = (0..self.num_idle()).map_while(|_| self.try_acquire()).collect();
+            let info = conns.iter().map(|conn| IdleInfo::of(conn)).collect();
+            for conn in conns {
+                // without resetting how long they've been idle
+                self.release_idle(conn);
+            }
+            info
+        });
+
+        let now = clock::now();
+        snapshot
+            .into_iter()
+            .map(|info| ConnectionInfo {
+                id: info.id,
+                age: now.saturating_duration_since(info.created_at),
+                idle_for: now.saturating_duration_since(info.idle_since),
+            })
+            .collect()
+    }
+
+    /// Start tracking a checkout of connection `id`, for leak detection.
+    pub(super) fn begin_checkout(&self, id: u64) {
+        if self.options.max_checkout_duration.is_none() {
+            return;
+        }
+
+        self.checkouts
+            .lock()
+            .expect("BUG: panicked while holding a lock")
+            .insert(id, Checkout { since: clock::now(), reported: false });
+    }
+
+    /// Stop tracking a checkout of connection `id`.
+    pub(super) fn end_checkout(&self, id: u64) {
+        if self.options.max_checkout_duration.is_none() {
+            return;
+        }
+
+        self.checkouts
+            .lock()
+            .expect("BUG: panicked while holding a lock")
+            .remove(&id);
+    }
+
+    /// Report checkouts that have gone on for longer than `max`, once each.
+    fn report_leaks(&self, max: Duration) {
+        let leaks = self
+            .checkouts
+            .lock()
+            .expect("BUG: panicked while holding a lock")
+            .iter_mut()
+            .filter(|(_, checkout)| !checkout.reported && clock::elapsed(checkout.since) > max)
+            .map(|(id, checkout)| {
+                checkout.reported = true;
+                (*id, clock::elapsed(checkout.since))
+            })
+            .collect::<Vec<_>>();
+
+        // Don't hold the lock while calling out.
+        for (id, checked_out_for) in leaks {
+            tracing::warn!(
+                connection_id = id,
+                ?checked_out_for,
+                "connection has been checked out for longer than `max_checkout_duration`, it may have been leaked"
+            );
+
+            if let Some(callback) = &self.options.on_leak {
+                if std::panic::catch_unwind(AssertUnwindSafe(|| callback(id, checked_out_for))).is_err() {
+                    tracing::error!("`on_leak` callback panicked");
+                }
+            }
+        }
+    }
+
+    pub(super) fn is_connect_failing(&self) -> bool {
+        self.connect_failing.load(Ordering::Acquire)
+    }
+
+    pub(super) fn last_connect_error(&self) -> Option<(String, Instant)> {
+        self.last_connect_error
+            .lock()
+            .expect("BUG: panicked while holding a lock")
+            .clone()
+    }
+
+    fn record_connect_error(&self, error: String) {
+        *self
+            .last_connect_error
+            .lock()
+            .expect("BUG: panicked while holding a lock") = Some((error, clock::now()));
+
+        self.connect_failing.store(true, Ordering::Release);
+    }
+
+    fn record_connect_success(&self) {
+        // Only take the lock if there is something to clear.
+        if self.connect_failing.swap(false, Ordering::AcqRel) {
+            *self
+                .last_connect_error
+                .lock()
+                .expect("BUG: panicked while holding a lock") = None;
+        }
+    }
+
+    pub(super) fn pause_reaping(&self) {
+        self.reaping_paused.store(true, Ordering::Release);
+    }
+
+    pub(super) fn resume_reaping(self: &Arc<Self>) {
+        if !self.reaping_paused.swap(false, Ordering::AcqRel) || self.is_closed() {
+            return;
+        }
+
+        // Don't wait for the next scheduled run to evict what overstayed during the pause.
+        // On the pool's runtime, as this may be called from outside of it, e.g. alongside
+        // `Pool::blocking_acquire()`.
+        let pool = self.clone();
+        self.runtime.spawn(async move { do_reap(&pool).await });
+    }
+
+    pub(super) fn is_reaping_paused(&self) -> bool {
+        self.reaping_paused.load(Ordering::Acquire)
+    }
+
     fn mark_closed(&self) {
         self.is_closed.store(true, Ordering::Release);
         self.on_closed.notify(usize::MAX);
+        self.num_open_changed.notify_waiters();
+    }
+
+    /// Wait until the pool has at least `min_connections` open, or is closed.
+    pub(super) async fn wait_min_connections(&self) {
+        loop {
+            // Register before checking so an open in between isn't missed.
+            let notified = self.num_open_changed.notified();
+            futures_util::pin_mut!(notified);
+            notified.as_mut().enable();
+
+            let min = cmp::min(self.options.min_connections, self.max_connections());
+            if self.num_open.load(Ordering::Acquire) >= min || self.is_closed() {
+                return;
+            }
+
+            notified.await;
+        }
     }
 
     pub(super) fn close<'a>(self: &'a Arc<Self>) -> impl Future<Output=()> + 'a {
         self.mark_closed();
 
+        // Forgive any outstanding shrink so waiting for every permit below can finish.
+        let debt = self.permit_debt.swap(0, Ordering::AcqRel);
+        self.semaphore.release(debt as usize);
+
         async move {
-            for permits in 1..=self.options.max_connections {
-                // Close any currently idle connections in the pool.
-                while let Some(idle) = self.idle_conns.pop() {
-                    let _ = idle.live.float((*self).clone()).close().await;
-                }
+            self.drained.get_or_init(|| self.drain()).await;
+        }
+    }
 
-                if self.size() == 0 {
-                    break;
+    /// Close the idle connections and wait for the checked-out ones to come back and be closed.
+    ///
+    /// Only run by one `close()` at a time.
+    async fn drain(self: &Arc<Self>) {
+        for permits in 1..=self.max_connections() {
+            // Close any currently idle connections in the pool. Idle connections don't hold a
+            // permit, so take one for each like an acquire would: closing it gives the permit
+            // back along with the connection's size slot.
+            while let Some(permit) = self.semaphore.try_acquire(1) {
+                match self.pop_idle(permit) {
+                    Ok(idle) => {
+                        let _ = idle.close().await;
+                    }
+                    Err(_) => break,
                 }
+            }
 
-                // Wait for all permits to be released.
-                let _permits = self.semaphore.acquire(permits).await;
+            if self.size() == 0 {
+                break;
+            }
+
+            // Wait for all permits to be released.
+            if self.semaphore.acquire(permits).await.is_err() {
+                break;
             }
         }
     }
@@ -113,17 +494,45 @@ impl<C: Connection> PoolInner<C> {
     ///
     /// If we steal a permit from the parent but *don't* open a connection,
     /// it should be returned to the parent.
-    async fn acquire_permit<'a>(self: &'a Arc<Self>) -> Result<AsyncSemaphoreReleaser<'a>, Error> {
+    ///
+    /// A normal-priority acquire first takes one of `self.normal_permits`, if there's a reserve,
+    /// which is returned along with it.
+    async fn acquire_permit<'a>(
+        self: &'a Arc<Self>,
+        priority: AcquirePriority,
+    ) -> Result<(AsyncSemaphoreReleaser<'a>, Option<AsyncSemaphoreReleaser<'a>>), Error> {
+        // An acquire that gets its permits right away never waits, so it isn't pending.
+        if let Some(permits) = self.try_acquire_permit(priority) {
+            return Ok(permits);
+        }
+
+        // Decremented on drop, so cancelled acquires stop counting as pending too.
+        let pending = PendingGuard::new(self);
+
+        let normal_permit = match (&self.normal_permits, priority) {
+            (Some(normal), AcquirePriority::Normal) => {
+                Some(self.close_event().do_until(normal.acquire(1)).await??)
+            }
+            _ => None,
+        };
+
+        if let Some(max_pending) = self.options.max_pending_acquires {
+            // Tasks that can get a permit right away never really queue.
+            if pending.ahead >= max_pending && self.semaphore.permits() == 0 {
+                return Err(Error::PoolExhausted);
+            }
+        }
+
         let parent = self
             .parent()
             // If we're already at the max size, we shouldn't try to steal from the parent.
             // This is just going to cause unnecessary churn in `acquire()`.
-            .filter(|_| self.size() < self.options.max_connections);
+            .filter(|_| self.size() < self.max_connections());
 
         let acquire_self = self.semaphore.acquire(1).fuse();
         let mut close_event = self.close_event();
 
-        if let Some(parent) = parent {
+        let permit = if let Some(parent) = parent {
             let acquire_parent = parent.0.semaphore.acquire(1);
             let parent_close_event = parent.0.close_event();
 
@@ -142,18 +551,20 @@ impl<C: Connection> PoolInner<C> {
                 }
 
                 if parent_close_event.as_mut().poll(cx).is_ready() {
-                    // Propagate the parent's close event to the child.
-                    let _ = self.close();
+                    // Propagate the parent's close event to the child. Only the synchronous part of
+                    // `close()` is needed here: the child's idle connections are drained by its
+                    // own `close()`.
+                    drop(self.close());
                     return Poll::Ready(Err(Error::PoolClosed));
                 }
 
                 if let Poll::Ready(permit) = acquire_self.as_mut().poll(cx) {
-                    return Poll::Ready(Ok(permit));
+                    return Poll::Ready(permit);
                 }
 
                 // Don't try the parent right away.
                 if poll_parent {
-                    acquire_parent.as_mut().poll(cx).map(Ok)
+                    acquire_parent.as_mut().poll(cx)
                 } else {
                     poll_parent = true;
                     cx.waker().wake_by_ref();
@@ -162,8 +573,23 @@ impl<C: Connection> PoolInner<C> {
             })
                 .await
         } else {
-            close_event.do_until(acquire_self).await
-        }
+            close_event.do_until(acquire_self).await?
+        }?;
+
+        Ok((permit, normal_permit))
+    }
+
+    /// Take the permits `acquire_permit()` waits for, if they're available right away.
+    fn try_acquire_permit(
+        &self,
+        priority: AcquirePriority,
+    ) -> Option<(AsyncSemaphoreReleaser<'_>, Option<AsyncSemaphoreReleaser<'_>>)> {
+        let normal_permit = match (&self.normal_permits, priority) {
+            (Some(normal), AcquirePriority::Normal) => Some(normal.try_acquire(1)?),
+            _ => None,
+        };
+
+        Some((self.semaphore.try_acquire(1)?, normal_permit))
     }
 
     fn parent(&self) -> Option<&Pool<C>> {
@@ -181,6 +607,103 @@ impl<C: Connection> PoolInner<C> {
         self.pop_idle(permit).ok()
     }
 
+    /// Like `try_acquire()`, but for a caller of the pool, leaving the reserved connections to
+    /// high-priority acquires.
+    pub(super) fn try_acquire_normal(self: &Arc<Self>) -> Option<Floating<C, Idle<C>>> {
+        let Some(normal) = &self.normal_permits else {
+            let conn = self.try_acquire()?;
+            self.notify_acquire(AcquireSource::Reused);
+            return Some(conn);
+        };
+
+        let normal_permit = normal.try_acquire(1)?;
+        let mut conn = self.try_acquire()?;
+        conn.guard.hold_normal_permit(normal_permit);
+        self.notify_acquire(AcquireSource::Reused);
+        Some(conn)
+    }
+
+    /// Give back a permit from `self.normal_permits`, held by a guard.
+    fn release_normal_permit(&self) {
+        if let Some(normal) = &self.normal_permits {
+            normal.release(1);
+        }
+    }
+
+    /// Take an idle connection for `Pool::acquire()` if that doesn't need to wait for anything,
+    /// so the acquire can resolve on its first poll.
+    ///
+    /// Returns `None` whenever `acquire_timed()` might have to await: for a permit, a ping, a
+    /// `before_acquire` hook, or a new connection. The caller then does the whole acquire.
+    pub(super) fn try_acquire_ready(
+        self: &Arc<Self>,
+        test: bool,
+        priority: AcquirePriority,
+    ) -> Option<Floating<C, Live<C>>> {
+        let options = &self.options;
+        if test
+            || options.before_acquire.is_some()
+            || options.before_acquire_sync.is_some()
+            || options.on_slow_acquire.is_some()
+            || (options.prefer_grow_below_min && self.size() < options.min_connections)
+        {
+            return None;
+        }
+
+        #[cfg(feature = "opentelemetry")]
+        if self.metrics.is_some() {
+            return None;
+        }
+
+        let normal_permit = match (&self.normal_permits, priority) {
+            (Some(normal), AcquirePriority::Normal) => Some(normal.try_acquire(1)?),
+            _ => None,
+        };
+
+        let mut conn = self.try_acquire()?;
+
+        if is_beyond_max_lifetime(&conn, options) {
+            // Closing it has to be awaited, so it goes to a task. It keeps its permit until
+            // it's closed, so the acquire that follows can't take the pool over its size.
+            self.runtime.spawn(async move {
+                drop(conn.close().await);
+            });
+            return None;
+        }
+
+        if let Some(normal_permit) = normal_permit {
+            conn.guard.hold_normal_permit(normal_permit);
+        }
+
+        self.notify_acquire(AcquireSource::Reused);
+        Some(conn.into_live())
+    }
+
+    /// Like `try_acquire()`, but only takes a connection past its deadline, if the idle queue
+    /// can find one without a scan.
+    fn try_acquire_expired(self: &Arc<Self>, now: Instant) -> Option<Floating<C, Idle<C>>> {
+        if self.is_closed() {
+            return None;
+        }
+
+        let permit = self.semaphore.try_acquire(1)?;
+        let idle = self.idle_conns.pop_expired(now)?;
+        self.num_idle.fetch_sub(1, Ordering::AcqRel);
+        Some(Floating::from_idle(idle, (*self).clone(), permit))
+    }
+
+    /// Like `try_acquire()`, but only takes a connection last kept alive at or before `due`.
+    fn try_acquire_keepalive_due(self: &Arc<Self>, due: Instant) -> Option<Floating<C, Idle<C>>> {
+        if self.is_closed() {
+            return None;
+        }
+
+        let permit = self.semaphore.try_acquire(1)?;
+        let idle = self.idle_conns.pop_keepalive_due(due)?;
+        self.num_idle.fetch_sub(1, Ordering::AcqRel);
+        Some(Floating::from_idle(idle, (*self).clone(), permit))
+    }
+
     fn pop_idle<'a>(
         self: &'a Arc<Self>,
         permit: AsyncSemaphoreReleaser<'a>,
@@ -193,29 +716,210 @@ impl<C: Connection> PoolInner<C> {
         }
     }
 
+    /// Pop another idle connection, reusing the permit held by `guard`.
+    ///
+    /// `guard` must belong to a connection that was just closed; its size slot is given up
+    /// and the popped connection's slot takes its place.
+    fn pop_idle_with_guard(
+        self: &Arc<Self>,
+        guard: DecrementSizeGuard<C>,
+    ) -> Result<Floating<C, Idle<C>>, DecrementSizeGuard<C>> {
+        if self.is_closed() {
+            return Err(guard);
+        }
+
+        if let Some(idle) = self.idle_conns.pop() {
+            self.num_idle.fetch_sub(1, Ordering::AcqRel);
+            // The closed connection no longer counts against the pool size,
+            // but we keep the permit for the connection we just popped.
+            self.decrement_size();
+            let mut guard = guard;
+            // already counted in `num_open`
+            guard.open = true;
+            Ok(Floating { inner: idle, guard })
+        } else {
+            Err(guard)
+        }
+    }
+
+    /// Check idle connections until one is usable, or `test_before_acquire_retries`
+    /// connections have been discarded.
+    ///
+    /// Only a single permit is held for the whole sequence: each discarded connection hands its
+    /// guard over to the next one popped, and the guard returned on `Err` is the one the caller
+    /// opens a new connection with. Churning through stale connections can't take the pool over
+    /// `max_connections`, even transiently.
+    async fn check_idle_conns(
+        self: &Arc<Self>,
+        mut conn: Floating<C, Idle<C>>,
+        test: bool,
+    ) -> Result<Floating<C, Live<C>>, DecrementSizeGuard<C>> {
+        let mut attempts = 1;
+
+        loop {
+            let guard = match check_idle_conn(conn, &self.options, test).await {
+                Ok(live) => return Ok(live),
+                Err(guard) => guard,
+            };
+
+            if attempts >= self.options.test_before_acquire_retries {
+                tracing::debug!(attempts, "discarded too many idle connections; opening a new one");
+                return Err(guard);
+            }
+
+            conn = self.pop_idle_with_guard(guard)?;
+            attempts += 1;
+        }
+    }
+
+    /// Invoke `options.on_close`, if set, for a connection that is about to be closed.
+    pub(super) fn notify_close(&self, meta: PoolConnectionMetadata) {
+        log_event!(self.options.log_settings.close_level, age = ?meta.age, "closing connection");
+
+        if let Some(callback) = &self.options.on_close {
+            if std::panic::catch_unwind(AssertUnwindSafe(|| callback(meta))).is_err() {
+                tracing::error!("`on_close` callback panicked");
+            }
+        }
+    }
+
+    /// Whether an acquire would have to wait, see `Pool::is_saturated()`.
+    pub(super) fn is_saturated(&self) -> bool {
+        // With a shared semaphore, the other pools sharing it may hold every connection it allows.
+        let shared_exhausted = self.options.shared_semaphore.as_ref().is_some_and(|shared| {
+            shared.permits() == 0 && self.num_idle() == 0
+        });
+
+        (self.semaphore.permits() == 0 && self.size() >= self.max_connections()) || shared_exhausted
+    }
+
+    /// Invoke `options.on_saturation_change`, if set, if the pool became saturated or stopped
+    /// being saturated, and has stayed that way for `options.saturation_debounce`.
+    fn update_saturation(&self) {
+        let Some(callback) = &self.options.on_saturation_change else {
+            return;
+        };
+
+        // Held while calling back, so concurrent changes are reported in order.
+        let mut saturation = self.saturation.lock().expect("BUG: panicked while holding a lock");
+        let saturated = self.is_saturated();
+
+        if saturated == saturation.reported {
+            // undone before it was reported, if it ever changed
+            saturation.changed_at = None;
+            return;
+        }
+
+        let debounce = self.options.saturation_debounce;
+        let changed_at = *saturation.changed_at.get_or_insert_with(|| {
+            if !debounce.is_zero() {
+                // Check again once the change would have lasted long enough.
+                let pool = self.weak.clone();
+                self.runtime.spawn(async move {
+                    clock::sleep(debounce).await;
+                    if let Some(pool) = pool.upgrade() {
+                        pool.update_saturation();
+                    }
+                });
+            }
+            clock::now()
+        });
+
+        if clock::elapsed(changed_at) < debounce {
+            return;
+        }
+
+        saturation.reported = saturated;
+        saturation.changed_at = None;
+        if std::panic::catch_unwind(AssertUnwindSafe(|| callback(saturated))).is_err() {
+            tracing::error!("`on_saturation_change` callback panicked");
+        }
+    }
+
+    fn notify_slow_acquire(&self, waited: Duration) {
+        let Some(threshold) = self.options.slow_acquire_threshold else {
+            return;
+        };
+
+        if waited <= threshold {
+            return;
+        }
+
+        if let Some(callback) = &self.options.on_slow_acquire {
+            if std::panic::catch_unwind(AssertUnwindSafe(|| callback(waited))).is_err() {
+                tracing::error!("`on_slow_acquire` callback panicked");
+            }
+        }
+    }
+
     pub(super) fn release(&self, floating: Floating<C, Live<C>>) {
         // `options.after_release` is invoked by `PoolConnection::release_to_pool()`.
+        self.release_idle(floating.into_idle());
+    }
 
-        let Floating { inner: idle, guard } = floating.into_idle();
+    /// Put an idle connection back in the idle queue as-is, keeping its `idle_since`.
+    fn release_idle(&self, floating: Floating<C, Idle<C>>) {
+        let Floating { inner: idle, guard } = floating;
 
-        if !self.idle_conns.push(idle).is_ok() {
-            panic!("BUG: connection queue overflow in release()");
-        }
+        // Every way back into a pool ends up here, so this catches connections mixed up
+        // between pools of the same type. The guard would otherwise fix up the size of one pool
+        // while the connection sits in the idle queue of another.
+        debug_assert!(
+            std::ptr::eq(Arc::as_ptr(&guard.pool), self),
+            "BUG: connection {} released to a pool it doesn't belong to",
+            idle.id
+        );
+
+        self.idle_conns.push(idle);
 
         // NOTE: we need to make sure we drop the permit *after* we push to the idle queue
         // don't decrease the size
         guard.release_permit();
 
         self.num_idle.fetch_add(1, Ordering::AcqRel);
+        self.connection_released.notify_waiters();
+    }
+
+    /// Add a connection opened elsewhere as an idle connection, for `Pool::add()`.
+    ///
+    /// Doesn't wait for room in the pool: gives the connection back if there's none right now.
+    pub(super) fn add(self: &Arc<Self>, conn: C, created_at: Instant) -> Result<(), C> {
+        if self.is_closed() || self.parent().is_some() {
+            return Err(conn);
+        }
+
+        let Some(permit) = self.semaphore.try_acquire(1) else {
+            return Err(conn);
+        };
+        let Ok(guard) = self.try_increment_size(permit) else {
+            return Err(conn);
+        };
+
+        let mut conn = Floating::new_live(conn, None, guard);
+        // keeps `created_at.elapsed()` and the like from underflowing
+        conn.inner.created_at = created_at.min(clock::now());
+        self.release(conn);
+        Ok(())
     }
 
     /// Try to atomically increment the pool size for a new connection.
     ///
-    /// Returns `Err` if the pool is at max capacity already or is closed.
+    /// Returns `Err` if the pool is at max capacity already or is closed, or if
+    /// `options.shared_semaphore` has no permits left.
     pub(super) fn try_increment_size<'a>(
         self: &'a Arc<Self>,
         permit: AsyncSemaphoreReleaser<'a>,
     ) -> Result<DecrementSizeGuard<C>, AsyncSemaphoreReleaser<'a>> {
+        // A permit from the shared semaphore is held for as long as the connection is open,
+        // and given back by `decrement_size()`.
+        let shared_permit = match &self.options.shared_semaphore {
+            Some(shared) => match shared.try_acquire(1) {
+                Some(shared_permit) => Some(shared_permit),
+                None => return Err(permit),
+            },
+            None => None,
+        };
+
         match self
             .size
             .fetch_update(Ordering::AcqRel, Ordering::Acquire, |size| {
@@ -224,79 +928,411 @@ impl<C: Connection> PoolInner<C> {
                 }
 
                 size.checked_add(1)
-                    .filter(|size| size <= &self.options.max_connections)
+                    .filter(|size| size <= &self.max_connections())
             }) {
             // we successfully incremented the size
-            Ok(_) => Ok(DecrementSizeGuard::from_permit((*self).clone(), permit)),
+            Ok(_) => {
+                if let Some(shared_permit) = shared_permit {
+                    shared_permit.disarm();
+                }
+                Ok(DecrementSizeGuard::from_permit((*self).clone(), permit))
+            }
             // the pool is at max capacity or is closed
             Err(_) => Err(permit),
         }
     }
 
     pub(super) async fn acquire(self: &Arc<Self>) -> Result<Floating<C, Live<C>>, Error> {
+        self.acquire_timed(
+            self.acquire_deadline(),
+            None,
+            self.options.test_before_acquire,
+            AcquirePriority::Normal,
+        )
+            .await
+    }
+
+    /// The deadline for an acquire starting now, according to `options.acquire_timeout`.
+    pub(super) fn acquire_deadline(&self) -> Instant {
+        clock::now() + self.options.acquire_timeout
+    }
+
+    /// Acquire a connection before `deadline`, recording where the time went into `timing`
+    /// if provided.
+    ///
+    /// `test` decides whether an idle connection is pinged before it's handed out, which is
+    /// `options.test_before_acquire` unless the caller overrides it for this acquire.
+    pub(super) async fn acquire_timed(
+        self: &Arc<Self>,
+        deadline: Instant,
+        mut timing: Option<&mut AcquireTiming>,
+        test: bool,
+        priority: AcquirePriority,
+    ) -> Result<Floating<C, Live<C>>, Error> {
         if self.is_closed() {
             return Err(Error::PoolClosed);
         }
 
-        let deadline = Instant::now() + self.options.acquire_timeout;
+        #[cfg(feature = "opentelemetry")]
+        let acquire_started = self.metrics.is_some().then(clock::now);
 
-        tokio::time::timeout(
-            self.options.acquire_timeout,
+        let res = tokio::time::timeout(
+            deadline.saturating_duration_since(clock::now()),
             async {
-                loop {
-                    // Handles the close-event internally
-                    let permit = self.acquire_permit().await?;
+                let started = (timing.is_some() || self.options.on_slow_acquire.is_some())
+                    .then(clock::now);
 
+                // Handles the close-event internally
+                let (permit, normal_permit) = self.acquire_permit(priority).await?;
 
-                    // First attempt to pop a connection from the idle queue.
-                    let guard = match self.pop_idle(permit) {
+                if let Some(started) = started {
+                    let waited = clock::elapsed(started);
 
-                        // Then, check that we can use it...
-                        Ok(conn) => match check_idle_conn(conn, &self.options).await {
+                    if let Some(timing) = timing.as_deref_mut() {
+                        timing.wait += waited;
+                    }
 
-                            // All good!
-                            Ok(live) => return Ok(live),
+                    self.notify_slow_acquire(waited);
+                }
 
-                            // if the connection isn't usable for one reason or another,
-                            // we get the `DecrementSizeGuard` back to open a new one
-                            Err(guard) => guard,
-                        },
-                        Err(permit) => if let Ok(guard) = self.try_increment_size(permit) {
-                            // we can open a new connection
-                            guard
-                        } else {
-                            // This can happen for a child pool that's at its connection limit,
-                            // or if the pool was closed between `acquire_permit()` and
-                            // `try_increment_size()`.
-                            tracing::debug!("woke but was unable to acquire idle connection or open new one; retrying");
-                            // If so, we're likely in the current-thread runtime if it's Tokio
-                            // and so we should yield to let any spawned release_to_pool() tasks
-                            // execute.
-                            tokio::task::yield_now().await;
-                            continue;
+                let (mut live, source) = match self.acquire_with_permit(permit, timing.as_deref_mut(), test).await? {
+                    // All good!
+                    Ok(live) => (live, AcquireSource::Reused),
+                    Err(guard) => {
+                        // Attempt to connect...
+                        let started = timing.is_some().then(clock::now);
+                        let res = self.connect(deadline, guard).await;
+
+                        if let (Some(timing), Some(started)) = (timing.as_deref_mut(), started) {
+                            timing.connect = Some(clock::elapsed(started));
                         }
-                    };
 
-                    // Attempt to connect...
-                    return self.connect(deadline, guard).await;
+                        (res?, AcquireSource::Created)
+                    }
+                };
+
+                if let Some(normal_permit) = normal_permit {
+                    live.guard.hold_normal_permit(normal_permit);
                 }
+
+                Ok::<_, Error>((live, source))
             },
         )
             .await
-            .map_err(|_| Error::PoolTimedOut)?
+            .map_err(|_| Error::PoolTimedOut)?;
+
+        #[cfg(feature = "opentelemetry")]
+        if let (Some(metrics), Some(started), Ok(_)) = (&self.metrics, acquire_started, &res) {
+            metrics.record_acquire_wait(clock::elapsed(started));
+        }
+
+        let (live, source) = res?;
+        self.notify_acquire(source);
+        Ok(live)
+    }
+
+    /// Take the `n` permits `acquire_many()` waits for, if they're available right away.
+    fn try_acquire_many_permits(
+        &self,
+        n: u32,
+    ) -> Option<(Vec<AsyncSemaphoreReleaser<'_>>, Vec<AsyncSemaphoreReleaser<'_>>)> {
+        let normal_permits = match &self.normal_permits {
+            Some(normal) => normal.try_acquire(n)?.split_each(),
+            None => Vec::new(),
+        };
+
+        Some((self.semaphore.try_acquire(n)?.split_each(), normal_permits))
+    }
+
+    /// Acquire `n` connections at once, for `Pool::acquire_many()`.
+    ///
+    /// All the permits are taken in a single wait on the semaphore, bounded by `deadline`, so
+    /// two tasks acquiring several connections can't each end up holding part of what the other
+    /// needs. Then each permit is turned into a connection like a plain acquire does, still
+    /// within `deadline`.
+    pub(super) async fn acquire_many(
+        self: &Arc<Self>,
+        n: u32,
+        deadline: Instant,
+    ) -> Result<Vec<Floating<C, Live<C>>>, Error> {
+        let limit = self.max_connections().saturating_sub(self.options.reserved_connections);
+        if n == 0 || n > limit {
+            return Err(Error::Configuration(
+                format!("cannot acquire {n} connections at once from a pool handing out at most {limit}").into(),
+            ));
+        }
+
+        // A child pool has no permits of its own to wait for.
+        if self.parent().is_some() {
+            return Err(Error::Configuration(
+                "`acquire_many()` isn't supported for a pool with a parent pool".into(),
+            ));
+        }
+
+        if self.is_closed() {
+            return Err(Error::PoolClosed);
+        }
+
+        let timeout = || deadline.saturating_duration_since(clock::now());
+
+        let (permits, normal_permits) = tokio::time::timeout(timeout(), async {
+            if let Some(permits) = self.try_acquire_many_permits(n) {
+                return Ok(permits);
+            }
+
+            let _pending = PendingGuard::new(self);
+            let mut close_event = self.close_event();
+
+            // Both waits are for all `n` permits at once, and always in this order.
+            let normal_permits = match &self.normal_permits {
+                Some(normal) => close_event.do_until(normal.acquire(n)).await??.split_each(),
+                None => Vec::new(),
+            };
+            let permits = close_event.do_until(self.semaphore.acquire(n)).await??;
+
+            Ok::<_, Error>((permits.split_each(), normal_permits))
+        })
+            .await
+            .map_err(|_| Error::PoolTimedOut)??;
+
+        let test = self.options.test_before_acquire;
+        let mut normal_permits = normal_permits.into_iter();
+        let mut conns = Vec::with_capacity(n as usize);
+        let mut sources = Vec::with_capacity(n as usize);
+
+        for permit in permits {
+            let res = tokio::time::timeout(timeout(), async {
+                match self.acquire_with_permit(permit, None, test).await? {
+                    Ok(live) => Ok((live, AcquireSource::Reused)),
+                    Err(guard) => Ok((self.connect(deadline, guard).await?, AcquireSource::Created)),
+                }
+            })
+                .await
+                .unwrap_or(Err(Error::PoolTimedOut));
+
+            match res {
+                Ok((mut live, source)) => {
+                    if let Some(normal_permit) = normal_permits.next() {
+                        live.guard.hold_normal_permit(normal_permit);
+                    }
+                    conns.push(live);
+                    sources.push(source);
+                }
+                Err(error) => {
+                    // Give back the connections we got so far, the remaining permits are
+                    // released when dropped.
+                    for live in conns {
+                        live.release();
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        for source in sources {
+            self.notify_acquire(source);
+        }
+
+        Ok(conns)
+    }
+
+    /// Count an acquire by where its connection came from, and invoke `options.on_acquire`.
+    pub(super) fn notify_acquire(&self, source: AcquireSource) {
+        match source {
+            AcquireSource::Reused => self.acquires_reused.fetch_add(1, Ordering::Relaxed),
+            AcquireSource::Created => self.acquires_created.fetch_add(1, Ordering::Relaxed),
+        };
+
+        if let Some(callback) = &self.options.on_acquire {
+            if std::panic::catch_unwind(AssertUnwindSafe(|| callback(source))).is_err() {
+                tracing::error!("`on_acquire` callback panicked");
+            }
+        }
+
+        self.update_saturation();
     }
 
+    /// The number of acquires served from the idle queue and by opening a new connection.
+    pub(super) fn acquire_sources(&self) -> (u64, u64) {
+        (
+            self.acquires_reused.load(Ordering::Relaxed),
+            self.acquires_created.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Turn a permit into an idle connection that passed its checks, or a guard to open a new
+    /// connection with.
+    ///
+    /// The permit is kept until then, even if neither is available right away: it was granted in
+    /// the semaphore's fair order, and giving it back to try again would put this task at the end
+    /// of the queue, behind waiters that arrived later.
+    async fn acquire_with_permit<'a>(
+        self: &'a Arc<Self>,
+        mut permit: AsyncSemaphoreReleaser<'a>,
+        mut timing: Option<&mut AcquireTiming>,
+        test: bool,
+    ) -> Result<Result<Floating<C, Live<C>>, DecrementSizeGuard<C>>, Error> {
+        // When the grace period for growing past `soft_max_connections` runs out, once started.
+        let mut soft_max_deadline = None;
+
+        loop {
+            let mut retry = false;
+
+            // Grow toward `min_connections` first if asked to, leaving any idle
+            // connection for the next caller.
+            let grown = if self.options.prefer_grow_below_min
+                && self.size() < self.options.min_connections
+            {
+                self.try_increment_size(permit)
+            } else {
+                Err(permit)
+            };
+
+            // Otherwise attempt to pop a connection from the idle queue.
+            permit = match grown {
+                Ok(guard) => return Ok(Err(guard)),
+                Err(permit) => match self.pop_idle(permit) {
+
+                    // Then, check that we can use it...
+                    Ok(conn) => {
+                        let started = timing.is_some().then(clock::now);
+                        let res = self.check_idle_conns(conn, test).await;
+
+                        if let (Some(timing), Some(started)) = (timing.as_deref_mut(), started) {
+                            timing.test += clock::elapsed(started);
+                        }
+
+                        // if the connection isn't usable for one reason or another,
+                        // we get the `DecrementSizeGuard` back to open a new one
+                        return Ok(res);
+                    }
+                    // Past the soft limit, give a connection in use a chance to be
+                    // returned before opening another one.
+                    Err(permit) => match self.soft_max_grace_left(&mut soft_max_deadline) {
+                        Some(left) => {
+                            self.close_event().do_until(self.wait_for_release(left)).await?;
+                            retry = true;
+                            permit
+                        }
+                        None => match self.try_increment_size(permit) {
+                            // we can open a new connection
+                            Ok(guard) => return Ok(Err(guard)),
+                            Err(permit) => permit,
+                        },
+                    },
+                },
+            };
+
+            if retry {
+                continue;
+            }
+
+            if self.is_closed() {
+                return Err(Error::PoolClosed);
+            }
+
+            if let Some(shared) = self.options.shared_semaphore.as_deref() {
+                if shared.permits() == 0 {
+                    // Every connection the shared semaphore allows for is open,
+                    // wait for it to have room again or for one of ours to be released.
+                    self.close_event()
+                        .do_until(self.wait_for_shared_permit(shared))
+                        .await?;
+                    continue;
+                }
+            }
+
+            // This can happen for a child pool that's at its connection limit, or while another
+            // task is between popping a connection we would have gotten and giving up its slot.
+            tracing::debug!("woke but was unable to acquire idle connection or open new one; waiting");
+            self.close_event().do_until(self.wait_for_room()).await?;
+        }
+    }
+
+    /// If the pool is at or past `soft_max_connections`, how much of the grace period is left to
+    /// wait for a connection to be released, starting it if it's the first time.
+    fn soft_max_grace_left(&self, deadline: &mut Option<Instant>) -> Option<Duration> {
+        let soft_max = self.options.soft_max_connections?;
+        if self.size() < soft_max {
+            return None;
+        }
+
+        let deadline = *deadline.get_or_insert_with(|| clock::now() + self.options.soft_max_grace);
+        deadline
+            .checked_duration_since(clock::now())
+            .filter(|left| !left.is_zero())
+    }
+
+    /// Wait up to `timeout` for a connection to be released to the idle queue.
+    async fn wait_for_release(&self, timeout: Duration) {
+        let released = self.connection_released.notified();
+        futures_util::pin_mut!(released);
+        released.as_mut().enable();
+
+        if self.num_idle() > 0 {
+            return;
+        }
+
+        let _ = tokio::time::timeout(timeout, released).await;
+    }
+
+    /// Wait until a connection is released to the idle queue, or there's room to open one.
+    async fn wait_for_room(&self) {
+        let released = self.connection_released.notified();
+        let freed = self.slot_freed.notified();
+        futures_util::pin_mut!(released, freed);
+        released.as_mut().enable();
+        freed.as_mut().enable();
+
+        if self.num_idle() > 0 || self.size() < self.max_connections() {
+            return;
+        }
+
+        future::select(released, freed).await;
+    }
+
+    /// Wait until `shared` has a permit available, or one of our connections is released to the
+    /// idle queue.
+    async fn wait_for_shared_permit(&self, shared: &AsyncSemaphore) {
+        let released = self.connection_released.notified();
+        futures_util::pin_mut!(released);
+        released.as_mut().enable();
+
+        if self.num_idle() > 0 {
+            return;
+        }
+
+        // Only wait for the permit, the caller takes it for real in `try_increment_size()`.
+        let available = shared.acquire(1);
+        futures_util::pin_mut!(available);
+        future::select(released, available).await;
+    }
+
+    /// Open a new connection in the slot held by `guard`.
+    ///
+    /// The guard is what keeps the pool within `max_connections`: it's only handed out by
+    /// `try_increment_size()`, or passed on from a connection that was just closed, so a new
+    /// connection always has its place in `size` before `connect()` is even called.
     pub(super) async fn connect(
         self: &Arc<Self>,
         deadline: Instant,
         guard: DecrementSizeGuard<C>,
     ) -> Result<Floating<C, Live<C>>, Error> {
+        debug_assert!(
+            !guard.cancelled && Arc::ptr_eq(&guard.pool, self) && self.size() > 0,
+            "BUG: opening a connection without a slot in the pool"
+        );
+
         if self.is_closed() {
             return Err(Error::PoolClosed);
         }
 
+        #[cfg(feature = "opentelemetry")]
+        let connect_started = self.metrics.is_some().then(clock::now);
+
         let mut backoff = Duration::from_millis(10);
         let max_backoff = deadline_as_timeout(deadline)? / 5;
+        let mut rejections = 0;
 
         loop {
             let timeout = deadline_as_timeout(deadline)?;
@@ -309,29 +1345,94 @@ impl<C: Connection> PoolInner<C> {
                 .expect("write-lock holder panicked")
                 .clone();
 
+            // a single attempt may be cut short by `connect_timeout`, and then retried
+            let connect_timeout = self.options.connect_timeout.filter(|t| *t < timeout);
+
             // result here is `Result<Result<C, Error>, TimeoutError>`
             // if this block does not return, sleep for the backoff timeout and try again
-            match tokio::time::timeout(timeout, connect_options.connect()).await {
+            match tokio::time::timeout(connect_timeout.unwrap_or(timeout), connect_options.connect()).await {
                 // successfully established connection
-                Ok(Ok(mut raw)) => {
+                Ok(Ok(raw)) => {
+                    // Closes the connection if we're cancelled while the hooks below run.
+                    let mut raw = CloseOnDrop(Some(raw));
+
                     // See comment on `PoolOptions::after_connect`
                     let meta = PoolConnectionMetadata {
                         age: Duration::ZERO,
                         idle_for: Duration::ZERO,
                     };
 
-                    let res = if let Some(callback) = &self.options.after_connect {
-                        callback(&mut raw, meta).await
-                    } else {
-                        Ok(())
-                    };
+                    let res = async {
+                        for command in &self.options.on_connect_commands {
+                            command.run(raw.get()).await.map_err(|error| {
+                                tracing::debug!(command = command.name(), "on-connect command failed");
+                                ("on_connect_commands", error)
+                            })?;
+                        }
+
+                        if let Some(callback) = &self.options.after_connect {
+                            callback(raw.get(), meta)
+                                .await
+                                .map_err(|error| ("after_connect", error))?;
+                        }
+
+                        if let Some(callback) = &self.options.validate_on_connect {
+                            let valid = callback(raw.get())
+                                .await
+                                .map_err(|error| ("validate_on_connect", error))?;
+
+                            if !valid {
+                                return Err(("validate_on_connect", Error::ConnectionRejected));
+                            }
+                        }
+
+                        match &self.options.connection_extension {
+                            Some(callback) => callback(raw.get())
+                                .await
+                                .map(Some)
+                                .map_err(|error| ("connection_extension", error)),
+                            None => Ok(None),
+                        }
+                    }
+                    .await;
 
                     match res {
-                        Ok(()) => return Ok(Floating::new_live(raw, guard)),
-                        Err(error) => {
-                            tracing::error!(%error, "error returned from after_connect");
+                        Ok(extension) => {
+                            self.record_connect_success();
+
+                            #[cfg(feature = "opentelemetry")]
+                            if let (Some(metrics), Some(started)) = (&self.metrics, connect_started) {
+                                metrics.record_connect(clock::elapsed(started));
+                            }
+
+                            let conn = Floating::new_live(raw.into_inner(), extension, guard);
+                            log_event!(
+                                self.options.log_settings.connect_level,
+                                id = conn.id,
+                                "opened a new connection"
+                            );
+                            return Ok(conn);
+                        }
+                        Err((hook, error)) => {
+                            tracing::error!(%error, "error returned from {hook}");
+                            // Marks the pool down: a connection that can't be set up or validated
+                            // is as good as none.
+                            self.record_connect_error(format!("error returned from {hook}: {error}"));
                             // The connection is broken, don't try to close nicely.
-                            let _ = raw.close_hard().await;
+                            let _ = raw.into_inner().close_hard().await;
+
+                            if hook == "validate_on_connect" {
+                                rejections += 1;
+
+                                // Retrying won't help if the backend itself is misconfigured,
+                                // e.g. it accepts connections but fails every command.
+                                if rejections >= MAX_CONNECT_REJECTIONS {
+                                    return Err(match error {
+                                        Error::ConnectionRejected => Error::ConnectionRejected,
+                                        error => Error::ConnectFailed(Box::new(error)),
+                                    });
+                                }
+                            }
 
                             // Fall through to the backoff.
                         }
@@ -339,28 +1440,50 @@ impl<C: Connection> PoolInner<C> {
                 }
 
                 // an IO error while connecting is assumed to be the system starting up
-                Ok(Err(Error::Io(e))) if e.kind() == std::io::ErrorKind::ConnectionRefused => (),
+                Ok(Err(Error::Io(e))) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    self.record_connect_error(Error::Io(e).to_string());
+                }
 
                 // We got a transient database error, retry.
 
                 // Any other error while connection should immediately
                 // terminate and bubble the error up
-                Ok(Err(e)) => return Err(e),
+                Ok(Err(e)) => {
+                    self.record_connect_error(e.to_string());
+                    return Err(e);
+                }
 
                 // timed out
-                Err(_) => return Err(Error::PoolTimedOut),
+                Err(_) => match connect_timeout {
+                    // only this attempt took longer than `connect_timeout`, try again
+                    Some(connect_timeout) => {
+                        tracing::debug!(?connect_timeout, "timed out while opening a connection, retrying");
+                        self.record_connect_error(format!(
+                            "timed out while opening a connection (`connect_timeout` of {connect_timeout:?})"
+                        ));
+                    }
+                    None => {
+                        self.record_connect_error("timed out while opening a connection".to_string());
+                        return Err(Error::PoolTimedOut);
+                    }
+                },
             }
 
             // If the connection is refused, wait in exponentially
             // increasing steps for the server to come up,
             // capped by a factor of the remaining time until the deadline
-            tokio::time::sleep(backoff).await;
+            clock::sleep(backoff).await;
             backoff = cmp::min(backoff * 2, max_backoff);
         }
     }
 
     /// Try to maintain `min_connections`, returning any errors (including `PoolTimedOut`).
     pub async fn try_min_connections(self: &Arc<Self>, deadline: Instant) -> Result<(), Error> {
+        // Connections opened here would never be handed out.
+        if self.options.disable_reuse {
+            return Ok(());
+        }
+
         while self.size() < self.options.min_connections {
             // Don't wait for a semaphore permit.
             //
@@ -387,7 +1510,7 @@ impl<C: Connection> PoolInner<C> {
     pub async fn min_connections_maintenance(self: &Arc<Self>, deadline: Option<Instant>) {
         let deadline = deadline.unwrap_or_else(|| {
             // Arbitrary default deadline if the caller doesn't care.
-            Instant::now() + Duration::from_secs(300)
+            clock::now() + Duration::from_secs(300)
         });
 
         match self.try_min_connections(deadline).await {
@@ -409,6 +1532,11 @@ impl<C: Connection> Drop for PoolInner<C> {
             // Release the stolen permits.
             parent.0.semaphore.release(self.semaphore.permits());
         }
+
+        if let Some(shared) = &self.options.shared_semaphore {
+            // Release the permits of the connections dropped along with us, e.g. idle ones.
+            shared.release(self.size() as usize);
+        }
     }
 }
 
@@ -416,19 +1544,50 @@ impl<C: Connection> Drop for PoolInner<C> {
 fn is_beyond_max_lifetime<C: Connection>(live: &Live<C>, options: &PoolOptions<C>) -> bool {
     options
         .max_lifetime
-        .map_or(false, |max| live.created_at.elapsed() > max)
+        .is_some_and(|max| clock::elapsed(live.created_at) > max)
 }
 
 /// Returns `true` if the connection has exceeded `options.idle_timeout` if set, `false` otherwise.
 fn is_beyond_idle_timeout<C: Connection>(idle: &Idle<C>, options: &PoolOptions<C>) -> bool {
-    options
-        .idle_timeout
-        .map_or(false, |timeout| idle.idle_since.elapsed() > timeout)
+    options.idle_timeout.is_some_and(|timeout| {
+        let timeout =
+            idle::jittered_idle_timeout(timeout, options.idle_timeout_jitter, idle.live.id);
+        clock::elapsed(idle.idle_since) > timeout
+    })
+}
+
+/// The delay before retry number `retry` (starting at 0) of [`Pool::acquire_retry()`].
+///
+/// The backoff doubles with each retry, and the delay is a random point in its upper half
+/// ("equal jitter"), so callers that timed out together spread out without any of them
+/// retrying right away.
+pub(crate) fn retry_delay(backoff: Duration, retry: u32) -> Duration {
+    use std::collections::hash_map::RandomState;
+    use std::hash::BuildHasher;
+
+    let cap = backoff.saturating_mul(1u32 << retry.min(31));
+    // `RandomState` is seeded randomly for each instance, which is all the randomness needed here
+    let fraction = jitter_fraction(RandomState::new().hash_one(retry));
+    cap / 2 + (cap / 2).mul_f64(fraction)
+}
+
+/// A pseudo-random number in `[0, 1)` that stays the same for a given connection id.
+///
+/// Uses the SplitMix64 finalizer so consecutive ids are spread out.
+pub(crate) fn jitter_fraction(id: u64) -> f64 {
+    let mut z = id.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+
+    // take the top 53 bits, which an `f64` can represent exactly
+    (z >> 11) as f64 / (1u64 << 53) as f64
 }
 
 async fn check_idle_conn<C: Connection>(
     mut conn: Floating<C, Idle<C>>,
     options: &PoolOptions<C>,
+    test: bool,
 ) -> Result<Floating<C, Live<C>>, DecrementSizeGuard<C>> {
     // If the connection we pulled has expired, close the connection and
     // immediately create a new connection
@@ -436,7 +1595,7 @@ async fn check_idle_conn<C: Connection>(
         return Err(conn.close().await);
     }
 
-    if options.test_before_acquire {
+    if test {
         // Check that the connection is still live
         if let Err(error) = conn.ping().await {
             // an error here means the other end has hung up or we lost connectivity
@@ -448,6 +1607,24 @@ async fn check_idle_conn<C: Connection>(
         }
     }
 
+    if let Some(test) = &options.before_acquire_sync {
+        let meta = conn.metadata();
+        match test(&mut conn.live.raw, meta) {
+            Ok(false) => {
+                // connection was rejected by user-defined hook, close nicely
+                return Err(conn.close().await);
+            }
+
+            Err(error) => {
+                tracing::warn!(%error, "error from `before_acquire_sync`");
+                // connection is broken so don't try to close nicely
+                return Err(conn.close_hard().await);
+            }
+
+            Ok(true) => {}
+        }
+    }
+
     if let Some(test) = &options.before_acquire {
         let meta = conn.metadata();
         match test(&mut conn.live.raw, meta).await {
@@ -473,7 +1650,7 @@ async fn check_idle_conn<C: Connection>(
 fn spawn_maintenance_tasks<C: Connection>(pool: &Arc<PoolInner<C>>) {
     // NOTE: use `pool_weak` for the maintenance tasks so
     // they don't keep `PoolInner` from being dropped.
-    let pool_weak = Arc::downgrade(&pool);
+    let pool_weak = Arc::downgrade(pool);
 
     let period = match (pool.options.max_lifetime, pool.options.idle_timeout) {
         (Some(it), None) | (None, Some(it)) => it,
@@ -493,6 +1670,14 @@ fn spawn_maintenance_tasks<C: Connection>(pool: &Arc<PoolInner<C>>) {
         }
     };
 
+    // With jittered idle timeouts, connections become due for reaping at different points within
+    // the period, so run more often to actually spread out the closes.
+    let period = if pool.options.idle_timeout.is_some() && pool.options.idle_timeout_jitter > 0.0 {
+        period.mul_f64((pool.options.idle_timeout_jitter / 4.0).max(0.01))
+    } else {
+        period
+    };
+
     // Immediately cancel this task if the pool is closed.
     let mut close_event = pool.close_event();
 
@@ -508,20 +1693,20 @@ fn spawn_maintenance_tasks<C: Connection>(pool: &Arc<PoolInner<C>>) {
                     }
 
                     // Don't run the reaper right away.
-                    if slept && !pool.idle_conns.is_empty() {
+                    if slept && !pool.idle_conns.is_empty() && !pool.is_reaping_paused() {
                         do_reap(&pool).await;
                     }
 
-                    let next_run = Instant::now() + period;
+                    let next_run = clock::now() + period;
 
                     pool.min_connections_maintenance(Some(next_run)).await;
 
                     // Don't hold a reference to the pool while sleeping.
                     drop(pool);
 
-                    if let Some(duration) = next_run.checked_duration_since(Instant::now()) {
+                    if let Some(duration) = next_run.checked_duration_since(clock::now()) {
                         // `async-std` doesn't have a `sleep_until()`
-                        tokio::time::sleep(duration).await;
+                        clock::sleep(duration).await;
                     } else {
                         // `next_run` is in the past, just yield.
                         tokio::task::yield_now().await;
@@ -534,22 +1719,145 @@ fn spawn_maintenance_tasks<C: Connection>(pool: &Arc<PoolInner<C>>) {
     });
 }
 
-async fn do_reap<C: Connection>(pool: &Arc<PoolInner<C>>) {
+fn spawn_leak_detection<C: Connection>(pool: &Arc<PoolInner<C>>) {
+    let Some(max) = pool.options.max_checkout_duration else {
+        return;
+    };
+
+    // Check often enough that a leak is reported at most `max / 2` late.
+    let period = cmp::max(max / 2, Duration::from_millis(1));
+    let pool_weak = Arc::downgrade(pool);
+
+    // Keep going after the pool is closed: leaked connections are also what would keep
+    // `Pool::close()` from finishing.
+    tokio::spawn(async move {
+        loop {
+            clock::sleep(period).await;
+
+            let Some(pool) = pool_weak.upgrade() else {
+                return;
+            };
+
+            pool.report_leaks(max);
+        }
+    });
+}
+
+fn spawn_keepalive<C: Connection>(pool: &Arc<PoolInner<C>>) {
+    let Some(interval) = pool.options.keepalive_interval else {
+        return;
+    };
+
+    // Check often enough that a connection goes at most `interval * 1.5` without a keepalive.
+    let period = cmp::max(interval / 2, Duration::from_millis(1));
+    let pool_weak = Arc::downgrade(pool);
+
+    // Immediately cancel this task if the pool is closed.
+    let mut close_event = pool.close_event();
+
+    tokio::spawn(async move {
+        let _ = close_event
+            .do_until(async {
+                loop {
+                    clock::sleep(period).await;
+
+                    let Some(pool) = pool_weak.upgrade() else {
+                        return;
+                    };
+
+                    do_keepalive(&pool, interval).await;
+                }
+            })
+            .await;
+    });
+}
+
+async fn do_keepalive<C: Connection>(pool: &Arc<PoolInner<C>>, interval: Duration) {
+    let Some(due_at) = clock::now().checked_sub(interval) else {
+        return;
+    };
+
+    // only connections waiting in the queue, and only the ones that are due
+    let due: Vec<_> = (0..pool.num_idle())
+        .map_while(|_| pool.try_acquire_keepalive_due(due_at))
+        .collect();
+
+    let failed = Mutex::new(Vec::new());
+
+    // `due` is out of the idle queue for now, so nothing else can use these connections.
+    futures_util::stream::iter(due)
+        .for_each_concurrent(pool.options.keepalive_concurrency, |mut conn| {
+            let failed = &failed;
+            async move {
+                match conn.live.raw.on_idle().await {
+                    Ok(()) => {
+                        conn.keepalive_at = clock::now();
+                        // without resetting how long it's been idle
+                        pool.release_idle(conn);
+                    }
+                    Err(error) => {
+                        tracing::debug!(%error, "keepalive failed, closing idle connection");
+                        failed
+                            .lock()
+                            .expect("BUG: panicked while holding a lock")
+                            .push(conn);
+                    }
+                }
+            }
+        })
+        .await;
+
+    let failed = failed.into_inner().expect("BUG: panicked while holding a lock");
+    future::join_all(failed.into_iter().map(|conn| conn.close_hard())).await;
+}
+
+pub(super) async fn do_reap<C: Connection>(pool: &Arc<PoolInner<C>>) {
     // reap at most the current size minus the minimum idle
     let max_reaped = pool.size().saturating_sub(pool.options.min_connections);
+    // connections over a `max_connections` lowered by `Pool::resize()` are reaped regardless
+    let mut excess = pool.size().saturating_sub(pool.max_connections());
 
     // collect connections to reap
-    let (reap, keep) = (0..max_reaped)
-        // only connections waiting in the queue
-        .filter_map(|_| pool.try_acquire())
-        .partition::<Vec<_>, _>(|conn| {
-            is_beyond_idle_timeout(conn, &pool.options)
-                || is_beyond_max_lifetime(conn, &pool.options)
-        });
+    let (reap, keep) = if pool.idle_conns.is_deadline_ordered() {
+        // only take out the connections that are due, leaving the others be
+        let now = clock::now();
+        let reap = (0..max_reaped)
+            .map_while(|_| {
+                if excess > 0 {
+                    excess -= 1;
+                    pool.try_acquire()
+                } else {
+                    pool.try_acquire_expired(now)
+                }
+            })
+            .collect::<Vec<_>>();
+        (reap, Vec::new())
+    } else {
+        (0..max_reaped)
+            // only connections waiting in the queue
+            .filter_map(|_| pool.try_acquire())
+            .partition::<Vec<_>, _>(|conn| {
+                if excess > 0 {
+                    excess -= 1;
+                    return true;
+                }
+
+                is_beyond_idle_timeout(conn, &pool.options)
+                    || is_beyond_max_lifetime(conn, &pool.options)
+            })
+    };
 
     for conn in keep {
-        // return valid connections to the pool first
-        pool.release(conn.into_live());
+        // return valid connections to the pool first, without resetting how long they've been idle
+        pool.release_idle(conn);
+    }
+
+    if !reap.is_empty() {
+        log_event!(
+            pool.options.log_settings.reap_level,
+            reaped = reap.len(),
+            "reaping idle connections"
+        );
     }
 
     for conn in reap {
@@ -557,20 +1865,93 @@ async fn do_reap<C: Connection>(pool: &Arc<PoolInner<C>>) {
     }
 }
 
+/// A connection checked out of the pool, tracked for leak detection.
+struct Checkout {
+    since: Instant,
+    /// Whether it was already reported as a likely leak.
+    reported: bool,
+}
+
+/// A new connection that isn't in the pool yet, closed with `close_hard()` if dropped.
+///
+/// If an acquire is cancelled after `connect()` succeeded but before the connection is pooled,
+/// e.g. while `after_connect` runs, the connection would otherwise just be dropped without
+/// being closed. Its slot in the pool is freed by the `DecrementSizeGuard` either way.
+struct CloseOnDrop<C: Connection>(Option<C>);
+
+impl<C: Connection> CloseOnDrop<C> {
+    fn get(&mut self) -> &mut C {
+        self.0.as_mut().expect("BUG: connection already taken")
+    }
+
+    fn into_inner(mut self) -> C {
+        self.0.take().expect("BUG: connection already taken")
+    }
+}
+
+impl<C: Connection> Drop for CloseOnDrop<C> {
+    fn drop(&mut self) {
+        let Some(raw) = self.0.take() else {
+            return;
+        };
+
+        tracing::debug!("acquire cancelled while opening a connection, closing it");
+        let close = raw.close_hard();
+
+        // Without a runtime, dropping the connection is the best we can do.
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                let _ = close.await;
+            });
+        }
+    }
+}
+
+/// Counts a task as waiting in `acquire_permit()` for as long as it is alive.
+struct PendingGuard<'a, C: Connection> {
+    pool: &'a PoolInner<C>,
+    /// The number of tasks that were already waiting.
+    ahead: usize,
+}
+
+impl<'a, C: Connection> PendingGuard<'a, C> {
+    fn new(pool: &'a PoolInner<C>) -> Self {
+        let ahead = pool.num_pending.fetch_add(1, Ordering::AcqRel);
+        Self { pool, ahead }
+    }
+}
+
+impl<C: Connection> Drop for PendingGuard<'_, C> {
+    fn drop(&mut self) {
+        self.pool.num_pending.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
 /// RAII guard returned by `Pool::try_increment_size()` and others.
 ///
 /// Will decrement the pool size if dropped, to avoid semantically "leaking" connections
 /// (where the pool thinks it has more connections than it does).
 pub struct DecrementSizeGuard<C: Connection> {
     pub(crate) pool: Arc<PoolInner<C>>,
+    /// Whether the guard also holds a permit from `pool.normal_permits`, given back with the
+    /// pool's permit.
+    pub(crate) normal_permit: bool,
+    /// Whether the guard's connection is counted in `pool.num_open`, and has to be taken out of
+    /// it when the guard drops.
+    pub(crate) open: bool,
     cancelled: bool,
 }
 
 impl<C: Connection> DecrementSizeGuard<C> {
     /// Create a new guard that will release a semaphore permit on-drop.
     pub fn new_permit(pool: Arc<PoolInner<C>>) -> Self {
+        #[cfg(debug_assertions)]
+        pool.outstanding_guards.fetch_add(1, Ordering::AcqRel);
+
         Self {
             pool,
+            normal_permit: false,
+            open: false,
             cancelled: false,
         }
     }
@@ -585,10 +1966,37 @@ impl<C: Connection> DecrementSizeGuard<C> {
     ///
     /// If the permit was stolen from the pool's parent, it will be returned to the child's semaphore.
     fn release_permit(self) {
-        self.pool.semaphore.release(1);
+        self.pool.release_permit();
+        if self.normal_permit {
+            self.pool.release_normal_permit();
+        }
         self.cancel();
     }
 
+    /// Take ownership of a permit from `pool.normal_permits`.
+    fn hold_normal_permit(&mut self, permit: AsyncSemaphoreReleaser<'_>) {
+        permit.disarm();
+        self.normal_permit = true;
+    }
+
+    /// Count the guard's newly opened connection in `pool.num_open`.
+    pub(crate) fn mark_open(&mut self) {
+        if !self.open {
+            self.open = true;
+            self.pool.num_open.fetch_add(1, Ordering::AcqRel);
+            self.pool.num_open_changed.notify_waiters();
+        }
+    }
+
+    /// Take the guard's connection, which was just closed, out of `pool.num_open`.
+    pub(crate) fn mark_closed(&mut self) {
+        if self.open {
+            self.open = false;
+            self.pool.num_open.fetch_sub(1, Ordering::AcqRel);
+            self.pool.num_open_changed.notify_waiters();
+        }
+    }
+
     pub fn cancel(mut self) {
         self.cancelled = true;
     }
@@ -596,11 +2004,18 @@ impl<C: Connection> DecrementSizeGuard<C> {
 
 impl<C: Connection> Drop for DecrementSizeGuard<C> {
     fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        self.pool.outstanding_guards.fetch_sub(1, Ordering::AcqRel);
+
         if !self.cancelled {
-            self.pool.size.fetch_sub(1, Ordering::AcqRel);
+            self.mark_closed();
+            self.pool.decrement_size();
 
             // and here we release the permit we got on construction
-            self.pool.semaphore.release(1);
+            self.pool.release_permit();
+            if self.normal_permit {
+                self.pool.release_normal_permit();
+            }
         }
     }
 }
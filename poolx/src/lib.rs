@@ -63,29 +63,55 @@ use std::time::{Duration, Instant};
 
 use event_listener::EventListener;
 pub use futures_core;
-use futures_core::FusedFuture;
+use futures_core::future::BoxFuture;
+use futures_core::{FusedFuture, Stream};
 use futures_util::FutureExt;
 pub use url;
+pub use tracing::level_filters::LevelFilter;
 
 pub use conn::*;
 pub use error::*;
 
 
 pub use self::connection::PoolConnection;
+pub use self::group::PoolGroup;
+pub use self::idle::IdleQueueKind;
+pub use self::log::LogSettings;
 use self::inner::PoolInner;
 #[doc(hidden)]
 pub use self::maybe::MaybePoolConnection;
-pub use self::options::{PoolConnectionMetadata, PoolOptions};
+pub use self::options::{
+    AcquirePriority, AcquireSource, InitCommand, PoolConnectionMetadata, PoolOptions, PoolOptionsSummary, ReturnMode,
+};
+pub use self::replicated::{ReplicaSelection, ReplicatedPool};
+pub use self::sync::AsyncSemaphore;
+#[cfg(feature = "tower")]
+pub use self::service::PoolService;
+#[cfg(feature = "deadpool-compat")]
+pub use self::manager::{ManagerConnection, ManagerOptions};
 
 mod conn;
 
 #[macro_use]
 pub mod maybe;
 
+#[macro_use]
+mod log;
+
+mod clock;
 mod connection;
 mod inner;
 mod options;
 mod error;
+mod group;
+mod idle;
+#[cfg(feature = "deadpool-compat")]
+mod manager;
+#[cfg(feature = "opentelemetry")]
+mod metrics;
+mod replicated;
+#[cfg(feature = "tower")]
+mod service;
 
 
 mod sync;
@@ -259,6 +285,123 @@ pub struct CloseEvent {
     listener: Option<EventListener>,
 }
 
+/// A future that resolves to a connection from the pool.
+///
+/// Returned by [`Pool::acquire()`]; having a name for it makes it possible to store it in a
+/// struct or spell out its type. It is `Unpin` and does not borrow the pool, so it can be
+/// polled by reference in a `tokio::select!` arm.
+///
+/// If its first poll finds an idle connection it can hand out right away, without testing it,
+/// it resolves without allocating. Otherwise it sets up the rest of the acquire in a boxed
+/// future: waiting for a permit, testing a connection, or opening one.
+///
+/// Dropping it before it resolves cancels the acquire: a semaphore permit it was holding goes
+/// straight back to the pool, and a connection that was being opened or tested is closed
+/// (see the note on cancellation in [`Pool::acquire()`]).
+#[must_use = "futures do nothing unless polled"]
+pub struct Acquire<C: Connection> {
+    state: AcquireState<C>,
+}
+
+enum AcquireState<C: Connection> {
+    /// Not polled yet.
+    Start {
+        pool: Arc<PoolInner<C>>,
+        test: bool,
+        priority: AcquirePriority,
+    },
+    Pending(BoxFuture<'static, Result<PoolConnection<C>, Error>>),
+    Done,
+}
+
+impl<C: Connection> Acquire<C> {
+    fn new(pool: &Pool<C>, test: bool, priority: AcquirePriority) -> Self {
+        Acquire {
+            state: AcquireState::Start {
+                pool: pool.0.clone(),
+                test,
+                priority,
+            },
+        }
+    }
+}
+
+/// Coarse health of a [`Pool`], as reported by [`Pool::health()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolHealth {
+    /// The pool is open and the last attempt to open a connection (if any) succeeded.
+    Up,
+    /// The pool is closed, or the last attempt to open a connection failed.
+    Down,
+}
+
+/// Metadata about a connection in the pool, as returned by [`Pool::idle_connections_info()`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ConnectionInfo {
+    /// Identifies the connection, unique within its pool.
+    pub id: u64,
+
+    /// The duration since the connection was first opened.
+    pub age: Duration,
+
+    /// The duration that the connection has spent in the idle queue.
+    pub idle_for: Duration,
+}
+
+/// A snapshot of a [`Pool`]'s counters, as returned by [`Pool::statistics()`].
+///
+/// The counters are read one after the other without locking, so under load they may not be
+/// perfectly consistent with each other.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolStatistics {
+    /// The number of open connections, including idle ones. See [`Pool::size()`].
+    pub size: u32,
+
+    /// The number of idle connections. See [`Pool::num_idle()`].
+    pub num_idle: usize,
+
+    /// The number of tasks waiting for a connection. See [`Pool::num_pending()`].
+    pub num_pending: usize,
+
+    /// The maximum number of connections. See [`Pool::max_connections()`].
+    pub max_connections: u32,
+
+    /// The number of returned connections closed since the pool was created because they failed
+    /// a check on release: [`after_release`][PoolOptions::after_release] rejected them, failed
+    /// or timed out, or [`rollback_on_release`][PoolOptions::rollback_on_release] couldn't roll
+    /// them back.
+    ///
+    /// A rising count points to an unstable backend. Each of these connections is also passed
+    /// to [`on_close`][PoolOptions::on_close], e.g. to log its age.
+    pub release_test_failures: u64,
+
+    /// The number of acquires since the pool was created that got an idle connection.
+    ///
+    /// Together with [`created`][Self::created], this is the pool's reuse rate. See also
+    /// [`PoolOptions::on_acquire`].
+    pub reused: u64,
+
+    /// The number of acquires since the pool was created that had to open a new connection.
+    pub created: u64,
+}
+
+/// Where the time went during a single [`Pool::acquire_instrumented()`] call.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AcquireTiming {
+    /// Time spent waiting for a semaphore permit, i.e. waiting for the pool to have capacity.
+    pub wait: Duration,
+
+    /// Time spent checking idle connections before handing one out, including
+    /// [`test_before_acquire`][PoolOptions::test_before_acquire] pings and
+    /// [`before_acquire`][PoolOptions::before_acquire].
+    pub test: Duration,
+
+    /// Time spent establishing a new connection, or `None` if an idle connection was reused.
+    pub connect: Option<Duration>,
+}
+
 impl<C: Connection> Pool<C> {
     /// Create a new connection pool with a default pool configuration and
     /// the given connection URL, and immediately establish one connection.
@@ -347,17 +490,386 @@ impl<C: Connection> Pool<C> {
     ///
     /// This should eliminate any potential `.await` points between acquiring a connection and
     /// returning it.
-    pub fn acquire(&self) -> impl Future<Output=Result<PoolConnection<C>, Error>> + 'static {
+    ///
+    /// ### Spawning an Acquire
+    /// The returned future holds its own handle to the pool instead of borrowing `self`, so it is
+    /// `'static` and can be spawned directly, as can the [`PoolConnection`] it resolves to:
+    ///
+    /// ```no_run
+    /// use poolx::{Connection, Error, Pool, PoolConnection};
+    ///
+    /// async fn spawned<C: Connection>(pool: &Pool<C>) -> Result<PoolConnection<C>, Error> {
+    ///     // No need to clone the pool first.
+    ///     tokio::spawn(pool.acquire()).await.unwrap()
+    /// }
+    ///
+    /// fn worker<C: Connection>(pool: &Pool<C>) -> tokio::task::JoinHandle<()> {
+    ///     let acquire = pool.acquire();
+    ///     tokio::spawn(async move {
+    ///         let conn = acquire.await.unwrap();
+    ///         // the task owns `conn` until it's done with it
+    ///         drop(conn);
+    ///     })
+    /// }
+    /// ```
+    pub fn acquire(&self) -> Acquire<C> {
+        Acquire::new(self, self.0.options.test_before_acquire, AcquirePriority::Normal)
+    }
+
+    /// Retrieves a connection from the pool like [`acquire()`][Self::acquire], but never pings an
+    /// idle connection before handing it out, even with
+    /// [`test_before_acquire(true)`][PoolOptions::test_before_acquire].
+    ///
+    /// This saves a round-trip for requests that can tolerate a failure, like fire-and-forget
+    /// commands. Other acquires still honor the option.
+    ///
+    /// The connection may be stale: the server may have closed it for idling, or the network
+    /// between may have dropped it, and the first command sent on it then fails. The other
+    /// checks still run: connections past their `max_lifetime` are closed, and
+    /// [`before_acquire`][PoolOptions::before_acquire] is called if set.
+    pub fn acquire_without_test(&self) -> Acquire<C> {
+        self.acquire_with_test(false)
+    }
+
+    /// Retrieves a connection from the pool like [`acquire()`][Self::acquire], but always pings
+    /// an idle connection before handing it out, even with
+    /// [`test_before_acquire(false)`][PoolOptions::test_before_acquire].
+    ///
+    /// Use it before work that is costly to redo if the connection turns out to be dead. Other
+    /// acquires still honor the option.
+    pub fn acquire_tested(&self) -> Acquire<C> {
+        self.acquire_with_test(true)
+    }
+
+    /// Retrieves a connection from the pool like [`acquire()`][Self::acquire], with the given
+    /// `priority`.
+    ///
+    /// With [`AcquirePriority::High`], the acquire may take one of the connections held back by
+    /// [`PoolOptions::reserved_connections`], so it can succeed while normal acquires are blocked.
+    /// `acquire_prioritized(AcquirePriority::Normal)` is the same as `acquire()`.
+    pub fn acquire_prioritized(&self, priority: AcquirePriority) -> Acquire<C> {
+        Acquire::new(self, self.0.options.test_before_acquire, priority)
+    }
+
+    /// Retrieves `n` connections from the pool at once, e.g. to run a few queries in parallel
+    /// for a single request.
+    ///
+    /// This waits for room for all `n` connections in a single place in the pool's fair queue,
+    /// rather than acquiring them one at a time. Two tasks each asking for most of the pool then
+    /// take turns instead of deadlocking, each holding some of the connections the other one
+    /// waits for.
+    ///
+    /// The whole call, waiting and then taking or opening each connection, is bounded by one
+    /// [`acquire_timeout`][PoolOptions::acquire_timeout]. If it fails, no connection is kept:
+    /// those already taken are returned to the pool.
+    ///
+    /// Returns [`Error::Configuration`] if `n` is 0 or more than the pool could ever hand out
+    /// at once, i.e. [`max_connections()`][Self::max_connections] minus any
+    /// [`reserved_connections`][PoolOptions::reserved_connections], or if the pool has a
+    /// [parent pool][PoolOptions::parent].
+    pub fn acquire_many(
+        &self,
+        n: u32,
+    ) -> impl Future<Output=Result<Vec<PoolConnection<C>>, Error>> + 'static {
+        let shared = self.0.clone();
+        async move {
+            let deadline = shared.acquire_deadline();
+            let conns = shared.acquire_many(n, deadline).await?;
+            Ok(conns.into_iter().map(|conn| conn.reattach()).collect())
+        }
+    }
+
+    fn acquire_with_test(&self, test: bool) -> Acquire<C> {
+        Acquire::new(self, test, AcquirePriority::Normal)
+    }
+
+    /// Retrieves a connection from the pool like [`acquire()`][Self::acquire], and wraps it in
+    /// `W`.
+    ///
+    /// This is for an application-specific type around [`PoolConnection`] that adds helper
+    /// methods or tracing. The wrapper doesn't need a `Drop` impl of its own: the
+    /// `PoolConnection` inside still returns the connection to the pool when the wrapper is
+    /// dropped.
+    ///
+    /// ```no_run
+    /// use std::ops::{Deref, DerefMut};
+    ///
+    /// use poolx::{Connection, Error, Pool, PoolConnection};
+    ///
+    /// struct AppConn<C: Connection>(PoolConnection<C>);
+    ///
+    /// impl<C: Connection> From<PoolConnection<C>> for AppConn<C> {
+    ///     fn from(conn: PoolConnection<C>) -> Self {
+    ///         AppConn(conn)
+    ///     }
+    /// }
+    ///
+    /// // forward to the connection, so its methods can be called on the wrapper
+    /// impl<C: Connection> Deref for AppConn<C> {
+    ///     type Target = C;
+    ///
+    ///     fn deref(&self) -> &C {
+    ///         &self.0
+    ///     }
+    /// }
+    ///
+    /// impl<C: Connection> DerefMut for AppConn<C> {
+    ///     fn deref_mut(&mut self) -> &mut C {
+    ///         &mut self.0
+    ///     }
+    /// }
+    ///
+    /// impl<C: Connection> AppConn<C> {
+    ///     async fn checked_ping(&mut self) -> Result<(), Error> {
+    ///         tracing::debug!("pinging");
+    ///         self.ping().await
+    ///     }
+    /// }
+    ///
+    /// async fn handler<C: Connection>(pool: &Pool<C>) -> Result<(), Error> {
+    ///     let mut conn: AppConn<C> = pool.acquire_as().await?;
+    ///     conn.checked_ping().await
+    ///     // `conn` goes back to the pool here
+    /// }
+    /// ```
+    pub fn acquire_as<W>(&self) -> impl Future<Output=Result<W, Error>> + 'static
+    where
+        W: From<PoolConnection<C>>,
+    {
+        let acquire = self.acquire();
+        async move { acquire.await.map(W::from) }
+    }
+
+    /// Retrieves a connection from the pool, blocking the current thread until it's available.
+    ///
+    /// This is for synchronous code such as a `Drop` impl or a sync trait method. The acquire
+    /// runs on the runtime the pool was created on, which must be a multi-threaded runtime (or a
+    /// current-thread runtime that another thread is driving with `Runtime::block_on`) for it to
+    /// make progress. Like [`acquire()`][Self::acquire], it is capped by
+    /// [`PoolOptions::acquire_timeout`].
+    ///
+    /// When the returned connection is dropped outside of any runtime, it goes back to the pool
+    /// on the pool's runtime.
+    ///
+    /// Returns [`Error::BlockingInRuntime`] if called from within an async runtime, where
+    /// blocking the thread could deadlock it; use `acquire().await` there instead.
+    pub fn blocking_acquire(&self) -> Result<PoolConnection<C>, Error> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Err(Error::BlockingInRuntime);
+        }
+
+        self.0.runtime.block_on(self.acquire())
+    }
+
+    /// Returns a [`Stream`] that yields a connection from the pool each time it is polled.
+    ///
+    /// Each item is the result of a [`Pool::acquire`], which is only started when the stream is
+    /// polled for the next item, so the stream never holds more connections than the consumer
+    /// asked for. Dropping the stream while an acquire is in flight cancels it like dropping the
+    /// future returned by `acquire()` would, without leaking its permit.
+    ///
+    /// The stream ends after yielding [`Error::PoolClosed`]; any other error is yielded and the
+    /// stream carries on.
+    ///
+    /// The returned stream is not `Unpin`; use `futures_util::pin_mut!` or `Box::pin` to
+    /// poll it.
+    pub fn acquire_stream(
+        &self,
+    ) -> impl Stream<Item=Result<PoolConnection<C>, Error>> + Send + 'static {
+        futures_util::stream::unfold(Some(self.clone()), |pool| async move {
+            let pool = pool?;
+            let res = pool.acquire().await;
+
+            let next = match res {
+                Err(Error::PoolClosed) => None,
+                _ => Some(pool),
+            };
+
+            Some((res, next))
+        })
+    }
+
+    /// Retrieves a connection from the pool, giving up at the absolute `deadline`.
+    ///
+    /// This is like [`Pool::acquire`], but the whole operation is bounded by `deadline` instead of
+    /// [`PoolOptions::acquire_timeout`], which composes better with a deadline propagated from
+    /// upstream (e.g. a request's overall time budget).
+    ///
+    /// Returns [`Error::PoolTimedOut`] if the deadline is reached. If the deadline has already
+    /// passed, this only succeeds if a connection can be taken immediately, as with
+    /// [`Pool::try_acquire`].
+    pub fn acquire_deadline(
+        &self,
+        deadline: Instant,
+    ) -> impl Future<Output=Result<PoolConnection<C>, Error>> + 'static {
+        let shared = self.0.clone();
+        async move {
+            if deadline.saturating_duration_since(clock::now()).is_zero() {
+                if shared.is_closed() {
+                    return Err(Error::PoolClosed);
+                }
+
+                return shared
+                    .try_acquire_normal()
+                    .map(|conn| conn.into_live().reattach())
+                    .ok_or(Error::PoolTimedOut);
+            }
+
+            let test = shared.options.test_before_acquire;
+            shared
+                .acquire_timed(deadline, None, test, AcquirePriority::Normal)
+                .await
+                .map(|conn| conn.reattach())
+        }
+    }
+
+    /// Retrieves a connection from the pool, retrying [`Pool::acquire`] with a backoff while it
+    /// fails with a [retryable][Error::is_retryable] error such as [`Error::PoolTimedOut`].
+    ///
+    /// Makes up to `attempts` acquires in total, and always at least one. Before each retry it
+    /// sleeps for a random delay between half and all of `backoff`, doubling `backoff` each
+    /// time, so that callers which timed out together don't all come back at once. Any other
+    /// error, like [`Error::PoolClosed`], is returned right away; after the last attempt its
+    /// error is returned.
+    ///
+    /// Each attempt is bounded by [`PoolOptions::acquire_timeout`], so the whole call can take
+    /// up to `attempts` times that plus the delays.
+    pub fn acquire_retry(
+        &self,
+        attempts: usize,
+        backoff: Duration,
+    ) -> impl Future<Output=Result<PoolConnection<C>, Error>> + 'static {
+        let pool = self.clone();
+        async move {
+            let mut retry = 0u32;
+            loop {
+                match pool.acquire().await {
+                    Err(e) if e.is_retryable() && (retry as usize) + 1 < attempts => {
+                        clock::sleep(inner::retry_delay(backoff, retry)).await;
+                        retry += 1;
+                    }
+                    res => return res,
+                }
+            }
+        }
+    }
+
+    /// Retrieves a connection from the pool, along with a breakdown of how long each phase took.
+    ///
+    /// This behaves exactly like [`Pool::acquire`], but reports the time spent waiting for
+    /// capacity, testing idle connections and opening a new connection separately, which helps
+    /// tell contention apart from slow connection setup.
+    ///
+    /// If the acquire fails, no timing is returned.
+    pub fn acquire_instrumented(
+        &self,
+    ) -> impl Future<Output=Result<(PoolConnection<C>, AcquireTiming), Error>> + 'static {
         let shared = self.0.clone();
-        async move { shared.acquire().await.map(|conn| conn.reattach()) }
+        async move {
+            let mut timing = AcquireTiming::default();
+            let deadline = shared.acquire_deadline();
+            let test = shared.options.test_before_acquire;
+            let conn = shared
+                .acquire_timed(deadline, Some(&mut timing), test, AcquirePriority::Normal)
+                .await?;
+            Ok((conn.reattach(), timing))
+        }
+    }
+
+    /// Retrieves a connection from the pool, runs `f` with it and returns it to the pool right
+    /// after.
+    ///
+    /// This makes the checkout window explicit, so a connection can't accidentally be held across
+    /// unrelated `.await`s and starve the pool. Errors from [`Pool::acquire`] are returned as-is;
+    /// otherwise the closure's output is returned in `Ok`.
+    ///
+    /// The connection is held by a [`PoolConnection`] for the duration, so it goes back to the pool
+    /// even if the returned future is cancelled. As with dropping a `PoolConnection` at any other
+    /// time, it may then be returned in the middle of whatever `f` was doing.
+    ///
+    /// ```rust,ignore
+    /// let value: Option<String> = pool
+    ///     .with_conn(|conn| Box::pin(async move { conn.get("key").await }))
+    ///     .await??;
+    /// ```
+    pub async fn with_conn<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: for<'c> FnOnce(&'c mut C) -> BoxFuture<'c, T>,
+    {
+        let mut conn = self.acquire().await?;
+        let res = f(&mut conn).await;
+        conn.return_now().await;
+        Ok(res)
     }
 
     /// Attempts to retrieve a connection from the pool if there is one available.
     ///
     /// Returns `None` immediately if there are no idle connections available in the pool
-    /// or there are tasks waiting for a connection which have yet to wake.
+    /// or there are tasks waiting for a connection which have yet to wake, or if the pool is
+    /// closed.
     pub fn try_acquire(&self) -> Option<PoolConnection<C>> {
-        self.0.try_acquire().map(|conn| conn.into_live().reattach())
+        self.0.try_acquire_normal().map(|conn| conn.into_live().reattach())
+    }
+
+    /// Add a connection opened elsewhere to the pool as an idle connection, e.g. one handed over
+    /// from another pool.
+    ///
+    /// The connection counts as just opened for [`max_lifetime`][PoolOptions::max_lifetime],
+    /// see [`add_with_metadata()`][Self::add_with_metadata] to keep its real age. It's added
+    /// as-is: the connect hooks like [`after_connect`][PoolOptions::after_connect] don't run,
+    /// and it has no [extension][PoolConnection::extension].
+    ///
+    /// Gives the connection back if the pool is closed, already has
+    /// [`max_connections`][Self::max_connections] open, or has a
+    /// [parent pool][PoolOptions::parent].
+    pub fn add(&self, conn: C) -> Result<(), C> {
+        self.0.add(conn, clock::now())
+    }
+
+    /// Like [`add()`][Self::add], but for a connection opened at `created_at`, so
+    /// [`max_lifetime`][PoolOptions::max_lifetime] counts its whole age.
+    ///
+    /// A `created_at` in the future is taken as now.
+    pub fn add_with_metadata(&self, conn: C, created_at: Instant) -> Result<(), C> {
+        self.0.add(conn, created_at)
+    }
+
+    /// Check that the pool can hand out a working connection, e.g. for a `/healthz` endpoint.
+    ///
+    /// Takes an idle connection if there is one, or else waits for one or opens a new one like
+    /// [`acquire()`][Self::acquire] does, then [pings][Connection::ping] it. A connection that
+    /// passes is returned to the pool; one that fails the ping, or doesn't answer it in time, is
+    /// closed.
+    ///
+    /// The whole check is capped by `timeout` instead of [`PoolOptions::acquire_timeout`],
+    /// returning [`Error::PoolTimedOut`] if it elapses.
+    pub async fn health_check(&self, timeout: Duration) -> Result<(), Error> {
+        let deadline = clock::now() + timeout;
+
+        let mut conn = match self.try_acquire() {
+            Some(conn) => conn,
+            None => {
+                let test = self.0.options.test_before_acquire;
+                self.0
+                    .acquire_timed(deadline, None, test, AcquirePriority::Normal)
+                    .await?
+                    .reattach()
+            }
+        };
+
+        let res = match deadline_as_timeout(deadline) {
+            Ok(timeout) => tokio::time::timeout(timeout, conn.ping())
+                .await
+                .unwrap_or(Err(Error::PoolTimedOut)),
+            Err(error) => Err(error),
+        };
+
+        match res {
+            Ok(()) => conn.return_now().await,
+            Err(_) => conn.close_hard().await,
+        }
+
+        res
     }
 
     /// Shut down the connection pool, immediately waking all tasks waiting for a connection.
@@ -380,7 +892,10 @@ impl<C: Connection> Pool<C> {
     /// when the last handle for the given pool instance is dropped, which could happen in a task
     /// spawned by `Pool` internally and so may be unpredictable otherwise.
     ///
-    /// `.close()` may be safely called and `.await`ed on multiple handles concurrently.
+    /// `.close()` may be safely called and `.await`ed on multiple handles concurrently, or again
+    /// after it completed: only one call drains the pool, and every call resolves once that
+    /// drain is done. If the call that is draining the pool is cancelled, another one waiting
+    /// takes over.
     pub fn close(&self) -> impl Future<Output=()> + '_ {
         self.0.close()
     }
@@ -466,11 +981,98 @@ impl<C: Connection> Pool<C> {
         self.0.close_event()
     }
 
+    /// Returns a future that resolves once the pool has at least
+    /// [`min_connections`][PoolOptions::min_connections] connections open. Connections still
+    /// being opened don't count, and closed ones stop counting.
+    ///
+    /// Useful for readiness checks, to hold off on serving traffic until the pool is warmed up
+    /// by the background task that maintains `min_connections`. Resolves immediately if
+    /// `min_connections` is 0, and also resolves if the pool is closed in the meantime.
+    ///
+    /// Any number of tasks can wait on this at once, and dropping the future is harmless.
+    pub fn wait_min_connections(&self) -> impl Future<Output=()> + Send + 'static {
+        let shared = self.0.clone();
+        async move { shared.wait_min_connections().await }
+    }
+
     /// Returns the number of connections currently active. This includes idle connections.
     pub fn size(&self) -> u32 {
         self.0.size()
     }
 
+    /// Returns the current maximum number of connections.
+    ///
+    /// This is [`PoolOptions::max_connections`] unless it was changed with [`Pool::resize`],
+    /// or the capacity of the semaphore given to [`PoolOptions::with_shared_semaphore`].
+    pub fn max_connections(&self) -> u32 {
+        self.0.max_connections()
+    }
+
+    /// Change the maximum number of connections at runtime.
+    ///
+    /// Growing takes effect immediately: tasks waiting in [`Pool::acquire`] can open new
+    /// connections right away.
+    ///
+    /// Shrinking is eventual, not immediate. Checked-out connections are never closed out from
+    /// under their users; instead, connections returned to the pool are closed until it is back
+    /// under the new maximum, and the idle reaper closes idle connections over the limit.
+    /// In the meantime [`Pool::size`] may be larger than `new_max`, but no new connections
+    /// are opened.
+    ///
+    /// Neither closes connections below [`PoolOptions::min_connections`]: shrinking under it
+    /// leaves that many connections open, of which only `new_max` can be checked out at once.
+    ///
+    /// For a pool created with [`PoolOptions::parent`], this only changes the cap on its
+    /// own size; permits are still taken from the parent.
+    ///
+    /// This has no effect on a pool created with [`PoolOptions::with_shared_semaphore`], which
+    /// is capped by the shared semaphore instead.
+    ///
+    /// [`PoolOptions::get_max_connections`] keeps returning the value the pool was built with.
+    ///
+    /// # Panics
+    /// If `new_max` is 0.
+    pub fn resize(&self, new_max: u32) {
+        assert!(new_max > 0, "`max_connections` must be at least 1");
+        self.0.resize(new_max)
+    }
+
+    /// Close idle connections right away until the pool is down to `target` connections, e.g.
+    /// in response to a memory alarm, without changing [`max_connections`][Self::max_connections].
+    ///
+    /// Stops early if there are no idle connections left: checked-out connections are never
+    /// touched, so the pool may stay above `target`. Doesn't go below
+    /// [`PoolOptions::min_connections`] either. Idle connections are closed gracefully, one at a
+    /// time, and the pool may grow back afterwards as usual.
+    ///
+    /// Returns the number of connections closed.
+    pub async fn shrink_to(&self, target: u32) -> usize {
+        self.0.shrink_to(target).await
+    }
+
+    /// Stop the idle reaper from closing connections until [`Pool::resume_reaping`] is called.
+    ///
+    /// While paused, idle connections are kept past [`idle_timeout`][PoolOptions::idle_timeout]
+    /// and [`max_lifetime`][PoolOptions::max_lifetime], e.g. to keep the pool warm ahead of an
+    /// expected burst. Connections past their `max_lifetime` are still closed instead of being
+    /// handed out by [`Pool::acquire`], and `min_connections` is still maintained.
+    pub fn pause_reaping(&self) {
+        self.0.pause_reaping()
+    }
+
+    /// Let the idle reaper close connections again after [`Pool::pause_reaping`].
+    ///
+    /// Connections that overstayed during the pause are reaped right away, in a background
+    /// task, rather than at the reaper's next scheduled run.
+    pub fn resume_reaping(&self) {
+        self.0.resume_reaping()
+    }
+
+    /// Returns `true` if reaping was paused with [`Pool::pause_reaping`].
+    pub fn is_reaping_paused(&self) -> bool {
+        self.0.is_reaping_paused()
+    }
+
     /// Returns the number of connections active and idle (not in use).
     ///
     /// As of 0.6.0, this has been fixed to use a separate atomic counter and so should be fine to
@@ -482,7 +1084,105 @@ impl<C: Connection> Pool<C> {
         self.0.num_idle()
     }
 
-    /// Gets a clone of the connection options for this pool
+    /// Returns a snapshot of the metadata of every connection currently in the idle queue.
+    ///
+    /// With [`IdleQueueKind::Deadline`], the connections stay in the idle queue, so acquires can
+    /// take them while this runs. The default [`IdleQueueKind::Fifo`] can't look at them in
+    /// place: they're taken out and put back in the same order, as a run of the reaper does, so
+    /// acquires briefly wait for them. Either way, this is O(n) in the number of idle
+    /// connections.
+    ///
+    /// Useful to spot connections that have been sitting idle for a long time, e.g. before
+    /// [`idle_timeout`][PoolOptions::idle_timeout] reaps them.
+    pub fn idle_connections_info(&self) -> Vec<ConnectionInfo> {
+        self.0.idle_connections_info()
+    }
+
+    /// Returns the number of tasks currently waiting for a connection in [`Pool::acquire`]
+    /// and the like.
+    ///
+    /// An acquire that can take a permit right away never counts. Cancelled and timed-out
+    /// acquires stop counting as soon as they are dropped.
+    pub fn num_pending(&self) -> usize {
+        self.0.num_pending()
+    }
+
+    /// Returns a snapshot of the pool's counters.
+    pub fn statistics(&self) -> PoolStatistics {
+        let (reused, created) = self.0.acquire_sources();
+        PoolStatistics {
+            size: self.size(),
+            num_idle: self.num_idle(),
+            num_pending: self.num_pending(),
+            max_connections: self.max_connections(),
+            release_test_failures: self.0.release_test_failures(),
+            reused,
+            created,
+        }
+    }
+
+    /// Returns the number of semaphore permits currently available, i.e. how many more
+    /// connections could be checked out right now without waiting.
+    ///
+    /// This is a lock-free read, but the value is only advisory: it may already be stale by the
+    /// time it is returned as other tasks acquire and release connections concurrently.
+    pub fn available_permits(&self) -> usize {
+        self.0.semaphore.permits()
+    }
+
+    /// Returns the number of connection slots currently in flight inside the pool, i.e. neither
+    /// idle nor checked out, such as a connection being opened, tested or closed.
+    ///
+    /// Once the pool has settled this must be back to 0; anything else means the pool's size
+    /// or permit accounting has drifted. Meant for tests asserting exactly that, so it is only
+    /// available in debug builds.
+    #[cfg(debug_assertions)]
+    pub fn outstanding_guards(&self) -> isize {
+        self.0.outstanding_guards.load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Returns `true` if the pool is at [`max_connections`][PoolOptions::max_connections] and
+    /// has no permits left, meaning a call to [`Pool::acquire`] would have to wait.
+    /// With [`PoolOptions::with_shared_semaphore`], that's also the case when every connection
+    /// the shared semaphore allows for is open and none of them are idle in this pool.
+    ///
+    /// Useful for shedding load before queueing on the pool. Like
+    /// [`available_permits`][Self::available_permits], this is a lock-free but racy snapshot and
+    /// should only be treated as a hint.
+    pub fn is_saturated(&self) -> bool {
+        self.0.is_saturated()
+    }
+
+    /// Returns the coarse health of the pool.
+    ///
+    /// The pool is [`PoolHealth::Down`] once closed, or while the most recent attempt to open a
+    /// connection has failed. It flips back to [`PoolHealth::Up`] as soon as a connection is
+    /// successfully opened again.
+    pub fn health(&self) -> PoolHealth {
+        if self.0.is_closed() || self.0.is_connect_failing() {
+            PoolHealth::Down
+        } else {
+            PoolHealth::Up
+        }
+    }
+
+    /// Returns the error from the most recent failed attempt to open a connection.
+    ///
+    /// This is cleared as soon as a connection is opened successfully. A pool that doesn't need
+    /// to open any connection keeps reporting the error, so check
+    /// [`Pool::last_connect_error_at()`] to tell an old failure from an ongoing one. Together
+    /// with [`Pool::health()`] this gives a health endpoint something concrete to report
+    /// without waiting for the next failure.
+    pub fn last_connect_error(&self) -> Option<String> {
+        self.0.last_connect_error().map(|(error, _)| error)
+    }
+
+    /// Returns when the error reported by [`Pool::last_connect_error()`] happened.
+    pub fn last_connect_error_at(&self) -> Option<Instant> {
+        self.0.last_connect_error().map(|(_, at)| at)
+    }
+
+    /// Gets a shared handle to the connection options for this pool.
     pub fn connect_options(&self) -> Arc<<C as Connection>::Options> {
         self.0
             .connect_options
@@ -504,14 +1204,49 @@ impl<C: Connection> Pool<C> {
         *guard = Arc::new(connect_options);
     }
 
-    /// Get the options for this pool
+    /// Modify the connection options this pool will use when opening any future connections.
+    ///
+    /// `f` runs while holding the lock on the options, so unlike reading them with
+    /// [`connect_options()`][Self::connect_options] and writing them back with
+    /// [`set_connect_options()`][Self::set_connect_options], concurrent updates can't overwrite
+    /// each other. Connects already in progress keep the options they started with and may use
+    /// either the old or the new ones; existing connections are left as-is.
+    ///
+    /// The options are cloned if a connect is currently holding on to them.
+    pub fn update_connect_options(&self, f: impl FnOnce(&mut <C as Connection>::Options))
+    where
+        <C as Connection>::Options: Clone,
+    {
+        let mut guard = self
+            .0
+            .connect_options
+            .write()
+            .expect("write-lock holder panicked");
+        f(Arc::make_mut(&mut guard));
+    }
+
+    /// Get the options for this pool.
+    ///
+    /// See [`Pool::describe()`] to get them as plain values, e.g. to log them.
     pub fn options(&self) -> &PoolOptions<C> {
         &self.0.options
     }
+
+    /// Get the plain settings of this pool, like [`PoolOptions::describe()`], together with
+    /// the adapter-specific settings of its current connect options in
+    /// [`connect_options`][PoolOptionsSummary::connect_options].
+    pub fn describe(&self) -> PoolOptionsSummary {
+        let mut summary = self.0.options.describe();
+        summary.connect_options = self.connect_options().extra().clone();
+        summary
+    }
 }
 
 
 /// Returns a new [Pool] tied to the same shared connection pool.
+///
+/// This only bumps a reference count: the clone is another handle to the same connections,
+/// limits and state, not a new pool. Clone the pool to move it into `tokio::spawn`ed tasks.
 impl<C: Connection> Clone for Pool<C> {
     fn clone(&self) -> Self {
         Self(Arc::clone(&self.0))
@@ -588,12 +1323,58 @@ impl FusedFuture for CloseEvent {
     }
 }
 
+impl<C: Connection> Future for Acquire<C> {
+    type Output = Result<PoolConnection<C>, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let AcquireState::Start { pool, test, priority } = &self.state {
+            if let Some(conn) = pool.try_acquire_ready(*test, *priority) {
+                self.state = AcquireState::Done;
+                return Poll::Ready(Ok(conn.reattach()));
+            }
+
+            let (pool, test, priority) = (pool.clone(), *test, *priority);
+            self.state = AcquireState::Pending(Box::pin(async move {
+                let deadline = pool.acquire_deadline();
+                pool.acquire_timed(deadline, None, test, priority)
+                    .await
+                    .map(|conn| conn.reattach())
+            }));
+        }
+
+        let AcquireState::Pending(inner) = &mut self.state else {
+            panic!("`Acquire` polled after completion");
+        };
+
+        let res = futures_core::ready!(inner.as_mut().poll(cx));
+
+        // Drop the finished future now rather than with `self`.
+        self.state = AcquireState::Done;
+
+        Poll::Ready(res)
+    }
+}
+
+impl<C: Connection> FusedFuture for Acquire<C> {
+    fn is_terminated(&self) -> bool {
+        matches!(self.state, AcquireState::Done)
+    }
+}
+
+impl<C: Connection> fmt::Debug for Acquire<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Acquire")
+            .field("terminated", &self.is_terminated())
+            .finish()
+    }
+}
+
 /// get the time between the deadline and now and use that as our timeout
 ///
 /// returns `Error::PoolTimedOut` if the deadline is in the past
 fn deadline_as_timeout(deadline: Instant) -> Result<Duration, Error> {
     deadline
-        .checked_duration_since(Instant::now())
+        .checked_duration_since(clock::now())
         .ok_or(Error::PoolTimedOut)
 }
 
@@ -602,9 +1383,2882 @@ fn deadline_as_timeout(deadline: Instant) -> Result<Duration, Error> {
 fn assert_pool_traits() {
     fn assert_send_sync<T: Send + Sync>() {}
     fn assert_clone<T: Clone>() {}
+    fn assert_send_unpin<T: Send + Unpin + 'static>() {}
 
     fn assert_pool<C: Connection>() {
         assert_send_sync::<Pool<C>>();
         assert_clone::<Pool<C>>();
+        assert_send_unpin::<Acquire<C>>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use futures_core::future::BoxFuture;
+    use futures_core::FusedFuture;
+    use futures_util::FutureExt;
+
+    use crate::{
+        clock, AcquirePriority, AsyncSemaphore, ConnectOptions, Connection, Error, IdleQueueKind,
+        InitCommand, Pool, PoolConnection, PoolGroup, PoolHealth, PoolOptions, ReplicaSelection,
+        ReplicatedPool, ReturnMode,
+    };
+
+    /// A connection that never touches the network.
+    ///
+    /// Every connection opened by the same `MockConnectOptions` shares its counters, so tests can
+    /// observe what the pool did to them.
+    pub(crate) struct MockConn {
+        pub(crate) id: u64,
+        /// Returned by `should_discard()`.
+        pub(crate) discard: bool,
+        shared: Arc<MockShared>,
+    }
+
+    #[derive(Debug, Default)]
+    pub(crate) struct MockShared {
+        pub(crate) connects: AtomicU64,
+        pub(crate) pings: AtomicUsize,
+        pub(crate) keepalives: AtomicUsize,
+        pub(crate) rollbacks: AtomicUsize,
+        pub(crate) closes: AtomicUsize,
+        /// The number of `MockConn`s alive right now, and the most there ever were.
+        pub(crate) open: AtomicUsize,
+        pub(crate) max_open: AtomicUsize,
+        /// Connections with an `id` below this value fail `ping()` and `on_idle()`.
+        pub(crate) dead_below: AtomicU64,
+        /// Makes `connect()` fail.
+        pub(crate) fail_connects: AtomicBool,
+        /// Makes `connect()` hang forever, like a black-holed host.
+        pub(crate) hang_connects: AtomicBool,
+        /// The number of `connect()` calls that hung.
+        pub(crate) hung_connects: AtomicUsize,
+    }
+
+    // Deliberately not `Clone`: the pool must not need to copy its options.
+    #[derive(Debug, Default)]
+    pub(crate) struct MockConnectOptions {
+        pub(crate) shared: Arc<MockShared>,
+    }
+
+    impl Drop for MockConn {
+        fn drop(&mut self) {
+            self.shared.open.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    impl Connection for MockConn {
+        type Options = MockConnectOptions;
+
+        fn close(self) -> BoxFuture<'static, Result<(), Error>> {
+            self.shared.closes.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move { Ok(()) })
+        }
+
+        fn close_hard(self) -> BoxFuture<'static, Result<(), Error>> {
+            self.shared.closes.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move { Ok(()) })
+        }
+
+        fn ping(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+            Box::pin(async move {
+                self.shared.pings.fetch_add(1, Ordering::SeqCst);
+                if self.id < self.shared.dead_below.load(Ordering::SeqCst) {
+                    return Err(Error::Io(std::io::ErrorKind::ConnectionReset.into()));
+                }
+                Ok(())
+            })
+        }
+
+        fn rollback_pending(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+            Box::pin(async move {
+                self.shared.rollbacks.fetch_add(1, Ordering::SeqCst);
+                if self.id < self.shared.dead_below.load(Ordering::SeqCst) {
+                    return Err(Error::Io(std::io::ErrorKind::ConnectionReset.into()));
+                }
+                Ok(())
+            })
+        }
+
+        fn on_idle(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+            Box::pin(async move {
+                self.shared.keepalives.fetch_add(1, Ordering::SeqCst);
+                if self.id < self.shared.dead_below.load(Ordering::SeqCst) {
+                    return Err(Error::Io(std::io::ErrorKind::ConnectionReset.into()));
+                }
+                Ok(())
+            })
+        }
+
+        fn should_discard(&self) -> bool {
+            self.discard
+        }
+    }
+
+    impl FromStr for MockConnectOptions {
+        type Err = Error;
+
+        fn from_str(_: &str) -> Result<Self, Self::Err> {
+            Ok(Self::default())
+        }
+    }
+
+    impl ConnectOptions for MockConnectOptions {
+        type Connection = MockConn;
+
+        fn from_url(_: &url::Url) -> Result<Self, Error> {
+            Ok(Self::default())
+        }
+
+        fn connect(&self) -> BoxFuture<'_, Result<Self::Connection, Error>> {
+            Box::pin(async move {
+                if self.shared.hang_connects.load(Ordering::SeqCst) {
+                    self.shared.hung_connects.fetch_add(1, Ordering::SeqCst);
+                    std::future::pending::<()>().await;
+                }
+
+                if self.shared.fail_connects.load(Ordering::SeqCst) {
+                    return Err(Error::Io(std::io::Error::other("mock connect failure")));
+                }
+
+                let id = self.shared.connects.fetch_add(1, Ordering::SeqCst);
+                let open = self.shared.open.fetch_add(1, Ordering::SeqCst) + 1;
+                self.shared.max_open.fetch_max(open, Ordering::SeqCst);
+                Ok(MockConn {
+                    id,
+                    discard: false,
+                    shared: self.shared.clone(),
+                })
+            })
+        }
+    }
+
+    pub(crate) fn mock_pool(options: PoolOptions<MockConn>) -> (Pool<MockConn>, Arc<MockShared>) {
+        let connect_options = MockConnectOptions::default();
+        let shared = connect_options.shared.clone();
+        (options.connect_lazy_with(connect_options), shared)
+    }
+
+    /// Open `n` connections and return them all to the idle queue.
+    pub(crate) async fn fill_idle(pool: &Pool<MockConn>, n: usize) {
+        let mut conns = Vec::with_capacity(n);
+        for _ in 0..n {
+            conns.push(pool.acquire().await.unwrap());
+        }
+        for mut conn in conns {
+            conn.return_to_pool().await;
+        }
+        assert_eq!(pool.num_idle(), n);
+    }
+
+    #[tokio::test]
+    async fn acquire_gives_up_on_dead_idle_connections() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(5)
+                .test_before_acquire_retries(3)
+                .acquire_timeout(Duration::from_secs(5)),
+        );
+
+        fill_idle(&pool, 5).await;
+
+        // Every connection opened so far is now stale.
+        shared.dead_below.store(5, Ordering::SeqCst);
+        let pings = shared.pings.load(Ordering::SeqCst);
+
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!(conn.id, 5);
+        assert_eq!(shared.pings.load(Ordering::SeqCst) - pings, 3);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 3);
+
+        // The discarded connections gave their slots back.
+        assert_eq!(pool.size(), 3);
+        assert_eq!(pool.num_idle(), 2);
+    }
+
+    #[tokio::test]
+    async fn clones_share_the_same_pool() {
+        let (pool, _) = mock_pool(PoolOptions::new());
+        let pool2 = pool.clone();
+
+        let _conn = pool.acquire().await.unwrap();
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool2.size(), 1);
+
+        let _conn2 = tokio::spawn(async move { pool2.acquire().await.unwrap() })
+            .await
+            .unwrap();
+        assert_eq!(pool.size(), 2);
+    }
+
+    #[tokio::test]
+    async fn saturation_follows_checked_out_connections() {
+        let (pool, _) = mock_pool(PoolOptions::new().max_connections(2));
+        assert_eq!(pool.available_permits(), 2);
+        assert!(!pool.is_saturated());
+
+        let _a = pool.acquire().await.unwrap();
+        let _b = pool.acquire().await.unwrap();
+        assert_eq!(pool.available_permits(), 0);
+        assert!(pool.is_saturated());
+    }
+
+    #[tokio::test]
+    async fn replicated_pool_skips_closed_replicas() {
+        let (primary, primary_shared) = mock_pool(PoolOptions::new());
+        let (replica1, replica1_shared) = mock_pool(PoolOptions::new());
+        let (replica2, replica2_shared) = mock_pool(PoolOptions::new());
+
+        let pool = ReplicatedPool::new(
+            primary,
+            vec![replica1.clone(), replica2],
+            ReplicaSelection::RoundRobin,
+        );
+
+        let _w = pool.acquire_write().await.unwrap();
+        let _r1 = pool.acquire_read().await.unwrap();
+        let _r2 = pool.acquire_read().await.unwrap();
+        assert_eq!(primary_shared.connects.load(Ordering::SeqCst), 1);
+        assert_eq!(replica1_shared.connects.load(Ordering::SeqCst), 1);
+        assert_eq!(replica2_shared.connects.load(Ordering::SeqCst), 1);
+
+        drop(_r1);
+        replica1.close().await;
+        let _r3 = pool.acquire_read().await.unwrap();
+        let _r4 = pool.acquire_read().await.unwrap();
+        assert_eq!(replica1_shared.connects.load(Ordering::SeqCst), 1);
+        assert_eq!(replica2_shared.connects.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replicated_pool_retries_a_failed_replica_after_the_backoff() {
+        let (primary, primary_shared) = mock_pool(PoolOptions::new());
+        let (replica1, replica1_shared) = mock_pool(PoolOptions::new());
+        let (replica2, replica2_shared) = mock_pool(PoolOptions::new());
+
+        let pool = ReplicatedPool::new(
+            primary,
+            vec![replica1.clone(), replica2],
+            ReplicaSelection::RoundRobin,
+        )
+            .retry_down_after(Duration::from_secs(5));
+
+        // replica 1 goes down on its first connect
+        replica1_shared.fail_connects.store(true, Ordering::SeqCst);
+        pool.acquire_read().await.unwrap_err();
+        assert_eq!(replica1.health(), PoolHealth::Down);
+
+        // and is skipped for a while
+        let mut reads = Vec::new();
+        for _ in 0..4 {
+            reads.push(pool.acquire_read().await.unwrap());
+        }
+        assert_eq!(replica2_shared.connects.load(Ordering::SeqCst), 4);
+
+        // once it's back and the backoff is over, it gets reads again
+        replica1_shared.fail_connects.store(false, Ordering::SeqCst);
+        tokio::time::advance(Duration::from_secs(5)).await;
+        for _ in 0..2 {
+            reads.push(pool.acquire_read().await.unwrap());
+        }
+        assert_eq!(replica1.health(), PoolHealth::Up);
+        assert_eq!(replica1_shared.connects.load(Ordering::SeqCst), 1);
+        assert_eq!(replica2_shared.connects.load(Ordering::SeqCst), 5);
+        assert_eq!(primary_shared.connects.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn on_close_fires_once_per_connection_and_survives_panics() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls2 = calls.clone();
+
+        let (pool, shared) = mock_pool(PoolOptions::new().on_close(move |_meta| {
+            calls2.fetch_add(1, Ordering::SeqCst);
+            panic!("on_close panicked");
+        }));
+
+        fill_idle(&pool, 3).await;
+        pool.acquire().await.unwrap().close().await.unwrap();
+        pool.close().await;
+
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn disable_reuse_opens_a_fresh_connection_every_time() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .disable_reuse(true)
+                .min_connections(2)
+                .max_connections(2),
+        );
+
+        for expected in 0..3 {
+            let mut conn = pool.acquire().await.unwrap();
+            assert_eq!(conn.id, expected);
+            conn.return_to_pool().await;
+        }
+
+        assert_eq!(pool.num_idle(), 0);
+        assert_eq!(pool.size(), 0);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn close_hard_frees_the_slot() {
+        let (pool, shared) = mock_pool(PoolOptions::new().max_connections(1));
+
+        pool.acquire().await.unwrap().close_hard().await;
+        assert_eq!(pool.size(), 0);
+        assert_eq!(pool.available_permits(), 1);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn acquire_deadline_times_out_at_the_deadline() {
+        let (pool, _) = mock_pool(PoolOptions::new().max_connections(1));
+
+        let conn = pool.acquire().await.unwrap();
+
+        let started = Instant::now();
+        let err = pool
+            .acquire_deadline(started + Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::PoolTimedOut));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        // A deadline in the past behaves like `try_acquire()`.
+        let err = pool.acquire_deadline(started).await.unwrap_err();
+        assert!(matches!(err, Error::PoolTimedOut));
+
+        let mut conn = conn;
+        conn.return_to_pool().await;
+        pool.acquire_deadline(started).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn max_pending_acquires_sheds_load() {
+        let (pool, _) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .max_pending_acquires(1),
+        );
+
+        let _conn = pool.acquire().await.unwrap();
+
+        let waiter = tokio::spawn(pool.acquire());
+        while pool.num_pending() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let err = pool.acquire().await.unwrap_err();
+        assert!(matches!(err, Error::PoolExhausted));
+
+        // Cancelling the waiter frees up its spot in the queue.
+        waiter.abort();
+        let _ = waiter.await;
+        assert_eq!(pool.num_pending(), 0);
+
+        let err = pool
+            .acquire_deadline(Instant::now() + Duration::from_millis(10))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::PoolTimedOut));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn acquires_that_dont_wait_are_not_pending() {
+        let (pool, _) = mock_pool(PoolOptions::new().max_connections(2).reserved_connections(1));
+        fill_idle(&pool, 1).await;
+
+        // Watch `num_pending()` from another thread while acquires never have to wait.
+        let done = Arc::new(AtomicBool::new(false));
+        let watcher = std::thread::spawn({
+            let pool = pool.clone();
+            let done = done.clone();
+            move || {
+                let mut seen = 0;
+                while !done.load(Ordering::Acquire) {
+                    seen = seen.max(pool.num_pending());
+                }
+                seen
+            }
+        });
+
+        for _ in 0..10_000 {
+            pool.acquire().await.unwrap().return_now().await;
+            for conn in pool.acquire_many(1).await.unwrap() {
+                conn.return_now().await;
+            }
+        }
+
+        done.store(true, Ordering::Release);
+        assert_eq!(watcher.join().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn idle_connections_info_leaves_the_queue_intact() {
+        let (pool, _) = mock_pool(PoolOptions::new());
+        fill_idle(&pool, 3).await;
+
+        let info = pool.idle_connections_info();
+        assert_eq!(info.iter().map(|info| info.id).collect::<Vec<_>>(), [0, 1, 2]);
+        assert!(info.iter().all(|info| info.age >= info.idle_for));
+
+        assert_eq!(pool.num_idle(), 3);
+        assert_eq!(pool.size(), 3);
+        assert_eq!(pool.available_permits(), 10);
+        assert_eq!(pool.acquire().await.unwrap().id, 0);
+    }
+
+    #[tokio::test]
+    async fn idle_connections_info_lists_every_kind_of_idle_queue() {
+        for options in [
+            PoolOptions::new().idle_queue(IdleQueueKind::Deadline),
+            PoolOptions::new().prefer_healthy(true),
+        ] {
+            let (pool, _) = mock_pool(options);
+            fill_idle(&pool, 3).await;
+
+            let mut ids: Vec<_> = pool.idle_connections_info().iter().map(|info| info.id).collect();
+            ids.sort();
+            assert_eq!(ids, [0, 1, 2]);
+
+            let conn = pool.acquire().await.unwrap();
+            let ids: Vec<_> = pool.idle_connections_info().iter().map(|info| info.id).collect();
+            assert_eq!(ids.len(), 2);
+            assert!(!ids.contains(&conn.id));
+            assert_eq!(pool.num_idle(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn before_acquire_sync_rejects_connections() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new().before_acquire_sync(|conn: &mut MockConn, _meta| Ok(conn.id != 0)),
+        );
+        fill_idle(&pool, 2).await;
+
+        assert_eq!(pool.acquire().await.unwrap().id, 1);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn last_connect_error_is_cleared_by_a_successful_connect() {
+        let (pool, shared) = mock_pool(PoolOptions::new());
+        assert_eq!(pool.health(), PoolHealth::Up);
+        assert_eq!(pool.last_connect_error_at(), None);
+
+        shared.fail_connects.store(true, Ordering::SeqCst);
+        let before = clock::now();
+        pool.acquire().await.unwrap_err();
+        assert_eq!(pool.health(), PoolHealth::Down);
+        assert!(pool.last_connect_error().unwrap().contains("mock connect failure"));
+        let at = pool.last_connect_error_at().unwrap();
+        assert!(at >= before && at <= clock::now());
+
+        // still reported while nothing is connecting, with its age telling it's old news
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!(pool.last_connect_error().is_some());
+        assert!(clock::elapsed(pool.last_connect_error_at().unwrap()) >= Duration::from_secs(60));
+
+        shared.fail_connects.store(false, Ordering::SeqCst);
+        pool.acquire().await.unwrap();
+        assert_eq!(pool.health(), PoolHealth::Up);
+        assert_eq!(pool.last_connect_error(), None);
+        assert_eq!(pool.last_connect_error_at(), None);
+    }
+
+    #[tokio::test]
+    async fn acquire_stream_yields_connections_on_demand() {
+        use futures_util::StreamExt;
+
+        let (pool, shared) = mock_pool(PoolOptions::new().max_connections(2));
+
+        let stream = pool.acquire_stream();
+        futures_util::pin_mut!(stream);
+
+        let a = stream.next().await.unwrap().unwrap();
+        let b = stream.next().await.unwrap().unwrap();
+        assert_eq!((a.id, b.id), (0, 1));
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 2);
+
+        pool.close().now_or_never();
+        assert!(matches!(stream.next().await, Some(Err(Error::PoolClosed))));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn after_connect_runs_for_warmup_and_on_demand_connections() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls2 = calls.clone();
+
+        let connect_options = MockConnectOptions::default();
+        let shared = connect_options.shared.clone();
+
+        let pool = PoolOptions::new()
+            .min_connections(3)
+            .after_connect(move |_conn: &mut MockConn, _meta| {
+                calls2.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async { Ok(()) })
+            })
+            .connect_with(connect_options)
+            .await
+            .unwrap();
+
+        // Warmed up by `min_connections`.
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 3);
+
+        let mut conns = Vec::new();
+        for _ in 0..5 {
+            conns.push(pool.acquire().await.unwrap());
+        }
+
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 5);
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn connection_extension_is_attached_to_each_connection() {
+        let (pool, _) = mock_pool(PoolOptions::new().connection_extension(|conn: &mut MockConn| {
+            let version = format!("v{}", conn.id);
+            Box::pin(async move { Ok(Arc::new(version) as Arc<dyn std::any::Any + Send + Sync>) })
+        }));
+
+        let a = pool.acquire().await.unwrap();
+        let b = pool.acquire().await.unwrap();
+        assert_eq!(a.extension::<String>().as_deref().map(String::as_str), Some("v0"));
+        assert_eq!(b.extension::<String>().as_deref().map(String::as_str), Some("v1"));
+        assert!(a.extension::<u32>().is_none());
+    }
+
+    #[tokio::test]
+    async fn acquire_fails_fast_on_a_closed_pool() {
+        let (pool, shared) = mock_pool(PoolOptions::new().max_connections(1));
+        let conn = pool.acquire().await.unwrap();
+
+        // Waiting for `conn` when the pool gets closed.
+        let waiter = tokio::spawn(pool.acquire());
+        while pool.num_pending() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let close = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.close().await }
+        });
+        while !pool.is_closed() {
+            tokio::task::yield_now().await;
+        }
+
+        assert!(matches!(waiter.await.unwrap(), Err(Error::PoolClosed)));
+        assert!(matches!(pool.acquire().await, Err(Error::PoolClosed)));
+        assert!(matches!(
+            pool.acquire_deadline(Instant::now()).await,
+            Err(Error::PoolClosed)
+        ));
+        assert!(pool.try_acquire().is_none());
+
+        drop(conn);
+        close.await.unwrap();
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn resize_grows_immediately_and_shrinks_as_connections_return() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .acquire_timeout(Duration::from_millis(50)),
+        );
+
+        let a = pool.acquire().await.unwrap();
+        assert!(pool.is_saturated());
+
+        pool.resize(3);
+        assert_eq!(pool.max_connections(), 3);
+        let b = pool.acquire().await.unwrap();
+        let c = pool.acquire().await.unwrap();
+        assert_eq!(pool.size(), 3);
+
+        pool.resize(1);
+        assert!(matches!(pool.acquire().await, Err(Error::PoolTimedOut)));
+
+        for mut conn in [a, b, c] {
+            conn.return_to_pool().await;
+        }
+
+        // two connections were over the new limit and closed, the last one is kept idle
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool.num_idle(), 1);
+        assert_eq!(pool.available_permits(), 1);
+        assert_eq!(pool.options().get_max_connections(), 1);
+
+        // growing again while nothing is owed hands out permits right away
+        pool.resize(2);
+        assert_eq!(pool.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn shrinking_below_min_connections_keeps_the_minimum_open() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .min_connections(2)
+                .max_connections(4)
+                .acquire_timeout(Duration::from_millis(50)),
+        );
+
+        let mut conns = Vec::new();
+        for _ in 0..4 {
+            conns.push(pool.acquire().await.unwrap());
+        }
+
+        pool.resize(1);
+        for conn in conns {
+            conn.return_now().await;
+        }
+
+        // closed on return down to `min_connections`, not to the new maximum
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.size(), 2);
+        assert_eq!(pool.num_idle(), 2);
+
+        // but only the new maximum can be checked out at once
+        let _conn = pool.acquire().await.unwrap();
+        assert!(matches!(pool.acquire().await, Err(Error::PoolTimedOut)));
+    }
+
+    #[tokio::test]
+    async fn dropping_a_pending_acquire_releases_its_place() {
+        let (pool, _) = mock_pool(PoolOptions::new().max_connections(1));
+        let conn = pool.acquire().await.unwrap();
+
+        let mut acquire = pool.acquire();
+        tokio::select! {
+            biased;
+            _ = &mut acquire => panic!("pool should be exhausted"),
+            _ = tokio::time::sleep(Duration::from_millis(20)) => {}
+        }
+        assert!(!acquire.is_terminated());
+        assert_eq!(pool.num_pending(), 1);
+
+        drop(acquire);
+        assert_eq!(pool.num_pending(), 0);
+
+        drop(conn);
+        let mut acquire = pool.acquire();
+        let _conn = (&mut acquire).await.unwrap();
+        assert!(acquire.is_terminated());
+    }
+
+    #[tokio::test]
+    async fn invalid_connection_limits_are_rejected() {
+        let err = PoolOptions::<MockConn>::new()
+            .max_connections(0)
+            .connect_with(MockConnectOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Configuration(_)), "{err:?}");
+        assert!(err.to_string().contains("`max_connections` must be at least 1"), "{err}");
+
+        let err = PoolOptions::<MockConn>::new()
+            .min_connections(100)
+            .max_connections(10)
+            .connect_with(MockConnectOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Configuration(_)), "{err:?}");
+        assert!(err.to_string().contains("`min_connections` (100) is greater than `max_connections` (10)"), "{err}");
+
+        let err = PoolOptions::<MockConn>::new()
+            .soft_max_connections(20)
+            .max_connections(10)
+            .connect_with(MockConnectOptions::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("`soft_max_connections` (20) is greater than `max_connections` (10)"), "{err}");
+
+        let err = PoolOptions::<MockConn>::new()
+            .reserved_connections(10)
+            .max_connections(10)
+            .connect_with(MockConnectOptions::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("`reserved_connections` (10) must be less than `max_connections` (10)"), "{err}");
+
+        let err = PoolOptions::<MockConn>::new()
+            .max_connections(0)
+            .connect_lazy("mock://")
+            .unwrap_err();
+        assert!(matches!(err, Error::Configuration(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "`min_connections` (3) is greater than `max_connections` (2)")]
+    async fn connect_lazy_with_panics_on_invalid_connection_limits() {
+        let _ = mock_pool(PoolOptions::new().min_connections(3).max_connections(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_timeout_jitter_spreads_out_reaping() {
+        let err = PoolOptions::<MockConn>::new()
+            .idle_timeout_jitter(1.5)
+            .connect_lazy("mock://")
+            .unwrap_err();
+        assert!(matches!(err, Error::Configuration(_)), "{err:?}");
+
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(8)
+                .idle_timeout(Duration::from_millis(400))
+                .idle_timeout_jitter(0.9),
+        );
+        fill_idle(&pool, 8).await;
+
+        // the clock only moves when everything else is waiting, in 5 ms steps up to 2 s
+        let mut seen = Vec::new();
+        for _ in 0..400 {
+            let num_idle = pool.num_idle();
+            if seen.last() != Some(&num_idle) {
+                seen.push(num_idle);
+            }
+            if num_idle == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert_eq!(pool.num_idle(), 0);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 8);
+        // not all closed by the same reaper run
+        assert!(seen.len() > 2, "{seen:?}");
+    }
+
+    #[tokio::test]
+    async fn on_slow_acquire_fires_only_for_outliers() {
+        let slow = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let slow2 = slow.clone();
+
+        let (pool, _) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .slow_acquire_threshold(Duration::from_millis(50))
+                .on_slow_acquire(move |waited| slow2.lock().unwrap().push(waited)),
+        );
+
+        // no contention, no callback
+        drop(pool.acquire().await.unwrap());
+        let conn = pool.acquire().await.unwrap();
+
+        let waiter = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.acquire().await.map(drop) }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(conn);
+        waiter.await.unwrap().unwrap();
+
+        let slow = slow.lock().unwrap();
+        assert_eq!(slow.len(), 1, "{slow:?}");
+        assert!(slow[0] >= Duration::from_millis(100), "{slow:?}");
+    }
+
+    #[tokio::test]
+    async fn pool_group_routes_by_key_and_sums_statistics() {
+        let (a, shared_a) = mock_pool(PoolOptions::new().max_connections(2));
+        let (b, shared_b) = mock_pool(PoolOptions::new().max_connections(3));
+        let group = PoolGroup::new(vec![a, b], |key: &str| key.len());
+
+        let _even = group.acquire_for("ab").await.unwrap();
+        let _odd = group.acquire_for("abc").await.unwrap();
+        let _odd2 = group.acquire_for("a").await.unwrap();
+        assert_eq!(shared_a.connects.load(Ordering::SeqCst), 1);
+        assert_eq!(shared_b.connects.load(Ordering::SeqCst), 2);
+
+        let stats = group.statistics();
+        assert_eq!(stats.size, 3);
+        assert_eq!(stats.num_idle, 0);
+        assert_eq!(stats.max_connections, 5);
+
+        group.close_all().now_or_never();
+        assert!(group.pools().iter().all(Pool::is_closed));
+        assert!(matches!(group.acquire_for("ab").await, Err(Error::PoolClosed)));
+    }
+
+    #[tokio::test]
+    async fn connections_asking_to_be_discarded_are_closed_on_return() {
+        let (pool, shared) = mock_pool(PoolOptions::new().max_connections(2));
+
+        let mut keep = pool.acquire().await.unwrap();
+        let mut discard = pool.acquire().await.unwrap();
+        discard.discard = true;
+
+        keep.return_to_pool().await;
+        discard.return_to_pool().await;
+
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool.num_idle(), 1);
+        assert_eq!(pool.acquire().await.unwrap().id, 0);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn return_now_releases_in_the_callers_task() {
+        let released = Arc::new(AtomicUsize::new(0));
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(2)
+                .min_connections(1)
+                .after_release({
+                    let released = released.clone();
+                    move |_conn, _meta| {
+                        released.fetch_add(1, Ordering::SeqCst);
+                        Box::pin(async { Ok(true) })
+                    }
+                }),
+        );
+
+        let conn = pool.acquire().await.unwrap();
+        // nothing else gets to run on this runtime until we yield
+        conn.return_now().now_or_never().unwrap();
+        assert_eq!(released.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.num_idle(), 1);
+
+        let mut conn = pool.acquire().await.unwrap();
+        conn.discard = true;
+        conn.return_now().await;
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+        // the replacement for `min_connections` was opened in our task as well
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool.num_idle(), 1);
+        assert_eq!(released.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn dropping_connections_around_runtime_shutdown_does_not_leak_permits() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (pool, outlives_runtime) = runtime.block_on(async {
+            let (pool, _) = mock_pool(PoolOptions::new().max_connections(2));
+            // dropped when the runtime cancels this task while shutting down
+            let conn = pool.acquire().await.unwrap();
+            tokio::spawn(async move {
+                let _conn = conn;
+                std::future::pending::<()>().await;
+            });
+            let outlives_runtime = pool.acquire().await.unwrap();
+            (pool, outlives_runtime)
+        });
+        drop(runtime);
+        assert_eq!(pool.size(), 1);
+
+        // dropped with no runtime around at all
+        drop(outlives_runtime);
+        assert_eq!(pool.size(), 0);
+        assert_eq!(pool.0.semaphore.permits(), 2);
+    }
+
+    #[test]
+    fn blocking_acquire_outside_of_the_runtime() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (pool, shared) = {
+            let _guard = runtime.enter();
+            mock_pool(PoolOptions::new().max_connections(1))
+        };
+
+        let conn = pool.blocking_acquire().unwrap();
+        assert_eq!(conn.id, 0);
+        // goes back to the pool on `runtime`
+        drop(conn);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pool.num_idle() != 1 {
+            assert!(Instant::now() < deadline, "connection wasn't returned to the pool");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(pool.blocking_acquire().unwrap().id, 0);
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 1);
+
+        let res = runtime.block_on(async { pool.blocking_acquire().map(|_| ()) });
+        assert!(matches!(res, Err(Error::BlockingInRuntime)));
+    }
+
+    #[tokio::test]
+    async fn shared_semaphore_caps_connections_across_pools() {
+        let semaphore = Arc::new(AsyncSemaphore::new(3));
+        let options = || {
+            PoolOptions::new()
+                .max_connections(10)
+                .acquire_timeout(Duration::from_millis(50))
+                .with_shared_semaphore(semaphore.clone())
+        };
+        let (a, _) = mock_pool(options());
+        let (b, _) = mock_pool(options());
+        assert_eq!(a.max_connections(), 3);
+
+        let a0 = a.acquire().await.unwrap();
+        let a1 = a.acquire().await.unwrap();
+        let b0 = b.acquire().await.unwrap();
+        assert!(a.is_saturated());
+        assert!(matches!(b.acquire().await, Err(Error::PoolTimedOut)));
+
+        // idle connections still count against the shared cap...
+        a1.return_now().await;
+        assert!(matches!(b.acquire().await, Err(Error::PoolTimedOut)));
+        // ...but can be reused by their own pool
+        let a1 = a.acquire().await.unwrap();
+        assert_eq!(a1.id, 1);
+
+        // a waiting acquire picks up a connection released to its pool
+        let acquire = tokio::spawn({
+            let a = a.clone();
+            async move { a.acquire().await.map(|conn| conn.id) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        a0.return_now().await;
+        assert_eq!(acquire.await.unwrap().unwrap(), 0);
+
+        // or one opened with the permit of a connection closed in another pool
+        let acquire = tokio::spawn({
+            let b = b.clone();
+            async move { b.acquire().await.map(|conn| conn.id) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        a1.close().await.unwrap();
+        assert_eq!(acquire.await.unwrap().unwrap(), 1);
+        assert_eq!(a.size() + b.size(), 3);
+
+        b0.return_now().await;
+        a.close().await;
+        b.close().await;
+        assert_eq!(semaphore.permits(), 3);
+    }
+
+    #[tokio::test]
+    async fn shared_semaphore_invalid_options() {
+        let semaphore = Arc::new(AsyncSemaphore::new(2));
+        let (parent, _) = mock_pool(PoolOptions::new());
+
+        let err = PoolOptions::<MockConn>::new()
+            .with_shared_semaphore(semaphore.clone())
+            .parent(parent)
+            .connect_lazy("mock://")
+            .unwrap_err();
+        assert!(err.to_string().contains("parent pool"), "{err}");
+
+        let err = PoolOptions::<MockConn>::new()
+            .min_connections(3)
+            .with_shared_semaphore(semaphore)
+            .connect_lazy("mock://")
+            .unwrap_err();
+        assert!(err.to_string().contains("capacity of the shared semaphore (2)"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn keepalive_interval_keeps_idle_connections_alive() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(2)
+                .keepalive_interval(Duration::from_millis(20)),
+        );
+
+        fill_idle(&pool, 2).await;
+        shared.dead_below.store(1, Ordering::SeqCst);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // the connection failing its keepalive was closed, the other one kept going
+        assert!(shared.keepalives.load(Ordering::SeqCst) >= 3);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool.acquire().await.unwrap().id, 1);
+    }
+
+    #[tokio::test]
+    async fn keepalives_run_concurrently_and_evict_dead_connections() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(6)
+                .keepalive_interval(Duration::from_millis(20))
+                .keepalive_concurrency(2),
+        );
+
+        fill_idle(&pool, 6).await;
+        shared.dead_below.store(3, Ordering::SeqCst);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 3);
+        assert_eq!(pool.size(), 3);
+        assert_eq!(pool.num_idle(), 3);
+
+        let mut ids: Vec<_> = pool.idle_connections_info().iter().map(|info| info.id).collect();
+        ids.sort();
+        assert_eq!(ids, [3, 4, 5]);
+
+        let err = PoolOptions::<MockConn>::new()
+            .keepalive_concurrency(0)
+            .connect_lazy("mock://")
+            .unwrap_err();
+        assert!(err.to_string().contains("`keepalive_concurrency` must be at least 1"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn semaphore_never_grants_zero_permits() {
+        let semaphore = AsyncSemaphore::new(1);
+
+        let permit = semaphore.try_acquire(1).unwrap();
+        assert!(semaphore.try_acquire(0).is_none());
+        assert!(semaphore.acquire(0).now_or_never().is_none());
+
+        drop(permit);
+        let permit = semaphore.try_acquire(0).unwrap();
+        assert_eq!(semaphore.permits(), 0);
+        drop(permit);
+
+        let _permit = semaphore.acquire(0).await.unwrap();
+        assert_eq!(semaphore.permits(), 0);
+    }
+
+    #[tokio::test]
+    async fn semaphore_try_acquire_does_not_jump_waiters() {
+        let semaphore = AsyncSemaphore::new(2);
+        let a = semaphore.try_acquire(1).unwrap();
+        let b = semaphore.try_acquire(1).unwrap();
+
+        // a waiter needing both permits gets the first one released, before it can complete
+        let waiter = semaphore.acquire(2);
+        futures_util::pin_mut!(waiter);
+        assert!(waiter.as_mut().now_or_never().is_none());
+
+        drop(a);
+        assert!(semaphore.try_acquire(1).is_none());
+        assert_eq!(semaphore.permits(), 0);
+
+        drop(b);
+        assert!(semaphore.try_acquire(1).is_none());
+        let permit = waiter.await.unwrap();
+        drop(permit);
+        assert_eq!(semaphore.permits(), 2);
+
+        // and the same through a pool: a queued acquire gets the next connection returned
+        let (pool, _) = mock_pool(PoolOptions::new().max_connections(1));
+        let conn = pool.acquire().await.unwrap();
+        let waiting = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.acquire().await.map(|conn| conn.id) }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(pool.num_pending(), 1);
+
+        let id = conn.id;
+        conn.return_now().await;
+        assert!(pool.try_acquire().is_none());
+        assert_eq!(waiting.await.unwrap().unwrap(), id);
+    }
+
+    #[tokio::test]
+    async fn health_check_pings_a_connection_and_returns_it() {
+        let (pool, shared) = mock_pool(PoolOptions::new().test_before_acquire(false));
+        let timeout = Duration::from_millis(100);
+
+        // opens a connection if there's no idle one...
+        pool.health_check(timeout).await.unwrap();
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 1);
+        assert_eq!(shared.pings.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.num_idle(), 1);
+
+        // ...and reuses it otherwise
+        pool.health_check(timeout).await.unwrap();
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 1);
+        assert_eq!(shared.pings.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.num_idle(), 1);
+
+        shared.dead_below.store(1, Ordering::SeqCst);
+        assert!(matches!(pool.health_check(timeout).await, Err(Error::Io(_))));
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.size(), 0);
+
+        shared.hang_connects.store(true, Ordering::SeqCst);
+        let started = Instant::now();
+        assert!(matches!(pool.health_check(timeout).await, Err(Error::PoolTimedOut)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    async fn size_guards_are_all_accounted_for() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(4)
+                .min_connections(1)
+                .acquire_timeout(Duration::from_millis(50)),
+        );
+
+        fill_idle(&pool, 3).await;
+        assert_eq!(pool.outstanding_guards(), 0);
+
+        // dead idle connections are closed and replaced
+        shared.dead_below.store(2, Ordering::SeqCst);
+        let mut conn = pool.acquire().await.unwrap();
+        conn.discard = true;
+        conn.return_now().await;
+
+        pool.acquire().await.unwrap().close().await.unwrap();
+        pool.acquire().await.unwrap().close_hard().await;
+        drop(pool.acquire().await.unwrap().detach());
+
+        // a cancelled acquire and failing connects
+        let _ = tokio::time::timeout(Duration::from_micros(1), pool.acquire()).await;
+        shared.fail_connects.store(true, Ordering::SeqCst);
+        let held: Vec<_> = std::iter::from_fn(|| pool.try_acquire()).collect();
+        let _ = pool.acquire().await;
+        shared.fail_connects.store(false, Ordering::SeqCst);
+        drop(held);
+
+        pool.resize(1);
+        drop(pool.acquire().await.unwrap());
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(pool.outstanding_guards(), 0);
+
+        pool.close().await;
+        assert_eq!(pool.outstanding_guards(), 0);
+        assert_eq!(pool.size(), 0);
+    }
+
+    #[tokio::test]
+    async fn describe_summarizes_the_effective_options() {
+        let (pool, _) = mock_pool(
+            PoolOptions::new()
+                .max_connections(7)
+                .min_connections(2)
+                .idle_timeout(None)
+                .keepalive_interval(Duration::from_secs(30))
+                .after_release(|_conn, _meta| Box::pin(async { Ok(true) })),
+        );
+
+        let summary = pool.options().describe();
+        assert_eq!(summary.max_connections, 7);
+        assert_eq!(summary.min_connections, 2);
+        assert_eq!(summary.idle_timeout, None);
+        assert_eq!(summary.keepalive_interval, Some(Duration::from_secs(30)));
+        assert_eq!(summary.acquire_timeout, PoolOptions::<MockConn>::new().get_acquire_timeout());
+        assert!(!summary.has_parent_pool);
+        assert_eq!(summary.shared_semaphore_capacity, None);
+        // the mock adapter has no settings of its own
+        assert!(summary.connect_options.is_empty());
+        assert_eq!(pool.describe(), summary);
+
+        // the options a pool was built with, not its current limits
+        pool.resize(3);
+        assert_eq!(pool.options().describe(), summary);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn churning_stale_idle_connections_never_exceeds_max_connections() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(3)
+                .test_before_acquire_retries(5)
+                .acquire_timeout(Duration::from_secs(5)),
+        );
+
+        for _ in 0..20 {
+            // every connection opened so far is stale
+            shared.dead_below.store(shared.connects.load(Ordering::SeqCst), Ordering::SeqCst);
+
+            let tasks: Vec<_> = (0..10)
+                .map(|_| {
+                    let pool = pool.clone();
+                    tokio::spawn(async move {
+                        let conn = pool.acquire().await.unwrap();
+                        tokio::task::yield_now().await;
+                        conn.return_now().await;
+                    })
+                })
+                .collect();
+
+            for task in tasks {
+                task.await.unwrap();
+            }
+        }
+
+        assert!(shared.closes.load(Ordering::SeqCst) > 0);
+        assert!(shared.max_open.load(Ordering::SeqCst) <= 3);
+        assert!(pool.size() <= 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn waiters_are_served_in_arrival_order() {
+        let (pool, _) = mock_pool(
+            PoolOptions::new()
+                .max_connections(3)
+                .acquire_timeout(Duration::from_secs(10)),
+        );
+
+        // check out every connection so all the tasks below have to queue
+        let mut held = Vec::new();
+        for _ in 0..3 {
+            held.push(pool.acquire().await.unwrap());
+        }
+
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut tasks = Vec::new();
+
+        for i in 0..30 {
+            let pool2 = pool.clone();
+            let order = order.clone();
+            tasks.push(tokio::spawn(async move {
+                let conn = pool2.acquire().await.unwrap();
+                order.lock().unwrap().push(i);
+                tokio::task::yield_now().await;
+                conn.return_now().await;
+            }));
+
+            // wait for it to queue up, so the arrival order is known
+            while pool.num_pending() <= i {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        for conn in held {
+            conn.return_now().await;
+        }
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        // Permits are granted strictly in order, but each task holds its permit until after
+        // it has recorded itself, so only the other 2 holding one at the same time can record
+        // out of order with it.
+        let order = order.lock().unwrap();
+        assert_eq!(order.len(), 30);
+        for (served, arrived) in order.iter().enumerate() {
+            assert!(served.abs_diff(*arrived) <= 2, "{order:?}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn min_connections_maintenance_never_overshoots_max_connections() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(3)
+                .min_connections(3)
+                .acquire_timeout(Duration::from_secs(5)),
+        );
+
+        for round in 0..20 {
+            // hold every connection the pool may have
+            let mut held = Vec::new();
+            for _ in 0..3 {
+                held.push(pool.acquire().await.unwrap());
+            }
+
+            let tasks: Vec<_> = held
+                .into_iter()
+                .enumerate()
+                .map(|(i, mut conn)| {
+                    let pool = pool.clone();
+                    tokio::spawn(async move {
+                        // closing a connection sets off `min_connections` maintenance, racing
+                        // the connections being returned and the acquires below
+                        conn.discard = (i + round) % 2 == 0;
+                        drop(conn);
+                        drop(pool.acquire().await.unwrap());
+                    })
+                })
+                .collect();
+
+            for task in tasks {
+                task.await.unwrap();
+            }
+
+            assert!(pool.size() <= 3);
+        }
+
+        assert!(shared.closes.load(Ordering::SeqCst) > 0);
+        assert!(shared.max_open.load(Ordering::SeqCst) <= 3);
+    }
+
+    // Single-threaded and without yielding, so `min_connections` maintenance never gets to run.
+    #[tokio::test(flavor = "current_thread")]
+    async fn prefer_grow_below_min_opens_instead_of_reusing() {
+        for prefer in [false, true] {
+            let (pool, shared) = mock_pool(
+                PoolOptions::new()
+                    .min_connections(3)
+                    .prefer_grow_below_min(prefer),
+            );
+
+            let conn = pool.acquire().now_or_never().unwrap().unwrap();
+            conn.return_now().now_or_never().unwrap();
+            assert_eq!(pool.num_idle(), 1);
+
+            let conn = pool.acquire().now_or_never().unwrap().unwrap();
+            if prefer {
+                assert_eq!(conn.id, 1);
+                assert_eq!(pool.num_idle(), 1);
+            } else {
+                assert_eq!(conn.id, 0);
+                assert_eq!(pool.num_idle(), 0);
+            }
+            assert_eq!(shared.connects.load(Ordering::SeqCst), 1 + prefer as u64);
+            conn.return_now().now_or_never().unwrap();
+
+            // at the floor, idle connections are reused again
+            if prefer {
+                let _conn = pool.acquire().now_or_never().unwrap().unwrap();
+                let _conn = pool.acquire().now_or_never().unwrap().unwrap();
+                assert_eq!(pool.size(), 3);
+                assert_eq!(shared.connects.load(Ordering::SeqCst), 3);
+            }
+        }
+    }
+
+    #[cfg(feature = "tower")]
+    #[tokio::test]
+    async fn pool_service_reserves_a_connection_in_poll_ready() {
+        use tower_service::Service;
+
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .acquire_timeout(Duration::from_millis(50)),
+        );
+        fn add_id(conn: &mut MockConn, n: u64) -> BoxFuture<'_, Result<u64, Error>> {
+            Box::pin(async move { Ok(conn.id + n) })
+        }
+
+        let mut service = crate::PoolService::new(pool.clone(), add_id);
+        let mut other = service.clone();
+
+        futures_util::future::poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        assert_eq!(pool.num_idle(), 0);
+
+        // the only connection is reserved
+        let err = futures_util::future::poll_fn(|cx| other.poll_ready(cx)).await.unwrap_err();
+        assert!(matches!(err, Error::PoolTimedOut));
+
+        assert_eq!(service.call(10).await.unwrap(), 10);
+        assert_eq!(pool.num_idle(), 1);
+
+        futures_util::future::poll_fn(|cx| other.poll_ready(cx)).await.unwrap();
+        assert_eq!(other.call(1).await.unwrap(), 1);
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn validate_on_connect_replaces_rejected_connections() {
+        fn skip_first(conn: &mut MockConn) -> BoxFuture<'_, Result<bool, Error>> {
+            Box::pin(async move { Ok(conn.id > 0) })
+        }
+
+        let (pool, shared) = mock_pool(PoolOptions::new().validate_on_connect(skip_first));
+
+        assert_eq!(pool.acquire().await.unwrap().id, 1);
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 2);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.size(), 1);
+    }
+
+    #[tokio::test]
+    async fn validate_on_connect_gives_up_on_a_misconfigured_backend() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .acquire_timeout(Duration::from_secs(30))
+                .validate_on_connect(|_| Box::pin(async { Ok(false) })),
+        );
+
+        let started = Instant::now();
+        let err = pool.acquire().await.unwrap_err();
+        assert!(matches!(err, Error::ConnectionRejected), "{err:?}");
+        // didn't wait for `acquire_timeout`
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 3);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 3);
+        assert_eq!(pool.size(), 0);
+        assert!(pool.last_connect_error().unwrap().contains("validate_on_connect"));
+    }
+
+    #[tokio::test]
+    async fn connections_failing_their_first_ping_surface_the_error() {
+        // the backend accepts connections, but every command on them fails
+        fn ping(conn: &mut MockConn) -> BoxFuture<'_, Result<bool, Error>> {
+            Box::pin(async move { conn.ping().await.map(|()| true) })
+        }
+
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .acquire_timeout(Duration::from_secs(30))
+                .validate_on_connect(ping),
+        );
+        shared.dead_below.store(u64::MAX, Ordering::SeqCst);
+
+        let started = Instant::now();
+        let err = pool.acquire().await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        match &err {
+            Error::ConnectFailed(source) => assert!(matches!(**source, Error::Io(_)), "{source:?}"),
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert!(!err.is_retryable());
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 3);
+        assert_eq!(pool.size(), 0);
+        // connecting isn't mistaken for the backend being up
+        assert_eq!(pool.health(), PoolHealth::Down);
+
+        shared.dead_below.store(0, Ordering::SeqCst);
+        pool.acquire().await.unwrap();
+        assert_eq!(pool.health(), PoolHealth::Up);
+    }
+
+    #[tokio::test]
+    async fn hanging_after_release_is_cut_off_by_release_timeout() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .release_timeout(Duration::from_millis(50))
+                .after_release(|_conn, _meta| Box::pin(std::future::pending())),
+        );
+
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!(conn.id, 0);
+        drop(conn);
+
+        // the permit comes back once the hook is abandoned, and the connection is closed
+        let conn = tokio::time::timeout(Duration::from_secs(5), pool.acquire())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(conn.id, 1);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.size(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_timeout_and_max_lifetime_follow_the_paused_clock() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .idle_timeout(Duration::from_secs(10 * 60))
+                .max_lifetime(Duration::from_secs(30 * 60)),
+        );
+        fill_idle(&pool, 2).await;
+
+        tokio::time::advance(Duration::from_secs(9 * 60)).await;
+        assert_eq!(pool.num_idle(), 2);
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!(conn.id, 0);
+
+        // the reaper's sleep completes as soon as the runtime is idle
+        tokio::time::sleep(Duration::from_secs(11 * 60)).await;
+        assert_eq!(pool.num_idle(), 0);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+
+        // the checked-out connection is past its lifetime by the time it's returned
+        tokio::time::advance(Duration::from_secs(20 * 60)).await;
+        conn.return_now().await;
+        assert_eq!(pool.acquire().await.unwrap().id, 2);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    #[should_panic(expected = "released to a pool it doesn't belong to")]
+    async fn releasing_a_connection_to_another_pool_panics() {
+        use crate::connection::Floating;
+        use crate::inner::DecrementSizeGuard;
+
+        let (a, _) = mock_pool(PoolOptions::new());
+        let (b, _) = mock_pool(PoolOptions::new());
+
+        let raw = a.acquire().await.unwrap().leak();
+        let floating = Floating::new_live(raw, None, DecrementSizeGuard::new_permit(a.0.clone()));
+        b.0.release(floating);
+    }
+
+    #[test]
+    fn error_is_retryable() {
+        use std::io::ErrorKind;
+
+        assert!(Error::PoolTimedOut.is_retryable());
+        assert!(Error::PoolExhausted.is_retryable());
+        assert!(Error::Io(ErrorKind::ConnectionReset.into()).is_retryable());
+        assert!(Error::Io(ErrorKind::TimedOut.into()).is_retryable());
+
+        assert!(!Error::PoolClosed.is_retryable());
+        assert!(!Error::Configuration("bad url".into()).is_retryable());
+        assert!(!Error::ConnectionRejected.is_retryable());
+        assert!(!Error::Io(ErrorKind::PermissionDenied.into()).is_retryable());
+        assert!(!Error::Other(anyhow::anyhow!("unknown")).is_retryable());
+    }
+
+    #[tokio::test]
+    async fn cancelling_an_acquire_during_connect_frees_its_slot() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .acquire_timeout(Duration::from_secs(60)),
+        );
+
+        shared.hang_connects.store(true, Ordering::SeqCst);
+        tokio::time::timeout(Duration::from_millis(50), pool.acquire())
+            .await
+            .unwrap_err();
+        assert_eq!(shared.hung_connects.load(Ordering::SeqCst), 1);
+
+        assert_eq!(pool.size(), 0);
+        assert_eq!(pool.0.semaphore.permits(), 1);
+        #[cfg(debug_assertions)]
+        assert_eq!(pool.outstanding_guards(), 0);
+
+        shared.hang_connects.store(false, Ordering::SeqCst);
+        pool.acquire().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancelling_an_acquire_during_after_connect_closes_the_connection() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .acquire_timeout(Duration::from_secs(60))
+                .after_connect(|conn: &mut MockConn, _meta| {
+                    Box::pin(async move {
+                        if conn.id == 0 {
+                            std::future::pending::<()>().await;
+                        }
+                        Ok(())
+                    })
+                }),
+        );
+
+        tokio::time::timeout(Duration::from_millis(50), pool.acquire())
+            .await
+            .unwrap_err();
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 1);
+
+        // closed in a spawned task
+        tokio::task::yield_now().await;
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+        assert_eq!(shared.open.load(Ordering::SeqCst), 0);
+        assert_eq!(pool.size(), 0);
+        assert_eq!(pool.0.semaphore.permits(), 1);
+
+        assert_eq!(pool.acquire().await.unwrap().id, 1);
+    }
+
+    /// Records the level and message of every event.
+    #[derive(Clone, Default)]
+    struct CapturedEvents(Arc<std::sync::Mutex<Vec<(tracing::Level, String)>>>);
+
+    impl tracing::Subscriber for CapturedEvents {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            struct Message(String);
+
+            impl tracing::field::Visit for Message {
+                fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                    if field.name() == "message" {
+                        self.0 = format!("{value:?}");
+                    }
+                }
+            }
+
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.0.lock().unwrap().push((*event.metadata().level(), message.0));
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    // Single-threaded, as the subscriber is only set for this thread.
+    #[tokio::test(flavor = "current_thread")]
+    async fn log_settings_set_the_level_of_lifecycle_events() {
+        use crate::{LevelFilter, LogSettings};
+
+        let captured = CapturedEvents::default();
+        let _guard = tracing::subscriber::set_default(captured.clone());
+
+        let settings = LogSettings::default()
+            .connect_level(LevelFilter::INFO)
+            .close_level(LevelFilter::OFF);
+        let (pool, _) = mock_pool(PoolOptions::new().log_settings(settings.clone()));
+        assert_eq!(pool.options().describe().log_settings, settings);
+
+        pool.acquire().await.unwrap().close_hard().await;
+
+        let events = captured.0.lock().unwrap();
+        assert!(
+            events.contains(&(tracing::Level::INFO, "opened a new connection".to_string())),
+            "{events:?}"
+        );
+        assert!(!events.iter().any(|(_, message)| message.contains("closing")), "{events:?}");
+    }
+
+    #[tokio::test]
+    async fn acquire_retry_backs_off_until_a_connection_is_free() {
+        let (pool, _) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .acquire_timeout(Duration::from_millis(20)),
+        );
+
+        let conn = pool.acquire().await.unwrap();
+        let start = Instant::now();
+        let err = pool.acquire_retry(3, Duration::from_millis(10)).await.unwrap_err();
+        assert!(matches!(err, Error::PoolTimedOut));
+        // three timeouts, plus delays of at least 5ms and 10ms
+        assert!(start.elapsed() >= Duration::from_millis(75), "{:?}", start.elapsed());
+
+        let holder = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            drop(conn);
+        });
+        pool.acquire_retry(5, Duration::from_millis(10)).await.unwrap();
+        holder.await.unwrap();
+
+        // a closed pool isn't retried
+        pool.close().await;
+        let start = Instant::now();
+        let err = pool.acquire_retry(5, Duration::from_secs(10)).await.unwrap_err();
+        assert!(matches!(err, Error::PoolClosed));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn retry_delay_is_jittered_within_the_doubled_backoff() {
+        let backoff = Duration::from_millis(100);
+        for retry in 0..4 {
+            let cap = backoff * (1 << retry);
+            let delays: Vec<_> = (0..20).map(|_| crate::inner::retry_delay(backoff, retry)).collect();
+            assert!(delays.iter().all(|d| *d >= cap / 2 && *d <= cap), "{delays:?}");
+            assert!(delays.iter().any(|d| *d != delays[0]), "{delays:?}");
+        }
+
+        // doesn't overflow
+        assert!(crate::inner::retry_delay(backoff, 1000) > Duration::from_secs(86400));
+    }
+
+    #[tokio::test]
+    async fn acquire_as_wraps_a_connection_that_still_returns_on_drop() {
+        struct Wrapped(PoolConnection<MockConn>);
+
+        impl From<PoolConnection<MockConn>> for Wrapped {
+            fn from(conn: PoolConnection<MockConn>) -> Self {
+                Wrapped(conn)
+            }
+        }
+
+        let (pool, shared) = mock_pool(PoolOptions::new().max_connections(1));
+
+        let wrapped: Wrapped = pool.acquire_as().await.unwrap();
+        assert_eq!(wrapped.0.id, 0);
+        assert_eq!(pool.num_idle(), 0);
+        drop(wrapped);
+
+        // the same connection comes back out of the pool
+        let wrapped: Wrapped = pool.acquire_as().await.unwrap();
+        assert_eq!(wrapped.0.id, 0);
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn on_saturation_change_fires_on_edges_only() {
+        let changes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let changes2 = changes.clone();
+        let (pool, _) = mock_pool(
+            PoolOptions::new()
+                .max_connections(2)
+                .on_saturation_change(move |saturated| changes2.lock().unwrap().push(saturated)),
+        );
+
+        // acquiring with permits to spare doesn't saturate the pool
+        let a = pool.acquire().await.unwrap();
+        assert!(changes.lock().unwrap().is_empty());
+
+        // taking the last one does, the same as `is_saturated()` tells
+        let b = pool.acquire().await.unwrap();
+        assert!(pool.is_saturated());
+        assert_eq!(*changes.lock().unwrap(), [true]);
+
+        // two waiters, but no more transitions
+        let waiters: Vec<_> = (0..2).map(|_| tokio::spawn(pool.acquire())).collect();
+        while pool.num_pending() < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(*changes.lock().unwrap(), [true]);
+
+        // handing connections over to the waiters leaves the pool saturated
+        b.return_now().await;
+        let mut waiters = waiters.into_iter();
+        let b = waiters.next().unwrap().await.unwrap().unwrap();
+        b.return_now().await;
+        let b = waiters.next().unwrap().await.unwrap().unwrap();
+        assert_eq!(*changes.lock().unwrap(), [true]);
+
+        b.return_now().await;
+        assert!(!pool.is_saturated());
+        assert_eq!(*changes.lock().unwrap(), [true, false]);
+        drop(a);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn saturation_debounce_only_reports_lasting_changes() {
+        let changes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let changes2 = changes.clone();
+        let (pool, _) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .saturation_debounce(Duration::from_millis(100))
+                .on_saturation_change(move |saturated| changes2.lock().unwrap().push(saturated)),
+        );
+        assert_eq!(pool.options().describe().saturation_debounce, Duration::from_millis(100));
+
+        // saturated for less than the debounce
+        let conn = pool.acquire().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        conn.return_now().await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(changes.lock().unwrap().is_empty());
+
+        // and for longer, reported once the debounce is over
+        let conn = pool.acquire().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(99)).await;
+        assert!(changes.lock().unwrap().is_empty());
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        assert_eq!(*changes.lock().unwrap(), [true]);
+
+        // the same goes for the way back
+        conn.return_now().await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let conn = pool.acquire().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(*changes.lock().unwrap(), [true]);
+
+        conn.return_now().await;
+        tokio::time::sleep(Duration::from_millis(101)).await;
+        assert_eq!(*changes.lock().unwrap(), [true, false]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_closes_close_each_connection_once() {
+        let (pool, shared) = mock_pool(PoolOptions::new().max_connections(4));
+        fill_idle(&pool, 3).await;
+        let conn = pool.acquire().await.unwrap();
+
+        let closes: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move { pool.close().await })
+            })
+            .collect();
+
+        // nobody finishes while a connection is still checked out
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(closes.iter().all(|close| !close.is_finished()));
+
+        drop(conn);
+        for close in closes {
+            tokio::time::timeout(Duration::from_secs(5), close).await.unwrap().unwrap();
+        }
+
+        // closing again afterwards is a no-op
+        pool.close().await;
+
+        assert_eq!(shared.closes.load(Ordering::SeqCst), shared.connects.load(Ordering::SeqCst) as usize);
+        assert_eq!(shared.open.load(Ordering::SeqCst), 0);
+        assert_eq!(pool.size(), 0);
+        assert_eq!(pool.num_idle(), 0);
+        // every permit given back exactly once
+        assert_eq!(pool.0.semaphore.permits(), 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn return_mode_block_returns_the_connection_before_drop_returns() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .return_on_drop(ReturnMode::Block)
+                .after_release(|_conn, _meta| {
+                    Box::pin(async {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        Ok(true)
+                    })
+                }),
+        );
+
+        let conn = pool.acquire().await.unwrap();
+        drop(conn);
+        // no yield in between
+        assert_eq!(pool.num_idle(), 1);
+
+        pool.acquire().await.unwrap();
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn return_mode_block_does_not_wait_to_replace_a_closed_connection() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .min_connections(1)
+                .return_on_drop(ReturnMode::Block),
+        );
+
+        let mut conn = pool.acquire().await.unwrap();
+        conn.discard = true;
+        shared.hang_connects.store(true, Ordering::SeqCst);
+
+        let start = Instant::now();
+        drop(conn);
+        // the replacement never connects, but that's not the drop's problem
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(pool.size(), 0);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "can't block a current-thread runtime")]
+    async fn return_mode_block_panics_on_a_current_thread_runtime() {
+        let (pool, _) = mock_pool(PoolOptions::new().return_on_drop(ReturnMode::Block));
+        drop(pool.acquire().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn return_mode_detach_drops_the_connection() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .return_on_drop(ReturnMode::Detach),
+        );
+
+        let conn = pool.acquire().await.unwrap();
+        drop(conn);
+        // gone right away, without being closed gracefully
+        assert_eq!(pool.size(), 0);
+        assert_eq!(pool.num_idle(), 0);
+        assert_eq!(shared.open.load(Ordering::SeqCst), 0);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 0);
+
+        // its slot is free for a new connection
+        let mut conn = pool.acquire().await.unwrap();
+        assert_eq!(conn.id, 1);
+
+        // explicit returns still go back to the pool
+        conn.return_to_pool().await;
+        assert_eq!(pool.num_idle(), 1);
+    }
+
+    #[tokio::test]
+    async fn rollback_on_release_rolls_back_returned_connections() {
+        let (pool, shared) = mock_pool(PoolOptions::new());
+
+        let conn = pool.acquire().await.unwrap();
+        conn.return_now().await;
+        assert_eq!(shared.rollbacks.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.num_idle(), 1);
+
+        // a connection that can't roll back is closed instead of reused
+        let conn = pool.acquire().await.unwrap();
+        shared.dead_below.store(1, Ordering::SeqCst);
+        conn.return_now().await;
+        assert_eq!(shared.rollbacks.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.num_idle(), 0);
+        assert_eq!(pool.size(), 0);
+
+        let (pool, shared) = mock_pool(PoolOptions::new().rollback_on_release(false));
+        pool.acquire().await.unwrap().return_now().await;
+        assert_eq!(shared.rollbacks.load(Ordering::SeqCst), 0);
+        assert!(!pool.options().describe().rollback_on_release);
+    }
+
+    #[tokio::test]
+    async fn statistics_count_release_test_failures() {
+        let closed = Arc::new(AtomicUsize::new(0));
+        let closed2 = closed.clone();
+        let (pool, _) = mock_pool(
+            PoolOptions::new()
+                .after_release(|conn: &mut MockConn, _meta| {
+                    let keep = conn.id != 1;
+                    Box::pin(async move { Ok(keep) })
+                })
+                .on_close(move |_meta| {
+                    closed2.fetch_add(1, Ordering::SeqCst);
+                }),
+        );
+
+        let conns = [pool.acquire().await.unwrap(), pool.acquire().await.unwrap()];
+        for conn in conns {
+            conn.return_now().await;
+        }
+
+        // only the second one was rejected
+        assert_eq!(pool.statistics().release_test_failures, 1);
+        assert_eq!(closed.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.num_idle(), 1);
+
+        // closing a pool isn't a failure
+        pool.acquire().await.unwrap().return_now().await;
+        pool.close().await;
+        assert_eq!(pool.statistics().release_test_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn on_connect_commands_run_in_order_on_new_connections() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let command = |name: &'static str| {
+            let log = log.clone();
+            InitCommand::new(name, move |conn: &mut MockConn| {
+                log.lock().unwrap().push((conn.id, name));
+                Box::pin(async move { Ok(()) })
+            })
+        };
+        let (pool, _) = mock_pool(
+            PoolOptions::new().on_connect_commands(vec![command("first"), command("second")]),
+        );
+
+        let conn = pool.acquire().await.unwrap();
+        let id = conn.id;
+        conn.return_now().await;
+        pool.acquire().await.unwrap().return_now().await;
+        assert_eq!(*log.lock().unwrap(), [(id, "first"), (id, "second")]);
+        assert_eq!(pool.options().describe().on_connect_commands, ["first", "second"]);
+
+        // a failing command discards the connection, like a failing `after_connect`
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .acquire_timeout(Duration::from_millis(100))
+                .on_connect_commands(vec![InitCommand::new("fail", |_conn: &mut MockConn| {
+                    Box::pin(async move { Err(Error::PoolClosed) })
+                })]),
+        );
+        assert!(matches!(pool.acquire().await, Err(Error::PoolTimedOut)));
+        assert_eq!(pool.size(), 0);
+        assert!(shared.closes.load(Ordering::SeqCst) >= 1);
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[tokio::test]
+    async fn with_meter_reports_pool_metrics() {
+        use opentelemetry::metrics::MeterProvider;
+        use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+        use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let (pool, _) = mock_pool(PoolOptions::new().with_meter(provider.meter("test")));
+        assert!(pool.options().describe().has_meter);
+
+        let held = pool.acquire().await.unwrap();
+        pool.acquire().await.unwrap().return_now().await;
+        pool.acquire().await.unwrap().return_now().await;
+
+        provider.force_flush().unwrap();
+        let exported = exporter.get_finished_metrics().unwrap();
+        let metrics = exported.last().unwrap().scope_metrics().flat_map(|scope| scope.metrics());
+        let mut seen = Vec::new();
+        for metric in metrics {
+            let value = match metric.data() {
+                AggregatedMetrics::U64(MetricData::Gauge(gauge)) => {
+                    gauge.data_points().next().unwrap().value()
+                }
+                AggregatedMetrics::U64(MetricData::Sum(sum)) => sum.data_points().next().unwrap().value(),
+                AggregatedMetrics::F64(MetricData::Histogram(histogram)) => {
+                    histogram.data_points().next().unwrap().count()
+                }
+                other => panic!("unexpected data for {}: {other:?}", metric.name()),
+            };
+            seen.push((metric.name().to_string(), value));
+        }
+        seen.sort();
+
+        assert_eq!(
+            seen,
+            [
+                ("poolx.acquire.wait_time".to_string(), 3),
+                ("poolx.acquires.pending".to_string(), 0),
+                ("poolx.connect.duration".to_string(), 2),
+                ("poolx.connections".to_string(), 2),
+                ("poolx.connections.idle".to_string(), 1),
+                ("poolx.connects".to_string(), 2),
+            ]
+        );
+        drop(held);
+    }
+
+    #[tokio::test]
+    async fn acquire_overrides_test_before_acquire_per_call() {
+        let (pool, shared) = mock_pool(PoolOptions::new().test_before_acquire(true));
+        fill_idle(&pool, 1).await;
+
+        pool.acquire_without_test().await.unwrap().return_now().await;
+        assert_eq!(shared.pings.load(Ordering::SeqCst), 0);
+        pool.acquire().await.unwrap().return_now().await;
+        assert_eq!(shared.pings.load(Ordering::SeqCst), 1);
+
+        // a dead connection is handed out as-is without the test...
+        shared.dead_below.store(1, Ordering::SeqCst);
+        let conn = pool.acquire_without_test().await.unwrap();
+        assert_eq!(conn.id, 0);
+        conn.return_now().await;
+
+        let (pool, shared) = mock_pool(PoolOptions::new().test_before_acquire(false));
+        fill_idle(&pool, 1).await;
+        pool.acquire().await.unwrap().return_now().await;
+        assert_eq!(shared.pings.load(Ordering::SeqCst), 0);
+
+        // ...and replaced with a new one with it
+        shared.dead_below.store(1, Ordering::SeqCst);
+        let conn = pool.acquire_tested().await.unwrap();
+        assert_eq!(shared.pings.load(Ordering::SeqCst), 1);
+        assert_eq!(conn.id, 1);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn discard_and_reacquire_replaces_the_connection() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .acquire_timeout(Duration::from_millis(100)),
+        );
+
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!(conn.id, 0);
+
+        // the slot of the broken connection is freed for its replacement
+        let conn = conn.discard_and_reacquire().await.unwrap();
+        assert_eq!(conn.id, 1);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+        assert_eq!(shared.rollbacks.load(Ordering::SeqCst), 0);
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool.num_idle(), 0);
+
+        conn.return_now().await;
+        assert_eq!(pool.acquire().await.unwrap().id, 1);
+    }
+
+    #[tokio::test]
+    async fn serverless_preset_closes_dropped_connections() {
+        let summary = PoolOptions::<MockConn>::serverless().describe();
+        assert_eq!(summary.min_connections, 0);
+        assert_eq!(summary.idle_timeout, Some(Duration::from_secs(30)));
+        assert_eq!(summary.acquire_timeout, Duration::from_secs(5));
+        assert!(summary.test_before_acquire);
+        assert_eq!(summary.return_on_drop, ReturnMode::Detach);
+
+        let (pool, shared) = mock_pool(PoolOptions::serverless().max_connections(1));
+        pool.acquire().await.unwrap().return_now().await;
+        assert_eq!(pool.num_idle(), 1);
+
+        drop(pool.acquire().await.unwrap());
+        assert_eq!(pool.size(), 0);
+        assert_eq!(shared.open.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn connections_exceeding_max_connection_errors_are_evicted() {
+        let (pool, shared) = mock_pool(PoolOptions::new().max_connection_errors(2));
+
+        // the count sticks with the connection across checkouts
+        for errors in 1..=2 {
+            let mut conn = pool.acquire().await.unwrap();
+            assert_eq!(conn.id, 0);
+            conn.mark_error();
+            assert_eq!(conn.error_count(), errors);
+            conn.return_now().await;
+            assert_eq!(pool.num_idle(), 1);
+        }
+
+        // a failed ping counts too, and takes it over the threshold
+        let mut conn = pool.acquire().await.unwrap();
+        shared.dead_below.store(1, Ordering::SeqCst);
+        assert!(conn.ping_tracked().await.is_err());
+        shared.dead_below.store(0, Ordering::SeqCst);
+        assert_eq!(conn.error_count(), 3);
+        conn.return_now().await;
+        assert_eq!(pool.num_idle(), 0);
+        assert_eq!(pool.size(), 0);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!((conn.id, conn.error_count()), (1, 0));
+        assert_eq!(pool.options().describe().max_connection_errors, Some(2));
+    }
+
+    #[tokio::test]
+    async fn soft_max_connections_serves_spikes_from_open_connections() {
+        async fn spike(soft_max: Option<u32>) -> u64 {
+            let (pool, shared) = mock_pool(
+                PoolOptions::new()
+                    .max_connections(10)
+                    .soft_max_connections(soft_max)
+                    .soft_max_grace(Duration::from_secs(5)),
+            );
+
+            let tasks: Vec<_> = (0..8)
+                .map(|_| {
+                    let pool = pool.clone();
+                    tokio::spawn(async move {
+                        let conn = pool.acquire().await.unwrap();
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        conn.return_now().await;
+                    })
+                })
+                .collect();
+            for task in tasks {
+                task.await.unwrap();
+            }
+
+            shared.connects.load(Ordering::SeqCst)
+        }
+
+        assert_eq!(spike(None).await, 8);
+        assert_eq!(spike(Some(2)).await, 2);
+
+        // past the grace period, the pool grows up to `max_connections` as usual
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(3)
+                .soft_max_connections(1)
+                .soft_max_grace(Duration::from_millis(20)),
+        );
+        let _a = pool.acquire().await.unwrap();
+        let _b = pool.acquire().await.unwrap();
+        let _c = pool.acquire().await.unwrap();
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn reserved_connections_are_left_to_high_priority_acquires() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(3)
+                .reserved_connections(1)
+                .acquire_timeout(Duration::from_millis(50)),
+        );
+        // `fill_idle()` only makes normal acquires, which can't open the reserved connection
+        let conns = [
+            pool.acquire().await.unwrap(),
+            pool.acquire().await.unwrap(),
+            pool.acquire_prioritized(AcquirePriority::High).await.unwrap(),
+        ];
+        for conn in conns {
+            conn.return_now().await;
+        }
+
+        let a = pool.acquire().await.unwrap();
+        let b = pool.acquire().await.unwrap();
+
+        // there's still an idle connection, but it's the reserved one
+        assert_eq!(pool.num_idle(), 1);
+        assert!(pool.try_acquire().is_none());
+        assert!(matches!(pool.acquire().await, Err(Error::PoolTimedOut)));
+
+        let high = pool.acquire_prioritized(AcquirePriority::High).await.unwrap();
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 3);
+
+        // a normal acquire blocked on the reserve gets the next connection returned
+        let waiting = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.acquire().await.map(|conn| conn.id) }
+        });
+        tokio::task::yield_now().await;
+        let a_id = a.id;
+        a.return_now().await;
+        assert_eq!(waiting.await.unwrap().unwrap(), a_id);
+
+        // returning every connection gives back each kind of permit once
+        b.return_now().await;
+        high.return_now().await;
+        let _a = pool.acquire().await.unwrap();
+        let _b = pool.acquire().await.unwrap();
+        assert!(matches!(pool.acquire().await, Err(Error::PoolTimedOut)));
+        let _high = pool.acquire_prioritized(AcquirePriority::High).await.unwrap();
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn connections_downcast_to_their_concrete_type() {
+        fn mock_id<C: Connection>(conn: &mut C) -> Option<u64> {
+            let mock = conn.as_any_mut().downcast_mut::<MockConn>()?;
+            mock.discard = true;
+            Some(mock.id)
+        }
+
+        let (pool, shared) = mock_pool(PoolOptions::new());
+        let mut conn = pool.acquire().await.unwrap();
+        assert_eq!(mock_id(&mut *conn), Some(0));
+        assert!(conn.as_any().downcast_ref::<String>().is_none());
+
+        // changed through the downcast
+        conn.return_now().await;
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn on_acquire_reports_whether_the_connection_was_reused() {
+        let sources = Arc::new(Mutex::new(Vec::new()));
+        let (pool, _) = mock_pool(PoolOptions::new().on_acquire({
+            let sources = sources.clone();
+            move |source| sources.lock().unwrap().push(source)
+        }));
+
+        pool.acquire().await.unwrap().return_now().await;
+        pool.acquire().await.unwrap().return_now().await;
+        let _conn = pool.try_acquire().unwrap();
+        let _other = pool.acquire().await.unwrap();
+
+        use crate::AcquireSource::{Created, Reused};
+        assert_eq!(*sources.lock().unwrap(), [Created, Reused, Reused, Created]);
+
+        let stats = pool.statistics();
+        assert_eq!((stats.reused, stats.created), (2, 2));
+    }
+
+    #[cfg(feature = "deadpool-compat")]
+    #[tokio::test]
+    async fn deadpool_managers_open_and_recycle_connections() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize};
+
+        use deadpool::managed::{Manager, Metrics, RecycleResult};
+
+        use crate::{ManagerConnection, ManagerOptions};
+
+        #[derive(Default)]
+        struct Counter {
+            created: AtomicUsize,
+            detached: AtomicUsize,
+            broken: AtomicBool,
+        }
+
+        impl Manager for Counter {
+            type Type = usize;
+            type Error = std::io::Error;
+
+            async fn create(&self) -> Result<usize, std::io::Error> {
+                Ok(self.created.fetch_add(1, Ordering::SeqCst))
+            }
+
+            async fn recycle(&self, _: &mut usize, _: &Metrics) -> RecycleResult<std::io::Error> {
+                if self.broken.load(Ordering::SeqCst) {
+                    return Err(std::io::Error::other("broken").into());
+                }
+                Ok(())
+            }
+
+            fn detach(&self, _: &mut usize) {
+                self.detached.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        // there's no URL to make a manager from
+        assert!(matches!("mock://".parse::<ManagerOptions<Counter>>(), Err(Error::Configuration(_))));
+
+        let pool: Pool<ManagerConnection<Counter>> =
+            PoolOptions::new().connect_lazy_with(ManagerOptions::new(Counter::default()));
+        let options = pool.connect_options();
+        let manager = options.manager();
+
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!(**conn, 0);
+        conn.return_now().await;
+
+        // recycled before it's handed out again
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!((**conn, conn.metrics().recycle_count), (0, 1));
+        conn.return_now().await;
+
+        // and detached when it fails to recycle
+        manager.broken.store(true, Ordering::SeqCst);
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!(**conn, 1);
+        assert_eq!(manager.detached.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn acquire_many_takes_turns_instead_of_deadlocking() {
+        let (pool, _shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(10)
+                .acquire_timeout(Duration::from_secs(5)),
+        );
+
+        // Acquiring one at a time, each task could end up holding 5 and waiting for 2 more.
+        let tasks: Vec<_> = (0..2)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    let conns = pool.acquire_many(7).await.unwrap();
+                    assert_eq!(conns.len(), 7);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    for conn in conns {
+                        conn.return_now().await;
+                    }
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            tokio::time::timeout(Duration::from_secs(2), task)
+                .await
+                .expect("acquire_many() deadlocked")
+                .unwrap();
+        }
+        assert!(pool.size() <= 10);
+    }
+
+    #[tokio::test]
+    async fn acquire_many_times_out_without_holding_permits() {
+        let (pool, _shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(10)
+                .acquire_timeout(Duration::from_millis(50)),
+        );
+
+        for n in [0, 11] {
+            let err = pool.acquire_many(n).await.unwrap_err();
+            assert!(matches!(err, Error::Configuration(_)), "{err:?}");
+        }
+
+        let held = pool.acquire_many(5).await.unwrap();
+        assert!(matches!(pool.acquire_many(7).await, Err(Error::PoolTimedOut)));
+
+        // the timed out call didn't keep any of the 5 permits that were free
+        let rest = pool.acquire_many(5).await.unwrap();
+        assert_eq!(pool.size(), 10);
+        drop((held, rest));
+    }
+
+    #[tokio::test]
+    async fn on_return_runs_once_for_its_checkout_only() {
+        let (pool, _shared) = mock_pool(PoolOptions::new().max_connections(1));
+        let ran = Arc::new(Mutex::new(Vec::new()));
+
+        let conn = pool.acquire().await.unwrap();
+        let (first, second) = (ran.clone(), ran.clone());
+        let conn = conn
+            .on_return(move |conn: &mut MockConn| {
+                first.lock().unwrap().push((conn.id, 1));
+                Box::pin(async { Ok(()) })
+            })
+            .on_return(move |conn: &mut MockConn| {
+                Box::pin(async move {
+                    tokio::task::yield_now().await;
+                    second.lock().unwrap().push((conn.id, 2));
+                    Ok(())
+                })
+            });
+        let id = conn.id;
+        conn.return_now().await;
+        assert_eq!(*ran.lock().unwrap(), [(id, 1), (id, 2)]);
+        assert_eq!(pool.num_idle(), 1);
+
+        // the next checkout of the same connection doesn't run them again
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!(conn.id, id);
+        conn.return_now().await;
+        assert_eq!(ran.lock().unwrap().len(), 2);
+
+        // a connection that's closed instead drops the closure without running it
+        let mut conn = pool.acquire().await.unwrap();
+        conn.discard = true;
+        let dropped = ran.clone();
+        conn.on_return(move |_: &mut MockConn| {
+            dropped.lock().unwrap().push((0, 3));
+            Box::pin(async { Ok(()) })
+        })
+        .return_now()
+        .await;
+        assert_eq!(ran.lock().unwrap().len(), 2);
+        assert_eq!(pool.size(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failing_or_hanging_on_return_closes_the_connection() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(1)
+                .release_timeout(Duration::from_millis(50)),
+        );
+
+        let conn = pool.acquire().await.unwrap();
+        conn.on_return(|_: &mut MockConn| Box::pin(async { Err(Error::ResponseError) }))
+            .return_now()
+            .await;
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.size(), 0);
+
+        let conn = pool.acquire().await.unwrap();
+        conn.on_return(|_: &mut MockConn| Box::pin(std::future::pending()))
+            .return_now()
+            .await;
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.size(), 0);
+        assert_eq!(pool.statistics().release_test_failures, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn added_connections_keep_their_age() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(2)
+                .max_lifetime(Duration::from_secs(5 * 60)),
+        );
+        let elsewhere = MockConnectOptions::default();
+
+        // on the paused clock, so going back 10 minutes can't underflow on a freshly booted machine
+        tokio::time::advance(Duration::from_secs(10 * 60)).await;
+        let old = elsewhere.connect().await.unwrap();
+        assert!(pool.add_with_metadata(old, clock::now() - Duration::from_secs(10 * 60)).is_ok());
+        assert!(pool.idle_connections_info()[0].age >= Duration::from_secs(10 * 60));
+
+        // past its lifetime already, so it's closed instead of handed out
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.size(), 1);
+
+        // a `created_at` in the future counts as now
+        let new = elsewhere.connect().await.unwrap();
+        assert!(pool.add_with_metadata(new, clock::now() + Duration::from_secs(60)).is_ok());
+        assert!(pool.idle_connections_info()[0].age < Duration::from_secs(1));
+
+        // no room left, the connection is given back
+        let extra = elsewhere.connect().await.unwrap();
+        let extra = pool.add(extra).unwrap_err();
+        assert_eq!(pool.size(), 2);
+
+        drop(conn);
+        pool.close().await;
+        assert!(pool.add(extra).is_err());
+    }
+
+    #[tokio::test]
+    async fn shrink_to_closes_idle_connections_down_to_the_target() {
+        let (pool, shared) = mock_pool(PoolOptions::new().max_connections(10).min_connections(2));
+        fill_idle(&pool, 8).await;
+        let _held = [pool.acquire().await.unwrap(), pool.acquire().await.unwrap()];
+
+        assert_eq!(pool.shrink_to(5).await, 3);
+        assert_eq!(pool.size(), 5);
+        assert_eq!(pool.num_idle(), 3);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 3);
+        assert_eq!(pool.max_connections(), 10);
+
+        // the checked-out connections and `min_connections` are kept
+        assert_eq!(pool.shrink_to(0).await, 3);
+        assert_eq!(pool.size(), 2);
+        assert_eq!(pool.num_idle(), 0);
+    }
+
+    #[tokio::test]
+    async fn prefer_healthy_hands_out_flaky_connections_last() {
+        for kind in [IdleQueueKind::Fifo, IdleQueueKind::Deadline] {
+            let (pool, _shared) = mock_pool(
+                PoolOptions::new()
+                    .max_connections(3)
+                    .idle_queue(kind)
+                    .prefer_healthy(true),
+            );
+            assert!(pool.options().describe().prefer_healthy);
+
+            let mut conns = [
+                pool.acquire().await.unwrap(),
+                pool.acquire().await.unwrap(),
+                pool.acquire().await.unwrap(),
+            ];
+            // 1 failed after 0, and 2 never did
+            conns[0].mark_error();
+            conns[1].mark_error();
+            for conn in conns {
+                conn.return_now().await;
+            }
+
+            let order = [
+                pool.acquire().await.unwrap(),
+                pool.acquire().await.unwrap(),
+                pool.acquire().await.unwrap(),
+            ]
+                .map(|conn| conn.id);
+            assert_eq!(order, [2, 0, 1], "{kind:?}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn prefer_healthy_window_forgives_old_errors() {
+        let (pool, _shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(2)
+                .prefer_healthy(true)
+                .prefer_healthy_window(Duration::from_secs(60)),
+        );
+        assert_eq!(pool.options().describe().prefer_healthy_window, Some(Duration::from_secs(60)));
+
+        let mut conns = [pool.acquire().await.unwrap(), pool.acquire().await.unwrap()];
+        conns[0].mark_error();
+        for conn in conns {
+            conn.return_now().await;
+        }
+
+        // within the window, the healthy connection keeps going first
+        for _ in 0..2 {
+            let conn = pool.acquire().await.unwrap();
+            assert_eq!(conn.id, 1);
+            conn.return_now().await;
+        }
+
+        // past it, 0 is back in the FIFO with the healthy connections, and takes its turn
+        tokio::time::advance(Duration::from_secs(61)).await;
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!(conn.id, 1);
+        conn.return_now().await;
+        assert_eq!(pool.acquire().await.unwrap().id, 0);
+    }
+
+    #[tokio::test]
+    async fn with_conn_returns_the_connection_right_after() {
+        let (pool, _) = mock_pool(PoolOptions::new().max_connections(1));
+
+        let id = pool.with_conn(|conn| Box::pin(async move { conn.id })).await.unwrap();
+        assert_eq!(id, 0);
+        assert_eq!(pool.num_idle(), 1);
+
+        // cancelled mid-closure, the connection still goes back
+        let res = pool
+            .with_conn(|_conn| Box::pin(std::future::pending::<()>()))
+            .now_or_never();
+        assert!(res.is_none());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(pool.num_idle(), 1);
+        assert_eq!(pool.size(), 1);
+
+        pool.close().await;
+        assert!(matches!(
+            pool.with_conn(|conn| Box::pin(async move { conn.id })).await,
+            Err(Error::PoolClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn paused_reaping_keeps_idle_connections_until_resumed() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(2)
+                .idle_timeout(Duration::from_millis(50)),
+        );
+
+        pool.pause_reaping();
+        assert!(pool.is_reaping_paused());
+        fill_idle(&pool, 2).await;
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(pool.num_idle(), 2);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 0);
+
+        // from outside of any runtime, the run it kicks off goes to the pool's
+        std::thread::spawn({
+            let pool = pool.clone();
+            move || pool.resume_reaping()
+        })
+        .join()
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.num_idle(), 0);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadline_idle_queue_reaps_only_expired_connections() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .max_connections(4)
+                .idle_timeout(Duration::from_millis(300))
+                .idle_queue(IdleQueueKind::Deadline),
+        );
+        assert_eq!(pool.options().describe().idle_queue, IdleQueueKind::Deadline);
+
+        pool.pause_reaping();
+        fill_idle(&pool, 4).await;
+
+        // acquires take the connections closest to expiring, here the first two opened, and
+        // returning them pushes their deadline back
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let conns = [pool.acquire().await.unwrap(), pool.acquire().await.unwrap()];
+        assert_eq!([conns[0].id, conns[1].id], [0, 1]);
+        for conn in conns {
+            conn.return_now().await;
+        }
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        pool.resume_reaping();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(pool.num_idle(), 2);
+        assert_eq!(shared.closes.load(Ordering::SeqCst), 2);
+
+        let conns = [pool.acquire().await.unwrap(), pool.acquire().await.unwrap()];
+        let mut ids = [conns[0].id, conns[1].id];
+        ids.sort();
+        assert_eq!(ids, [0, 1]);
+    }
+
+    /// Times a reaper run over 10,000 idle connections that aren't due yet, for each kind of
+    /// idle queue.
+    ///
+    /// Run with `cargo test --release -p poolx reaping_10k -- --ignored --nocapture`.
+    #[tokio::test]
+    #[ignore = "benchmark, run explicitly in release mode"]
+    async fn reaping_10k_idle_connections() {
+        const RUNS: u32 = 100;
+
+        for kind in [IdleQueueKind::Fifo, IdleQueueKind::Deadline] {
+            let (pool, _) = mock_pool(
+                PoolOptions::new()
+                    .max_connections(10_000)
+                    .idle_timeout(Duration::from_secs(60 * 60))
+                    .idle_queue(kind),
+            );
+            fill_idle(&pool, 10_000).await;
+
+            let start = Instant::now();
+            for _ in 0..RUNS {
+                crate::inner::do_reap(&pool.0).await;
+            }
+            println!("{kind:?}: {:?} per reaper run", start.elapsed() / RUNS);
+
+            assert_eq!(pool.num_idle(), 10_000);
+        }
+    }
+
+    /// Compares acquire/release throughput of a pool with the bare semaphore and queue hand-off
+    /// it's built on, and with a channel, as discussed in the `idle` module docs.
+    ///
+    /// Run with `cargo test --release -p poolx acquire_release_throughput -- --ignored --nocapture`.
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "benchmark, run explicitly in release mode"]
+    async fn acquire_release_throughput() {
+        use crossbeam_queue::SegQueue;
+        use tokio::sync::Semaphore;
+
+        const CYCLES: usize = 20_000;
+
+        async fn mops<F>(tasks: usize, task: impl Fn() -> F) -> f64
+        where
+            F: std::future::Future<Output=()> + Send + 'static,
+        {
+            let start = Instant::now();
+            let handles: Vec<_> = (0..tasks).map(|_| tokio::spawn(task())).collect();
+            for handle in handles {
+                handle.await.unwrap();
+            }
+            (tasks * CYCLES) as f64 / start.elapsed().as_secs_f64() / 1e6
+        }
+
+        let row = |[tasks, pool, bare, channel]: [&str; 4]| {
+            println!("{tasks:>9} {pool:>12} {bare:>20} {channel:>15}");
+        };
+        row(["tasks/max", "Pool", "semaphore+SegQueue", "async-channel"]);
+        for (tasks, max) in [(8, 16), (64, 16), (256, 16), (64, 64)] {
+            let (pool, _) = mock_pool(
+                PoolOptions::new()
+                    .max_connections(max)
+                    .test_before_acquire(false)
+                    .acquire_timeout(Duration::from_secs(60)),
+            );
+            fill_idle(&pool, max as usize).await;
+            let pool = mops(tasks, || {
+                let pool = pool.clone();
+                async move {
+                    for _ in 0..CYCLES {
+                        pool.acquire().await.unwrap().return_now().await;
+                    }
+                }
+            })
+            .await;
+
+            let bare = Arc::new((Semaphore::new(max as usize), SegQueue::new()));
+            (0..max).for_each(|i| bare.1.push(i));
+            let bare = mops(tasks, || {
+                let bare = bare.clone();
+                async move {
+                    for _ in 0..CYCLES {
+                        let permit = bare.0.acquire().await.unwrap();
+                        let conn = bare.1.pop().unwrap();
+                        bare.1.push(conn);
+                        drop(permit);
+                    }
+                }
+            })
+            .await;
+
+            let (tx, rx) = async_channel::bounded(max as usize);
+            (0..max).for_each(|i| tx.try_send(i).unwrap());
+            let channel = mops(tasks, || {
+                let (tx, rx) = (tx.clone(), rx.clone());
+                async move {
+                    for _ in 0..CYCLES {
+                        let conn = rx.recv().await.unwrap();
+                        tx.send(conn).await.unwrap();
+                    }
+                }
+            })
+            .await;
+
+            row([
+                &format!("{tasks}/{max}"),
+                &format!("{pool:.1} Mops/s"),
+                &format!("{bare:.1} Mops/s"),
+                &format!("{channel:.1} Mops/s"),
+            ]);
+        }
+    }
+
+    #[tokio::test]
+    async fn wait_min_connections_resolves_once_warmed_up() {
+        let (pool, _) = mock_pool(PoolOptions::new());
+        assert!(pool.wait_min_connections().now_or_never().is_some());
+
+        let (pool, shared) = mock_pool(PoolOptions::new().min_connections(3).max_connections(3));
+        let waiters = (0..2)
+            .map(|_| tokio::spawn(pool.wait_min_connections()))
+            .collect::<Vec<_>>();
+
+        for waiter in waiters {
+            tokio::time::timeout(Duration::from_secs(1), waiter)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        assert_eq!(pool.size(), 3);
+        assert_eq!(shared.connects.load(Ordering::SeqCst), 3);
+
+        // never reaches the floor, but closing releases the waiter
+        let (pool, shared) = mock_pool(PoolOptions::new().min_connections(1));
+        shared.fail_connects.store(true, Ordering::SeqCst);
+        let mut waiter = tokio::spawn(pool.wait_min_connections());
+        tokio::time::timeout(Duration::from_millis(20), &mut waiter).await.unwrap_err();
+        pool.close().await;
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn wait_min_connections_only_counts_open_connections() {
+        // a connect in progress holds a slot in `size()`, but isn't open yet
+        let (pool, shared) = mock_pool(PoolOptions::new().min_connections(1));
+        shared.hang_connects.store(true, Ordering::SeqCst);
+        while shared.hung_connects.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(pool.size(), 1);
+        let waiter = pool.wait_min_connections();
+        tokio::time::timeout(Duration::from_millis(20), waiter).await.unwrap_err();
+
+        // closing a connection takes it out of the count again
+        let (pool, shared) = mock_pool(PoolOptions::new().min_connections(1));
+        pool.wait_min_connections().await;
+        shared.fail_connects.store(true, Ordering::SeqCst);
+        pool.acquire().await.unwrap().close().await.unwrap();
+        let mut waiter = tokio::spawn(pool.wait_min_connections());
+        tokio::time::timeout(Duration::from_millis(20), &mut waiter).await.unwrap_err();
+
+        shared.fail_connects.store(false, Ordering::SeqCst);
+        drop(pool.acquire().await.unwrap());
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn on_leak_reports_long_checkouts_once() {
+        let leaks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let leaks2 = leaks.clone();
+
+        let (pool, _) = mock_pool(
+            PoolOptions::new()
+                .max_connections(2)
+                .max_checkout_duration(Duration::from_millis(30))
+                .on_leak(move |id, checked_out_for| leaks2.lock().unwrap().push((id, checked_out_for))),
+        );
+
+        let held = pool.acquire().await.unwrap();
+        // returned in time, never reported
+        pool.acquire().await.unwrap().return_to_pool().await;
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        {
+            let leaks = leaks.lock().unwrap();
+            assert_eq!(leaks.len(), 1, "{leaks:?}");
+            assert_eq!(leaks[0].0, held.id);
+            assert!(leaks[0].1 > Duration::from_millis(30));
+        }
+
+        // once returned, checking it out again starts over
+        drop(held);
+        let _again = pool.acquire().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(leaks.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn connect_timeout_retries_hung_connects() {
+        let (pool, shared) = mock_pool(
+            PoolOptions::new()
+                .connect_timeout(Duration::from_millis(20))
+                .acquire_timeout(Duration::from_millis(300)),
+        );
+        shared.hang_connects.store(true, Ordering::SeqCst);
+
+        // each attempt is abandoned after `connect_timeout` and retried until the deadline
+        let started = Instant::now();
+        assert!(matches!(pool.acquire().await, Err(Error::PoolTimedOut)));
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert!(shared.hung_connects.load(Ordering::SeqCst) > 1);
+
+        let error = pool.last_connect_error().unwrap();
+        assert!(error.contains("`connect_timeout` of 20ms"), "{error}");
+
+        // the host comes back within the acquire's budget
+        let acquire = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.acquire().await.map(|conn| conn.id) }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shared.hang_connects.store(false, Ordering::SeqCst);
+        assert_eq!(acquire.await.unwrap().unwrap(), 0);
+        assert_eq!(pool.last_connect_error(), None);
+    }
+
+    #[tokio::test]
+    async fn acquire_instrumented_reports_connect_only_for_new_connections() {
+        let (pool, _) = mock_pool(PoolOptions::new().max_connections(1));
+
+        let (conn, timing) = pool.acquire_instrumented().await.unwrap();
+        assert!(timing.connect.is_some());
+        assert_eq!(timing.test, Duration::ZERO);
+
+        drop(conn);
+
+        let (_conn, timing) = pool.acquire_instrumented().await.unwrap();
+        assert!(timing.connect.is_none());
     }
 }
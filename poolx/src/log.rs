@@ -0,0 +1,81 @@
+use tracing::level_filters::LevelFilter;
+
+/// The levels at which the pool logs routine events in the lifecycle of its connections.
+///
+/// Set with [`PoolOptions::log_settings`][crate::PoolOptions::log_settings], e.g. to promote
+/// new connections to `INFO` during development, or to quiet down reaping in production. Any
+/// level may be [`LevelFilter::OFF`] to not log that event at all.
+///
+/// Events that point to a bug or a misconfiguration, like a panicking callback, are always
+/// logged at their own level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LogSettings {
+    /// A new connection was opened. Defaults to `DEBUG`.
+    pub connect_level: LevelFilter,
+    /// A connection is closed, or closing it failed. Defaults to `DEBUG`.
+    pub close_level: LevelFilter,
+    /// The reaper closed idle connections. Defaults to `DEBUG`.
+    pub reap_level: LevelFilter,
+    /// [`after_release`][crate::PoolOptions::after_release] failed or timed out for a returned
+    /// connection. Defaults to `WARN`.
+    pub release_test_failure_level: LevelFilter,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            connect_level: LevelFilter::DEBUG,
+            close_level: LevelFilter::DEBUG,
+            reap_level: LevelFilter::DEBUG,
+            release_test_failure_level: LevelFilter::WARN,
+        }
+    }
+}
+
+impl LogSettings {
+    /// Set the level for newly opened connections.
+    pub fn connect_level(mut self, level: LevelFilter) -> Self {
+        self.connect_level = level;
+        self
+    }
+
+    /// Set the level for closed connections.
+    pub fn close_level(mut self, level: LevelFilter) -> Self {
+        self.close_level = level;
+        self
+    }
+
+    /// Set the level for idle connections closed by the reaper.
+    pub fn reap_level(mut self, level: LevelFilter) -> Self {
+        self.reap_level = level;
+        self
+    }
+
+    /// Set the level for `after_release` failures.
+    pub fn release_test_failure_level(mut self, level: LevelFilter) -> Self {
+        self.release_test_failure_level = level;
+        self
+    }
+}
+
+/// Like `tracing::event!`, but with a level only known at runtime, skipping the event entirely
+/// for `LevelFilter::OFF`.
+macro_rules! log_event {
+    ($level:expr, $($args:tt)*) => {{
+        use ::tracing::level_filters::LevelFilter;
+
+        let level: LevelFilter = $level;
+        if level == LevelFilter::ERROR {
+            ::tracing::error!($($args)*);
+        } else if level == LevelFilter::WARN {
+            ::tracing::warn!($($args)*);
+        } else if level == LevelFilter::INFO {
+            ::tracing::info!($($args)*);
+        } else if level == LevelFilter::DEBUG {
+            ::tracing::debug!($($args)*);
+        } else if level == LevelFilter::TRACE {
+            ::tracing::trace!($($args)*);
+        }
+    }};
+}
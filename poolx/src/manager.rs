@@ -0,0 +1,180 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use deadpool::managed::{Manager, Metrics, RecycleError};
+use futures_core::future::BoxFuture;
+use url::Url;
+
+use crate::clock;
+use crate::{ConnectOptions, Connection, Error};
+
+/// The [`ConnectOptions`] of a pool of connections made by a [`deadpool` `Manager`][Manager],
+/// to reuse a manager written for `deadpool` with a [`Pool`][crate::Pool].
+///
+/// The pool opens each connection with [`Manager::create()`] and checks it with
+/// [`Manager::recycle()`], where it would [ping][Connection::ping] it, so `recycle()` runs
+/// before an idle connection is handed out if
+/// [`test_before_acquire`][crate::PoolOptions::test_before_acquire] is set. Closing a
+/// connection calls [`Manager::detach()`] and drops it, as `deadpool` does when it lets go of
+/// an object.
+///
+/// There's no URL to build a manager from, so the pool has to be created with
+/// [`connect_with()`][crate::PoolOptions::connect_with] or
+/// [`connect_lazy_with()`][crate::PoolOptions::connect_lazy_with].
+///
+/// Requires the `deadpool-compat` feature.
+///
+/// ```rust,ignore
+/// let pool = PoolOptions::new()
+///     .max_connections(16)
+///     .connect_lazy_with(ManagerOptions::new(MyManager::new(config)));
+///
+/// let mut conn = pool.acquire().await?;
+/// conn.do_something().await?; // derefs to `MyManager::Type`
+/// ```
+pub struct ManagerOptions<M> {
+    manager: Arc<M>,
+}
+
+impl<M> ManagerOptions<M> {
+    /// Open connections with `manager`.
+    pub fn new(manager: M) -> Self {
+        Self { manager: Arc::new(manager) }
+    }
+
+    /// Get the manager.
+    pub fn manager(&self) -> &M {
+        &self.manager
+    }
+}
+
+impl<M> fmt::Debug for ManagerOptions<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManagerOptions")
+            .field("manager", &std::any::type_name::<M>())
+            .finish()
+    }
+}
+
+impl<M> FromStr for ManagerOptions<M> {
+    type Err = Error;
+
+    fn from_str(_: &str) -> Result<Self, Error> {
+        Err(no_url())
+    }
+}
+
+impl<M> ConnectOptions for ManagerOptions<M>
+where
+    M: Manager + 'static,
+    M::Type: 'static,
+    M::Error: std::error::Error + Sync + 'static,
+{
+    type Connection = ManagerConnection<M>;
+
+    fn from_url(_: &Url) -> Result<Self, Error> {
+        Err(no_url())
+    }
+
+    fn connect(&self) -> BoxFuture<'_, Result<Self::Connection, Error>> {
+        Box::pin(async move {
+            let obj = self
+                .manager
+                .create()
+                .await
+                .map_err(|e| Error::Other(e.into()))?;
+
+            Ok(ManagerConnection {
+                obj,
+                manager: self.manager.clone(),
+                metrics: Metrics::default(),
+            })
+        })
+    }
+}
+
+fn no_url() -> Error {
+    Error::Configuration(
+        "a `ManagerOptions` can't be parsed from a URL, create the pool with `connect_with()` or `connect_lazy_with()`"
+            .into(),
+    )
+}
+
+/// A connection made by a `deadpool` [`Manager`], dereferencing to the manager's
+/// [`Type`][Manager::Type]. See [`ManagerOptions`].
+///
+/// Requires the `deadpool-compat` feature.
+pub struct ManagerConnection<M: Manager> {
+    obj: M::Type,
+    manager: Arc<M>,
+    /// Passed to `Manager::recycle()`, like `deadpool` keeps for each of its objects.
+    metrics: Metrics,
+}
+
+impl<M: Manager> ManagerConnection<M> {
+    /// Get the `deadpool` metrics of the connection, as passed to [`Manager::recycle()`].
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Take the manager's object out of the connection, without detaching it.
+    pub fn into_inner(self) -> M::Type {
+        self.obj
+    }
+}
+
+impl<M: Manager> Deref for ManagerConnection<M> {
+    type Target = M::Type;
+
+    fn deref(&self) -> &M::Type {
+        &self.obj
+    }
+}
+
+impl<M: Manager> DerefMut for ManagerConnection<M> {
+    fn deref_mut(&mut self) -> &mut M::Type {
+        &mut self.obj
+    }
+}
+
+impl<M: Manager> fmt::Debug for ManagerConnection<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManagerConnection")
+            .field("metrics", &self.metrics)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M> Connection for ManagerConnection<M>
+where
+    M: Manager + 'static,
+    M::Type: 'static,
+    M::Error: std::error::Error + Sync + 'static,
+{
+    type Options = ManagerOptions<M>;
+
+    fn close(mut self) -> BoxFuture<'static, Result<(), Error>> {
+        self.manager.detach(&mut self.obj);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn close_hard(self) -> BoxFuture<'static, Result<(), Error>> {
+        self.close()
+    }
+
+    fn ping(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            match self.manager.recycle(&mut self.obj, &self.metrics).await {
+                Ok(()) => {
+                    self.metrics.recycled = Some(clock::now());
+                    self.metrics.recycle_count += 1;
+                    Ok(())
+                }
+                Err(RecycleError::Message(message)) => Err(Error::Other(anyhow::anyhow!("{message}"))),
+                Err(RecycleError::Backend(e)) => Err(Error::Other(e.into())),
+            }
+        })
+    }
+}
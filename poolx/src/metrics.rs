@@ -0,0 +1,85 @@
+//! OpenTelemetry metrics for a pool, set up with
+//! [`PoolOptions::with_meter()`][crate::PoolOptions::with_meter].
+//!
+//! The gauges are observed lazily, whenever the meter's reader collects, so they cost nothing
+//! between collections. Only the histograms and the connect counter record on the hot path.
+
+use std::sync::Weak;
+use std::time::Duration;
+
+use opentelemetry::metrics::{Counter, Histogram, Meter, ObservableGauge};
+
+use crate::inner::PoolInner;
+use crate::Connection;
+
+pub(crate) struct PoolMetrics {
+    acquire_wait: Histogram<f64>,
+    connect_duration: Histogram<f64>,
+    connects: Counter<u64>,
+    // Keeps the gauges' callbacks registered for as long as the pool is alive.
+    _gauges: [ObservableGauge<u64>; 3],
+}
+
+impl PoolMetrics {
+    /// Create the instruments for `pool` on `meter`.
+    ///
+    /// The gauges only hold a weak reference, so they don't keep a closed pool alive, and stop
+    /// reporting once it's dropped.
+    pub(crate) fn new<C: Connection>(meter: &Meter, pool: &Weak<PoolInner<C>>) -> Self {
+        Self {
+            acquire_wait: meter
+                .f64_histogram("poolx.acquire.wait_time")
+                .with_description("How long a successful acquire took, from the call until a connection was handed out.")
+                .with_unit("s")
+                .build(),
+            connect_duration: meter
+                .f64_histogram("poolx.connect.duration")
+                .with_description("How long it took to open a new connection, including retries and the on-connect hooks.")
+                .with_unit("s")
+                .build(),
+            connects: meter
+                .u64_counter("poolx.connects")
+                .with_description("The number of connections opened.")
+                .build(),
+            _gauges: [
+                gauge(meter, "poolx.connections", "The number of open connections, idle or in use.", pool, |pool| {
+                    pool.size().into()
+                }),
+                gauge(meter, "poolx.connections.idle", "The number of idle connections.", pool, |pool| {
+                    pool.num_idle() as u64
+                }),
+                gauge(meter, "poolx.acquires.pending", "The number of tasks waiting to acquire a connection.", pool, |pool| {
+                    pool.num_pending() as u64
+                }),
+            ],
+        }
+    }
+
+    pub(crate) fn record_acquire_wait(&self, waited: Duration) {
+        self.acquire_wait.record(waited.as_secs_f64(), &[]);
+    }
+
+    pub(crate) fn record_connect(&self, took: Duration) {
+        self.connect_duration.record(took.as_secs_f64(), &[]);
+        self.connects.add(1, &[]);
+    }
+}
+
+fn gauge<C: Connection>(
+    meter: &Meter,
+    name: &'static str,
+    description: &'static str,
+    pool: &Weak<PoolInner<C>>,
+    read: fn(&PoolInner<C>) -> u64,
+) -> ObservableGauge<u64> {
+    let pool = pool.clone();
+    meter
+        .u64_observable_gauge(name)
+        .with_description(description)
+        .with_callback(move |gauge| {
+            if let Some(pool) = pool.upgrade() {
+                gauge.observe(read(&pool), &[]);
+            }
+        })
+        .build()
+}
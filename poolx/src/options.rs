@@ -1,11 +1,16 @@
 use crate::error::Error;
 use futures_core::future::BoxFuture;
+use std::any::Any;
+use std::collections::BTreeMap;
 use std::fmt::{self, Debug, Formatter};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+use crate::clock;
 use crate::conn::Connection;
+use crate::idle::IdleQueueKind;
 use crate::inner::PoolInner;
-use crate::Pool;
+use crate::{LogSettings, Pool};
+use crate::sync::AsyncSemaphore;
 
 /// Configuration options for [`Pool`][super::Pool].
 ///
@@ -40,8 +45,16 @@ use crate::Pool;
 /// parameter everywhere, and `Box` is in the prelude so it doesn't need to be manually imported,
 /// so having the closure return `Pin<Box<dyn Future>` directly is the path of least resistance from
 /// the perspectives of both API designer and consumer.
+///
+/// ### Notification Callbacks
+/// The `on_*` callbacks, like [`on_close`][Self::on_close], are plain closures the pool calls to
+/// report what's happening. If one panics, the panic is caught and logged, so it can't poison the
+/// pool or fail whatever triggered the callback.
+#[allow(clippy::type_complexity)]
 pub struct PoolOptions<C: Connection> {
     pub(crate) test_before_acquire: bool,
+    pub(crate) test_before_acquire_retries: u32,
+    pub(crate) on_connect_commands: Vec<InitCommand<C>>,
     pub(crate) after_connect: Option<
         Arc<
             dyn Fn(&mut C, PoolConnectionMetadata) -> BoxFuture<'_, Result<(), Error>>
@@ -50,6 +63,17 @@ pub struct PoolOptions<C: Connection> {
                 + Sync,
         >,
     >,
+    pub(crate) validate_on_connect: Option<
+        Arc<dyn Fn(&mut C) -> BoxFuture<'_, Result<bool, Error>> + 'static + Send + Sync>,
+    >,
+    pub(crate) connection_extension: Option<
+        Arc<
+            dyn Fn(&mut C) -> BoxFuture<'_, Result<Arc<dyn Any + Send + Sync>, Error>>
+                + 'static
+                + Send
+                + Sync,
+        >,
+    >,
     pub(crate) before_acquire: Option<
         Arc<
             dyn Fn(
@@ -61,6 +85,14 @@ pub struct PoolOptions<C: Connection> {
                 + Sync,
         >,
     >,
+    pub(crate) before_acquire_sync: Option<
+        Arc<
+            dyn Fn(&mut C, PoolConnectionMetadata) -> Result<bool, Error>
+                + 'static
+                + Send
+                + Sync,
+        >,
+    >,
     pub(crate) after_release: Option<
         Arc<
             dyn Fn(
@@ -72,14 +104,43 @@ pub struct PoolOptions<C: Connection> {
                 + Sync,
         >,
     >,
+    pub(crate) on_close: Option<Arc<dyn Fn(PoolConnectionMetadata) + 'static + Send + Sync>>,
+    pub(crate) on_slow_acquire: Option<Arc<dyn Fn(Duration) + 'static + Send + Sync>>,
+    pub(crate) slow_acquire_threshold: Option<Duration>,
+    pub(crate) on_saturation_change: Option<Arc<dyn Fn(bool) + 'static + Send + Sync>>,
+    pub(crate) saturation_debounce: Duration,
+    pub(crate) on_leak: Option<Arc<dyn Fn(u64, Duration) + 'static + Send + Sync>>,
+    pub(crate) on_acquire: Option<Arc<dyn Fn(AcquireSource) + 'static + Send + Sync>>,
+    pub(crate) max_checkout_duration: Option<Duration>,
     pub(crate) max_connections: u32,
+    pub(crate) soft_max_connections: Option<u32>,
+    pub(crate) soft_max_grace: Duration,
+    pub(crate) reserved_connections: u32,
     pub(crate) acquire_timeout: Duration,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) release_timeout: Duration,
+    pub(crate) max_pending_acquires: Option<usize>,
+    pub(crate) max_connection_errors: Option<u32>,
     pub(crate) min_connections: u32,
+    pub(crate) prefer_grow_below_min: bool,
     pub(crate) max_lifetime: Option<Duration>,
     pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) idle_timeout_jitter: f64,
+    pub(crate) keepalive_interval: Option<Duration>,
+    pub(crate) keepalive_concurrency: usize,
+    pub(crate) idle_queue: IdleQueueKind,
+    pub(crate) prefer_healthy: bool,
+    pub(crate) prefer_healthy_window: Option<Duration>,
     pub(crate) fair: bool,
+    pub(crate) disable_reuse: bool,
+    pub(crate) return_on_drop: ReturnMode,
+    pub(crate) rollback_on_release: bool,
+    pub(crate) log_settings: LogSettings,
 
     pub(crate) parent_pool: Option<Pool<C>>,
+    pub(crate) shared_semaphore: Option<Arc<AsyncSemaphore>>,
+    #[cfg(feature = "opentelemetry")]
+    pub(crate) meter: Option<opentelemetry::metrics::Meter>,
 }
 
 // Manually implement `Clone` to avoid a trait bound issue.
@@ -89,18 +150,167 @@ impl<C: Connection> Clone for PoolOptions<C> {
     fn clone(&self) -> Self {
         PoolOptions {
             test_before_acquire: self.test_before_acquire,
+            test_before_acquire_retries: self.test_before_acquire_retries,
+            on_connect_commands: self.on_connect_commands.clone(),
             after_connect: self.after_connect.clone(),
+            validate_on_connect: self.validate_on_connect.clone(),
+            connection_extension: self.connection_extension.clone(),
             before_acquire: self.before_acquire.clone(),
+            before_acquire_sync: self.before_acquire_sync.clone(),
             after_release: self.after_release.clone(),
+            on_close: self.on_close.clone(),
+            on_slow_acquire: self.on_slow_acquire.clone(),
+            slow_acquire_threshold: self.slow_acquire_threshold,
+            on_saturation_change: self.on_saturation_change.clone(),
+            saturation_debounce: self.saturation_debounce,
+            on_leak: self.on_leak.clone(),
+            on_acquire: self.on_acquire.clone(),
+            max_checkout_duration: self.max_checkout_duration,
             max_connections: self.max_connections,
+            soft_max_connections: self.soft_max_connections,
+            soft_max_grace: self.soft_max_grace,
+            reserved_connections: self.reserved_connections,
             acquire_timeout: self.acquire_timeout,
+            connect_timeout: self.connect_timeout,
+            release_timeout: self.release_timeout,
+            max_pending_acquires: self.max_pending_acquires,
+            max_connection_errors: self.max_connection_errors,
             min_connections: self.min_connections,
+            prefer_grow_below_min: self.prefer_grow_below_min,
             max_lifetime: self.max_lifetime,
             idle_timeout: self.idle_timeout,
+            idle_timeout_jitter: self.idle_timeout_jitter,
+            keepalive_interval: self.keepalive_interval,
+            keepalive_concurrency: self.keepalive_concurrency,
+            idle_queue: self.idle_queue,
+            prefer_healthy: self.prefer_healthy,
+            prefer_healthy_window: self.prefer_healthy_window,
             fair: self.fair,
-            parent_pool: self.parent_pool.as_ref().map(Pool::clone),
+            disable_reuse: self.disable_reuse,
+            return_on_drop: self.return_on_drop,
+            rollback_on_release: self.rollback_on_release,
+            log_settings: self.log_settings.clone(),
+            parent_pool: self.parent_pool.clone(),
+            shared_semaphore: self.shared_semaphore.clone(),
+            #[cfg(feature = "opentelemetry")]
+            meter: self.meter.clone(),
+        }
+    }
+}
+
+/// A command run on every new connection, set with [`PoolOptions::on_connect_commands`].
+///
+/// The name identifies the command in [`PoolOptions::describe()`] and the `Debug` output of
+/// the options, so it shouldn't include secrets.
+#[allow(clippy::type_complexity)]
+pub struct InitCommand<C: Connection> {
+    name: String,
+    run: Arc<dyn Fn(&mut C) -> BoxFuture<'_, Result<(), Error>> + 'static + Send + Sync>,
+}
+
+impl<C: Connection> InitCommand<C> {
+    /// Create a command named `name` that runs `run` on the connection.
+    ///
+    /// For why `run` has to return a boxed future, see [the docs of `PoolOptions`][PoolOptions].
+    pub fn new<F>(name: impl Into<String>, run: F) -> Self
+    where
+        for<'c> F: Fn(&'c mut C) -> BoxFuture<'c, Result<(), Error>> + 'static + Send + Sync,
+    {
+        Self {
+            name: name.into(),
+            run: Arc::new(run),
         }
     }
+
+    /// Get the name of the command.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn run<'c>(&self, conn: &'c mut C) -> BoxFuture<'c, Result<(), Error>> {
+        (self.run)(conn)
+    }
+}
+
+impl<C: Connection> Clone for InitCommand<C> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            run: self.run.clone(),
+        }
+    }
+}
+
+impl<C: Connection> Debug for InitCommand<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("InitCommand").field(&self.name).finish()
+    }
+}
+
+/// What dropping a [`PoolConnection`][crate::PoolConnection] does with its connection, set with
+/// [`PoolOptions::return_on_drop`].
+///
+/// Returning a connection may have to wait, e.g. for
+/// [`after_release`][PoolOptions::after_release] or to close it, which `Drop` can't do.
+/// [`PoolConnection::return_now()`][crate::PoolConnection::return_now] and
+/// [`close()`][crate::PoolConnection::close] are unaffected by this setting, so code that
+/// always returns its connections explicitly only relies on it for the error paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum ReturnMode {
+    /// Return the connection in a new task, spawned on the current runtime or the one the pool
+    /// was created on.
+    ///
+    /// The drop itself never blocks, and the connection is back in the pool shortly after. The
+    /// cost is a task per drop.
+    #[default]
+    Spawn,
+    /// Return the connection in the dropping thread, blocking it until the connection is back in
+    /// the pool or closed, like awaiting `return_now()` would.
+    ///
+    /// Within a runtime this uses [`tokio::task::block_in_place`], which stalls the worker thread
+    /// for the duration and **panics on a current-thread runtime**, where blocking would
+    /// deadlock. Outside of a runtime, the pool's runtime must be able to make progress, as for
+    /// [`Pool::blocking_acquire()`][crate::Pool::blocking_acquire]. If the thread is already
+    /// panicking, the connection is dropped as with [`Detach`][Self::Detach] instead.
+    ///
+    /// If the connection is closed rather than put back, opening its replacement to keep
+    /// [`min_connections`][PoolOptions::min_connections] happens in a spawned task, as with
+    /// [`Spawn`][Self::Spawn], so the drop never waits for a connect.
+    Block,
+    /// Drop the connection without closing it, giving up its slot in the pool.
+    ///
+    /// Nothing is spawned and nothing blocks, but the connection is lost: the server only sees
+    /// the socket close, and the next acquire has to open a new connection.
+    /// [`min_connections`][PoolOptions::min_connections] is restored by the pool's maintenance
+    /// task rather than right away. Best combined with returning connections explicitly.
+    Detach,
+}
+
+/// The priority of an acquire made with
+/// [`Pool::acquire_prioritized()`][crate::Pool::acquire_prioritized].
+///
+/// Only matters with [`PoolOptions::reserved_connections`] set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum AcquirePriority {
+    /// Can't take the connections held in reserve. This is the priority of every other way to
+    /// acquire a connection.
+    #[default]
+    Normal,
+    /// Can take any connection, including the reserved ones.
+    High,
+}
+
+/// Where the connection handed out by an acquire came from, as passed to
+/// [`PoolOptions::on_acquire`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AcquireSource {
+    /// An idle connection was taken from the pool.
+    Reused,
+    /// A new connection was opened for the acquire.
+    Created,
 }
 
 /// Metadata for the connection being processed by a [`PoolOptions`] callback.
@@ -119,6 +329,75 @@ pub struct PoolConnectionMetadata {
     pub idle_for: Duration,
 }
 
+impl PoolConnectionMetadata {
+    /// The duration since the connection was first opened.
+    ///
+    /// See [the field docs][PoolConnectionMetadata#structfield.age] for details.
+    pub fn age(&self) -> Duration {
+        self.age
+    }
+
+    /// The duration that the connection spent in the idle queue.
+    ///
+    /// See [the field docs][PoolConnectionMetadata#structfield.idle_for] for details.
+    pub fn idle_for(&self) -> Duration {
+        self.idle_for
+    }
+}
+
+/// The plain settings of a [`PoolOptions`], without its callbacks, as returned by
+/// [`PoolOptions::describe()`].
+///
+/// Meant for logging or exposing the effective configuration of a pool, e.g. on a debug
+/// endpoint. See the [`PoolOptions`] method of the same name for what each field means.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct PoolOptionsSummary {
+    pub max_connections: u32,
+    pub soft_max_connections: Option<u32>,
+    pub soft_max_grace: Duration,
+    pub reserved_connections: u32,
+    pub min_connections: u32,
+    pub prefer_grow_below_min: bool,
+    pub acquire_timeout: Duration,
+    pub connect_timeout: Option<Duration>,
+    pub release_timeout: Duration,
+    pub max_pending_acquires: Option<usize>,
+    pub max_connection_errors: Option<u32>,
+    pub max_lifetime: Option<Duration>,
+    pub idle_timeout: Option<Duration>,
+    pub idle_timeout_jitter: f64,
+    pub keepalive_interval: Option<Duration>,
+    pub keepalive_concurrency: usize,
+    pub idle_queue: IdleQueueKind,
+    pub prefer_healthy: bool,
+    pub prefer_healthy_window: Option<Duration>,
+    pub slow_acquire_threshold: Option<Duration>,
+    pub saturation_debounce: Duration,
+    pub max_checkout_duration: Option<Duration>,
+    pub test_before_acquire: bool,
+    pub test_before_acquire_retries: u32,
+    pub disable_reuse: bool,
+    pub return_on_drop: ReturnMode,
+    pub rollback_on_release: bool,
+    /// The names of the [`on_connect_commands`][PoolOptions::on_connect_commands], in order.
+    pub on_connect_commands: Vec<String>,
+    pub log_settings: LogSettings,
+    /// Whether a parent pool was set.
+    pub has_parent_pool: bool,
+    /// The capacity of the semaphore given to
+    /// [`with_shared_semaphore`][PoolOptions::with_shared_semaphore], if any.
+    pub shared_semaphore_capacity: Option<usize>,
+    /// Whether a meter was given to [`with_meter`][PoolOptions::with_meter].
+    #[cfg(feature = "opentelemetry")]
+    pub has_meter: bool,
+    /// The adapter-specific settings of the pool's connect options, see
+    /// [`ConnectOptions::extra()`][crate::ConnectOptions::extra].
+    ///
+    /// Only filled in by [`Pool::describe()`]: a bare `PoolOptions` has no connect options yet.
+    pub connect_options: BTreeMap<String, String>,
+}
+
 impl<C: Connection> Default for PoolOptions<C> {
     fn default() -> Self {
         Self::new()
@@ -135,26 +414,106 @@ impl<C: Connection> PoolOptions<C> {
     pub fn new() -> Self {
         Self {
             // User-specifiable routines
+            on_connect_commands: Vec::new(),
             after_connect: None,
+            validate_on_connect: None,
+            connection_extension: None,
             before_acquire: None,
+            before_acquire_sync: None,
             after_release: None,
+            on_close: None,
+            on_slow_acquire: None,
+            slow_acquire_threshold: None,
+            on_saturation_change: None,
+            saturation_debounce: Duration::ZERO,
+            on_leak: None,
+            on_acquire: None,
+            max_checkout_duration: None,
             test_before_acquire: true,
+            test_before_acquire_retries: 3,
             // A production application will want to set a higher limit than this.
             max_connections: 10,
+            soft_max_connections: None,
+            soft_max_grace: Duration::from_millis(50),
+            reserved_connections: 0,
             min_connections: 0,
+            prefer_grow_below_min: false,
             acquire_timeout: Duration::from_secs(30),
+            connect_timeout: None,
+            release_timeout: Duration::from_secs(5),
+            max_pending_acquires: None,
+            max_connection_errors: None,
             idle_timeout: Some(Duration::from_secs(10 * 60)),
+            idle_timeout_jitter: 0.0,
+            keepalive_interval: None,
+            keepalive_concurrency: 8,
+            idle_queue: IdleQueueKind::Fifo,
+            prefer_healthy: false,
+            prefer_healthy_window: None,
             max_lifetime: Some(Duration::from_secs(30 * 60)),
             fair: true,
+            disable_reuse: false,
+            return_on_drop: ReturnMode::Spawn,
+            rollback_on_release: true,
+            log_settings: LogSettings::default(),
             parent_pool: None,
+            shared_semaphore: None,
+            #[cfg(feature = "opentelemetry")]
+            meter: None,
         }
     }
 
+    /// Returns a configuration for short-lived serverless invocations, e.g. on AWS Lambda, where
+    /// the process may be frozen between invocations and its connections are silently dropped by
+    /// the server or the network in the meantime.
+    ///
+    /// Starting from [`new()`][Self::new], this sets:
+    ///
+    /// * [`min_connections(0)`][Self::min_connections]: no connections are opened until an
+    ///   invocation needs one.
+    /// * [`idle_timeout(30s)`][Self::idle_timeout]: connections left idle don't linger.
+    /// * [`acquire_timeout(5s)`][Self::acquire_timeout]: an invocation fails fast instead of
+    ///   spending its time budget waiting for a connection.
+    /// * [`test_before_acquire(true)`][Self::test_before_acquire]: a connection kept across a
+    ///   freeze is checked before it's handed out.
+    /// * [`return_on_drop(ReturnMode::Detach)`][Self::return_on_drop]: a dropped
+    ///   [`PoolConnection`][crate::PoolConnection] is dropped without being closed, giving up its
+    ///   slot, instead of being returned in a spawned task, which might not run before the
+    ///   process is frozen. The server only sees its socket close. Return connections with
+    ///   [`return_now()`][crate::PoolConnection::return_now] to reuse them within and across
+    ///   invocations.
+    ///
+    /// Everything else keeps its default, and any of it can be changed afterwards:
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use poolx::{Connection, PoolOptions};
+    ///
+    /// fn pool_options<C: Connection>() -> PoolOptions<C> {
+    ///     PoolOptions::serverless()
+    ///         .max_connections(2)
+    ///         .acquire_timeout(Duration::from_secs(2))
+    /// }
+    /// ```
+    pub fn serverless() -> Self {
+        Self::new()
+            .min_connections(0)
+            .idle_timeout(Duration::from_secs(30))
+            .acquire_timeout(Duration::from_secs(5))
+            .test_before_acquire(true)
+            .return_on_drop(ReturnMode::Detach)
+    }
+
     /// Set the maximum number of connections that this pool should maintain.
     ///
     /// Be mindful of the connection limits for your database as well as other applications
     /// which may want to connect to the same database (or even multiple instances of the same
     /// application in high-availability deployments).
+    ///
+    /// Must be at least 1; building a pool with `max_connections(0)` fails with
+    /// [`Error::Configuration`] (or panics, for
+    /// [`connect_lazy_with()`][Self::connect_lazy_with] which can't return one).
     pub fn max_connections(mut self, max: u32) -> Self {
         self.max_connections = max;
         self
@@ -165,6 +524,82 @@ impl<C: Connection> PoolOptions<C> {
         self.max_connections
     }
 
+    /// Set the number of connections the pool prefers to stay under, growing past it up to
+    /// [`max_connections`] only if a connection doesn't free up quickly.
+    ///
+    /// An acquire that finds no idle connection while the pool already has `soft_max`
+    /// connections or more waits up to [`soft_max_grace`] for one to be returned before
+    /// opening a new one. A short spike is then served by the connections already open instead of
+    /// opening extra ones that sit idle until they're reaped, while sustained load still grows
+    /// the pool to `max_connections`. Below the soft limit, and at `max_connections`, acquires
+    /// behave as without it.
+    ///
+    /// The grace period starts the first time an acquire finds the pool at the soft limit, and
+    /// counts toward [`acquire_timeout`]. If a returned connection is taken by another task,
+    /// the acquire keeps waiting for what's left of it.
+    ///
+    /// Must not be greater than `max_connections`, or building the pool fails with
+    /// [`Error::Configuration`]. Defaults to `None`, opening connections right away up to
+    /// `max_connections`.
+    ///
+    /// [`max_connections`]: Self::max_connections
+    /// [`soft_max_grace`]: Self::soft_max_grace
+    /// [`acquire_timeout`]: Self::acquire_timeout
+    pub fn soft_max_connections(mut self, soft_max: impl Into<Option<u32>>) -> Self {
+        self.soft_max_connections = soft_max.into();
+        self
+    }
+
+    /// Get the number of connections the pool prefers to stay under, if set.
+    pub fn get_soft_max_connections(&self) -> Option<u32> {
+        self.soft_max_connections
+    }
+
+    /// Set how long an acquire past [`soft_max_connections`] waits for a connection to be
+    /// returned before opening a new one.
+    ///
+    /// Longer smooths out longer spikes, at the cost of that much latency for the acquires that
+    /// end up opening a connection anyway. Defaults to 50 milliseconds.
+    ///
+    /// [`soft_max_connections`]: Self::soft_max_connections
+    pub fn soft_max_grace(mut self, grace: Duration) -> Self {
+        self.soft_max_grace = grace;
+        self
+    }
+
+    /// Get how long an acquire past `soft_max_connections` waits before opening a new connection.
+    pub fn get_soft_max_grace(&self) -> Duration {
+        self.soft_max_grace
+    }
+
+    /// Set the number of connections held in reserve for
+    /// [`acquire_prioritized(AcquirePriority::High)`][crate::Pool::acquire_prioritized].
+    ///
+    /// Every other acquire is limited to `max_connections - n` connections checked out at once,
+    /// and waits for one of those to be returned past that, even if the pool could open more.
+    /// A high-priority acquire can take any connection, so it still finds one, or room to open
+    /// one, while the others are blocked. This keeps headroom for critical paths, like health
+    /// checks or admin requests, when the rest of the application saturates the pool.
+    ///
+    /// The reserved connections are only slots: combine with [`min_connections`] to keep them
+    /// open so high-priority acquires don't have to connect first. The limit is set when the pool
+    /// is built, [`Pool::resize()`][crate::Pool::resize] doesn't change it.
+    ///
+    /// Must be less than `max_connections`, or the capacity of the
+    /// [shared semaphore][Self::with_shared_semaphore] if set, or building the pool fails with
+    /// [`Error::Configuration`]. Defaults to 0.
+    ///
+    /// [`min_connections`]: Self::min_connections
+    pub fn reserved_connections(mut self, reserved: u32) -> Self {
+        self.reserved_connections = reserved;
+        self
+    }
+
+    /// Get the number of connections held in reserve for high-priority acquires.
+    pub fn get_reserved_connections(&self) -> u32 {
+        self.reserved_connections
+    }
+
     /// Set the minimum number of connections to maintain at all times.
     ///
     /// When the pool is built, this many connections will be automatically spun up.
@@ -176,13 +611,9 @@ impl<C: Connection> PoolOptions<C> {
     /// This is only done on a best-effort basis, however. The routine that maintains this value
     /// has a deadline so it doesn't wait forever if the database is being slow or returning errors.
     ///
-    /// This value is clamped internally to not exceed [`max_connections`].
-    ///
-    /// We've chosen not to assert `min_connections <= max_connections` anywhere
-    /// because it shouldn't break anything internally if the condition doesn't hold,
-    /// and if the application allows either value to be dynamically set
-    /// then it should be checking this condition itself and returning
-    /// a nicer error than a panic anyway.
+    /// This must not exceed [`max_connections`]; building a pool with
+    /// `min_connections > max_connections` fails with [`Error::Configuration`]
+    /// (or panics, for [`connect_lazy_with()`][Self::connect_lazy_with] which can't return one).
     ///
     /// [`max_lifetime`]: Self::max_lifetime
     /// [`idle_timeout`]: Self::idle_timeout
@@ -197,6 +628,27 @@ impl<C: Connection> PoolOptions<C> {
         self.min_connections
     }
 
+    /// If true, an acquire made while the pool is below [`min_connections`] opens a new
+    /// connection even if there's an idle one, leaving the idle connection for the next caller.
+    ///
+    /// Under steady light load, acquires otherwise keep reusing the same few idle connections
+    /// and the pool only reaches `min_connections` through its background maintenance. This
+    /// trades a little acquire latency now, paying for a connect that wasn't strictly needed,
+    /// for a warmer pool sooner.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`min_connections`]: Self::min_connections
+    pub fn prefer_grow_below_min(mut self, prefer: bool) -> Self {
+        self.prefer_grow_below_min = prefer;
+        self
+    }
+
+    /// Get whether acquires below `min_connections` prefer opening a new connection.
+    pub fn get_prefer_grow_below_min(&self) -> bool {
+        self.prefer_grow_below_min
+    }
+
     /// Set the maximum amount of time to spend waiting for a connection in [`Pool::acquire()`].
     ///
     /// Caps the total amount of time `Pool::acquire()` can spend waiting across multiple phases:
@@ -209,7 +661,8 @@ impl<C: Connection> PoolOptions<C> {
     ///     * If [`before_acquire`][Self::before_acquire] is set, that will also be executed.
     /// * If a new connection needs to be opened, that will obviously require I/O, handshaking,
     ///   and initialization commands.
-    ///     * If [`after_connect`][Self::after_connect] is set, that will also be executed.
+    ///     * If [`after_connect`][Self::after_connect] or
+    ///       [`validate_on_connect`][Self::validate_on_connect] are set, they will also be executed.
     pub fn acquire_timeout(mut self, timeout: Duration) -> Self {
         self.acquire_timeout = timeout;
         self
@@ -220,6 +673,65 @@ impl<C: Connection> PoolOptions<C> {
         self.acquire_timeout
     }
 
+    /// Set the maximum amount of time a single [`ConnectOptions::connect()`] call may take.
+    ///
+    /// Without this, one attempt that hangs, e.g. on a TCP handshake with a black-holed host,
+    /// can use up the whole [`acquire_timeout`][Self::acquire_timeout]. With it, the attempt is
+    /// abandoned and treated as a failed connect: it's recorded for
+    /// [`Pool::last_connect_error()`] and retried with backoff as long as the acquire has time
+    /// left, so the acquire only fails with [`Error::PoolTimedOut`] once its own deadline passes.
+    ///
+    /// This only covers `connect()` itself, not [`after_connect`][Self::after_connect].
+    ///
+    /// Defaults to `None`, bounded only by `acquire_timeout`.
+    ///
+    /// [`ConnectOptions::connect()`]: crate::ConnectOptions::connect
+    pub fn connect_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.connect_timeout = timeout.into();
+        self
+    }
+
+    /// Get the maximum amount of time a single connect may take.
+    pub fn get_connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
+
+    /// Set the maximum number of tasks that may wait for a connection at once.
+    ///
+    /// When this many tasks are already queued in [`Pool::acquire()`] and no connection is
+    /// available, further calls fail immediately with [`Error::PoolExhausted`] instead of
+    /// queueing, which sheds load rather than letting waiters pile up during an incident.
+    /// See [`Pool::num_pending()`].
+    ///
+    /// `None` means unbounded, which is the default.
+    pub fn max_pending_acquires(mut self, max: impl Into<Option<usize>>) -> Self {
+        self.max_pending_acquires = max.into();
+        self
+    }
+
+    /// Get the maximum number of tasks that may wait for a connection at once.
+    pub fn get_max_pending_acquires(&self) -> Option<usize> {
+        self.max_pending_acquires
+    }
+
+    /// Set the maximum number of errors a connection may have had and still be reused.
+    ///
+    /// Errors are reported with [`PoolConnection::mark_error()`][crate::PoolConnection::mark_error],
+    /// and counted when [`PoolConnection::ping_tracked()`][crate::PoolConnection::ping_tracked]
+    /// fails. A connection with more errors than this is closed when it's returned, and replaced
+    /// with a new one on a later acquire.
+    ///
+    /// `None` means connections are never evicted for their errors, which is the default.
+    pub fn max_connection_errors(mut self, max: impl Into<Option<u32>>) -> Self {
+        self.max_connection_errors = max.into();
+        self
+    }
+
+    /// Get the maximum number of errors a connection may have had and still be reused.
+    pub fn get_max_connection_errors(&self) -> Option<u32> {
+        self.max_connection_errors
+    }
+
     /// Set the maximum lifetime of individual connections.
     ///
     /// Any connection with a lifetime greater than this will be closed.
@@ -259,6 +771,132 @@ impl<C: Connection> PoolOptions<C> {
         self.idle_timeout
     }
 
+    /// Shorten each connection's [`idle_timeout`] by a random fraction of up to `jitter`.
+    ///
+    /// Connections that went idle together, e.g. at the end of a traffic spike, would otherwise
+    /// all be closed by the same reaper run. With a jitter of `0.2` and an idle timeout of
+    /// 10 minutes, each connection is closed after somewhere between 8 and 10 minutes of idling
+    /// instead. The fraction is picked once per connection, so it doesn't change between runs
+    /// of the reaper.
+    ///
+    /// Must be between `0.0` and `1.0`; building a pool with any other value fails with
+    /// [`Error::Configuration`]. Defaults to `0.0` (no jitter).
+    ///
+    /// [`idle_timeout`]: Self::idle_timeout
+    pub fn idle_timeout_jitter(mut self, jitter: f64) -> Self {
+        self.idle_timeout_jitter = jitter;
+        self
+    }
+
+    /// Get the fraction by which idle timeouts are randomly shortened.
+    pub fn get_idle_timeout_jitter(&self) -> f64 {
+        self.idle_timeout_jitter
+    }
+
+    /// Call [`Connection::on_idle`] on connections that have been idle for this long, and then
+    /// again every `interval` for as long as they stay idle.
+    ///
+    /// Use this to send application-level keepalives where middleboxes like NAT gateways drop
+    /// connections that have been quiet for too long, which TCP keepalives alone don't always
+    /// prevent. A connection whose `on_idle()` fails is closed.
+    ///
+    /// Idle connections are checked every `interval / 2`, so each one gets a keepalive at most
+    /// one and a half `interval`s after the last one. With the FIFO [`idle_queue`][Self::idle_queue],
+    /// a check stops at the first connection that isn't due yet, so the rare due one queued
+    /// behind it waits for the next check. Doesn't affect
+    /// [`idle_timeout`][Self::idle_timeout]: a keepalive doesn't count as the connection being used.
+    ///
+    /// Defaults to `None` (no keepalives).
+    pub fn keepalive_interval(mut self, interval: impl Into<Option<Duration>>) -> Self {
+        self.keepalive_interval = interval.into();
+        self
+    }
+
+    /// Get the interval at which idle connections are kept alive.
+    pub fn get_keepalive_interval(&self) -> Option<Duration> {
+        self.keepalive_interval
+    }
+
+    /// Set how many idle connections may run their [`Connection::on_idle`] keepalive at once.
+    ///
+    /// Each check of [`keepalive_interval`][Self::keepalive_interval] takes all connections due
+    /// for a keepalive out of the idle queue, so a concurrent acquire can't get hold of them, and
+    /// sends the keepalives concurrently up to this limit. Healthy connections are put back as
+    /// they finish, failed ones are closed together at the end.
+    ///
+    /// Must be at least 1. Defaults to 8.
+    pub fn keepalive_concurrency(mut self, limit: usize) -> Self {
+        self.keepalive_concurrency = limit;
+        self
+    }
+
+    /// Get how many keepalives may run at once.
+    pub fn get_keepalive_concurrency(&self) -> usize {
+        self.keepalive_concurrency
+    }
+
+    /// Set how the pool stores its idle connections.
+    ///
+    /// [`IdleQueueKind::Deadline`] lets the reaper close expired connections without taking
+    /// every idle connection out of the queue to check it, which pays off for pools with
+    /// thousands of connections. See [`IdleQueueKind`] for the tradeoffs.
+    ///
+    /// Defaults to [`IdleQueueKind::Fifo`].
+    pub fn idle_queue(mut self, kind: IdleQueueKind) -> Self {
+        self.idle_queue = kind;
+        self
+    }
+
+    /// Get how the pool stores its idle connections.
+    pub fn get_idle_queue(&self) -> IdleQueueKind {
+        self.idle_queue
+    }
+
+    /// If true, acquires prefer idle connections that never reported an error with
+    /// [`PoolConnection::mark_error`][crate::PoolConnection::mark_error], or a failed
+    /// [`PoolConnection::ping_tracked`][crate::PoolConnection::ping_tracked].
+    ///
+    /// Connections that did are only handed out once no other is idle, the one whose last error
+    /// is the oldest first, so flaky connections sit idle until they age out with
+    /// [`idle_timeout`][Self::idle_timeout], or until their last error is older than
+    /// [`prefer_healthy_window`][Self::prefer_healthy_window]. Together with
+    /// [`max_connection_errors`][Self::max_connection_errors], which closes the worst of them,
+    /// this steers load towards healthy connections.
+    ///
+    /// This keeps the connections that reported errors apart from the
+    /// [idle queue][Self::idle_queue], behind a mutex, and makes the reaper check every idle
+    /// connection even with [`IdleQueueKind::Deadline`].
+    ///
+    /// Defaults to `false`.
+    pub fn prefer_healthy(mut self, prefer: bool) -> Self {
+        self.prefer_healthy = prefer;
+        self
+    }
+
+    /// Get whether acquires prefer connections that never reported an error.
+    pub fn get_prefer_healthy(&self) -> bool {
+        self.prefer_healthy
+    }
+
+    /// Set how long an error keeps a connection behind the healthy ones, with
+    /// [`prefer_healthy`][Self::prefer_healthy].
+    ///
+    /// Once its last error is older than this, a connection is handed out like one that never
+    /// reported any, so a single hiccup doesn't sideline it for the rest of its life. Each
+    /// acquire then takes a short lock to move such connections back among the healthy ones.
+    ///
+    /// Defaults to `None`: a connection that reported an error stays behind the others until it
+    /// is closed.
+    pub fn prefer_healthy_window(mut self, window: impl Into<Option<Duration>>) -> Self {
+        self.prefer_healthy_window = window.into();
+        self
+    }
+
+    /// Get how long an error keeps a connection behind the healthy ones.
+    pub fn get_prefer_healthy_window(&self) -> Option<Duration> {
+        self.prefer_healthy_window
+    }
+
     /// If true, the health of a connection will be verified by a call to [`Connection::ping`]
     /// before returning the connection.
     ///
@@ -273,6 +911,73 @@ impl<C: Connection> PoolOptions<C> {
         self.test_before_acquire
     }
 
+    /// Set the maximum number of idle connections a single [`Pool::acquire()`] will test
+    /// and discard before giving up on the idle queue and opening a new connection.
+    ///
+    /// This bounds the work done by one call when many idle connections have gone stale at once,
+    /// e.g. because the server restarted or is flapping. Each discarded connection is closed
+    /// and its slot is handed over to the next attempt, so the pool never exceeds
+    /// [`max_connections`][Self::max_connections] while doing this.
+    ///
+    /// Applies to connections rejected by [`test_before_acquire`][Self::test_before_acquire],
+    /// [`max_lifetime`][Self::max_lifetime] or [`before_acquire`][Self::before_acquire].
+    ///
+    /// A value of `0` behaves like `1`. Defaults to `3`.
+    pub fn test_before_acquire_retries(mut self, retries: u32) -> Self {
+        self.test_before_acquire_retries = retries;
+        self
+    }
+
+    /// Get the maximum number of idle connections tested per acquire.
+    pub fn get_test_before_acquire_retries(&self) -> u32 {
+        self.test_before_acquire_retries
+    }
+
+    /// If set to `true`, connections are never reused: every connection returned to the pool is
+    /// closed instead of being put in the idle queue, so every [`Pool::acquire()`] opens a fresh one.
+    ///
+    /// [`max_connections`][Self::max_connections] is still enforced, while
+    /// [`min_connections`][Self::min_connections] is not maintained in this mode as it would
+    /// only open connections that get thrown away.
+    ///
+    /// This is meant for debugging, e.g. to rule out state leaking between uses of a connection.
+    /// Defaults to `false`.
+    pub fn disable_reuse(mut self, disable: bool) -> Self {
+        self.disable_reuse = disable;
+        self
+    }
+
+    /// Get whether connection reuse is disabled.
+    pub fn get_disable_reuse(&self) -> bool {
+        self.disable_reuse
+    }
+
+    /// Set what dropping a [`PoolConnection`][crate::PoolConnection] does with its connection.
+    ///
+    /// Defaults to [`ReturnMode::Spawn`]. See [`ReturnMode`] for the tradeoffs.
+    pub fn return_on_drop(mut self, mode: ReturnMode) -> Self {
+        self.return_on_drop = mode;
+        self
+    }
+
+    /// Get what dropping a connection does with it.
+    pub fn get_return_on_drop(&self) -> ReturnMode {
+        self.return_on_drop
+    }
+
+    /// Set the levels at which routine connection lifecycle events are logged.
+    ///
+    /// See [`LogSettings`] for the events and their default levels.
+    pub fn log_settings(mut self, settings: LogSettings) -> Self {
+        self.log_settings = settings;
+        self
+    }
+
+    /// Get the levels at which routine connection lifecycle events are logged.
+    pub fn get_log_settings(&self) -> &LogSettings {
+        &self.log_settings
+    }
+
     /// If set to `true`, calls to `acquire()` are fair and connections  are issued
     /// in first-come-first-serve order. If `false`, "drive-by" tasks may steal idle connections
     /// ahead of tasks that have been waiting.
@@ -291,6 +996,36 @@ impl<C: Connection> PoolOptions<C> {
         self
     }
 
+    /// Run `commands` in order on every new connection, e.g. to select a database or set
+    /// session variables.
+    ///
+    /// This is a declarative alternative to [`after_connect`][Self::after_connect] for
+    /// connections that need a few setup steps, and adapters may provide constructors for
+    /// common commands. The commands run before `after_connect`, and are handled the same way:
+    /// if one fails, the error is logged, the connection is closed and a new one is opened in
+    /// its place, running all the commands again.
+    ///
+    /// Replaces any commands set before. Defaults to none.
+    ///
+    /// ```no_run
+    /// use poolx::{Connection, InitCommand, PoolOptions};
+    ///
+    /// fn pool_options<C: Connection>() -> PoolOptions<C> {
+    ///     PoolOptions::new().on_connect_commands(vec![
+    ///         InitCommand::new("ping", |conn: &mut C| conn.ping()),
+    ///     ])
+    /// }
+    /// ```
+    pub fn on_connect_commands(mut self, commands: Vec<InitCommand<C>>) -> Self {
+        self.on_connect_commands = commands;
+        self
+    }
+
+    /// Get the commands run on every new connection.
+    pub fn get_on_connect_commands(&self) -> &[InitCommand<C>] {
+        &self.on_connect_commands
+    }
+
     /// Perform an asynchronous action after connecting to the database.
     ///
     /// If the operation returns with an error then the error is logged, the connection is closed
@@ -299,18 +1034,29 @@ impl<C: Connection> PoolOptions<C> {
     /// This occurs in a backoff loop to avoid high CPU usage and spamming logs during a transient
     /// error condition.
     ///
-    /// Note that this may be called for internally opened connections, such as when maintaining
+    /// This runs exactly once for every connection the pool opens, whether eagerly or on demand.
+    /// In particular it is also called for internally opened connections, such as when maintaining
     /// [`min_connections`][Self::min_connections], that are then immediately returned to the pool
     /// without invoking [`after_release`][Self::after_release].
     ///
-    /// # Example: Additional Parameters
+    /// # Example: Check New Connections
     /// This callback may be used to set additional configuration parameters
-    /// that are not exposed by the database's `ConnectOptions`.
-    ///
-    /// This example is written for PostgreSQL but can likely be adapted to other databases.
+    /// that are not exposed by the database's `ConnectOptions`, or just to make sure
+    /// a new connection works before it is handed out.
     ///
+    /// This example works with any [`Connection`] as it only relies on [`Connection::ping`].
     /// ```no_run
-    /// # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+    /// use poolx::{Connection, PoolOptions};
+    ///
+    /// fn pool_options<C: Connection>() -> PoolOptions<C> {
+    ///     PoolOptions::new()
+    ///         .after_connect(|conn: &mut C, _meta| Box::pin(async move {
+    ///             // Fail early if the server accepted the connection but doesn't answer.
+    ///             conn.ping().await?;
+    ///
+    ///             Ok(())
+    ///         }))
+    /// }
     /// ```
     ///
     /// For a discussion on why `Box::pin()` is required, see [the type-level docs][Self].
@@ -327,6 +1073,54 @@ impl<C: Connection> PoolOptions<C> {
         self
     }
 
+    /// Check each new connection before it is ever handed out, right after
+    /// [`after_connect`][Self::after_connect].
+    ///
+    /// Unlike `after_connect`, this is a pass/fail gate: if the callback returns `Ok(false)` or
+    /// an error, the connection is closed and a new one is opened in its place. This is useful to
+    /// make sure a connection landed on the right server, e.g. a primary rather than a replica.
+    ///
+    /// If three new connections in a row fail the check, the backend is assumed to be
+    /// misconfigured and [`Pool::acquire`] gives up instead of retrying until it times out. It
+    /// returns [`Error::ConnectFailed`] with the callback's error if the last one failed with an
+    /// error, or else [`Error::ConnectionRejected`]. Use a [ping][Connection::ping] as the check
+    /// to catch a backend that accepts connections but fails every command. Either way,
+    /// [`Pool::health()`] reports the pool down until a connection passes the check.
+    ///
+    /// This is *not* invoked for idle connections being reused. Use
+    /// [`before_acquire`][Self::before_acquire] for those.
+    ///
+    /// For a discussion on why `Box::pin()` is required, see [the type-level docs][Self].
+    pub fn validate_on_connect<F>(mut self, callback: F) -> Self
+    where
+        for<'c> F: Fn(&'c mut C) -> BoxFuture<'c, Result<bool, Error>> + 'static + Send + Sync,
+    {
+        self.validate_on_connect = Some(Arc::new(callback));
+        self
+    }
+
+    /// Compute a value to attach to each new connection, right after
+    /// [`after_connect`][Self::after_connect] and
+    /// [`validate_on_connect`][Self::validate_on_connect].
+    ///
+    /// The value is type-erased and can be retrieved from a checked-out connection with
+    /// [`PoolConnection::extension()`][crate::PoolConnection::extension]. This is useful to cache
+    /// things that only need to be computed once per connection, such as the server version or
+    /// its supported capabilities, instead of probing for them on every use.
+    ///
+    /// If the callback returns an error, the connection is treated like one that failed
+    /// `after_connect`: it is closed and a new one is opened in its place.
+    pub fn connection_extension<F>(mut self, callback: F) -> Self
+    where
+        for<'c> F: Fn(&'c mut C) -> BoxFuture<'c, Result<Arc<dyn Any + Send + Sync>, Error>>
+            + 'static
+            + Send
+            + Sync,
+    {
+        self.connection_extension = Some(Arc::new(callback));
+        self
+    }
+
     /// Perform an asynchronous action on a previously idle connection before giving it out.
     ///
     /// Alongside the connection, the closure gets [`PoolConnectionMetadata`] which contains
@@ -347,9 +1141,24 @@ impl<C: Connection> PoolOptions<C> {
     /// If you only want to ping connections if they've been idle a certain amount of time,
     /// you can implement your own logic here:
     ///
-    /// This example is written for Postgres but should be trivially adaptable to other databases.
+    /// This example works with any [`Connection`] as it only relies on [`Connection::ping`].
     /// ```no_run
-    /// # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::time::Duration;
+    ///
+    /// use poolx::{Connection, PoolOptions};
+    ///
+    /// fn pool_options<C: Connection>() -> PoolOptions<C> {
+    ///     PoolOptions::new()
+    ///         .test_before_acquire(false)
+    ///         .before_acquire(|conn: &mut C, meta| Box::pin(async move {
+    ///             // Only ping connections that have been sitting idle for over a minute.
+    ///             if meta.idle_for() > Duration::from_secs(60) {
+    ///                 conn.ping().await?;
+    ///             }
+    ///
+    ///             Ok(true)
+    ///         }))
+    /// }
     ///```
     ///
     /// For a discussion on why `Box::pin()` is required, see [the type-level docs][Self].
@@ -364,6 +1173,22 @@ impl<C: Connection> PoolOptions<C> {
         self
     }
 
+    /// Perform a synchronous check on a previously idle connection before giving it out.
+    ///
+    /// This behaves exactly like [`before_acquire`][Self::before_acquire], but as the closure
+    /// doesn't return a future, nothing needs to be boxed. Prefer it for checks that only inspect
+    /// local state of the connection, as it saves an allocation on every acquire.
+    ///
+    /// If both are set, this runs first and [`before_acquire`][Self::before_acquire] only runs if
+    /// it returned `Ok(true)`.
+    pub fn before_acquire_sync<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&mut C, PoolConnectionMetadata) -> Result<bool, Error> + 'static + Send + Sync,
+    {
+        self.before_acquire_sync = Some(Arc::new(callback));
+        self
+    }
+
     /// Perform an asynchronous action on a connection before it is returned to the pool.
     ///
     /// Alongside the connection, the closure gets [`PoolConnectionMetadata`] which contains
@@ -374,19 +1199,34 @@ impl<C: Connection> PoolOptions<C> {
     /// and the connection is closed, allowing a task waiting on [`Pool::acquire`] to
     /// open a new one in its place.
     ///
-    /// # Example (Postgres): Close Memory-Hungry Connections
-    /// Instead of relying on [`max_lifetime`][Self::max_lifetime] to close connections,
-    /// we can monitor their memory usage directly and close any that have allocated too much.
+    /// # Example: Retire Connections When They Come Back
+    /// Unlike [`max_lifetime`][Self::max_lifetime], which is enforced by the idle reaper and
+    /// before acquiring, this checks connections as soon as they are released, and can also
+    /// look at their state:
+    ///
+    /// This example works with any [`Connection`] as it only relies on [`Connection::ping`].
+    /// ```no_run
+    /// use std::time::Duration;
     ///
-    /// Note that this is purely an example showcasing a possible use for this callback
-    /// and may be flawed as it has not been tested.
+    /// use poolx::{Connection, PoolOptions};
     ///
-    /// This example queries [`pg_backend_memory_contexts`](https://www.postgresql.org/docs/current/view-pg-backend-memory-contexts.html)
-    /// which is only allowed for superusers.
+    /// fn pool_options<C: Connection>() -> PoolOptions<C> {
+    ///     PoolOptions::new()
+    ///         .after_release(|conn: &mut C, meta| Box::pin(async move {
+    ///             // Close connections that have been open for over an hour...
+    ///             if meta.age() > Duration::from_secs(60 * 60) {
+    ///                 return Ok(false);
+    ///             }
     ///
-    /// ```no_run
-    /// # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+    ///             // ...or that stopped answering while they were checked out.
+    ///             conn.ping().await?;
+    ///
+    ///             Ok(true)
+    ///         }))
+    /// }
+    /// ```
     ///
+    /// For a discussion on why `Box::pin()` is required, see [the type-level docs][Self].
     pub fn after_release<F>(mut self, callback: F) -> Self
     where
         for<'c> F: Fn(&'c mut C, PoolConnectionMetadata) -> BoxFuture<'c, Result<bool, Error>>
@@ -398,6 +1238,276 @@ impl<C: Connection> PoolOptions<C> {
         self
     }
 
+    /// Set the maximum amount of time [`after_release`][Self::after_release] may take for a
+    /// returned connection.
+    ///
+    /// A hook that hangs, e.g. on I/O over a wedged connection, would otherwise keep the
+    /// connection's slot in the pool forever. Once the timeout expires the connection is closed
+    /// with [`Connection::close_hard`], so its permit is reliably freed for a new connection.
+    ///
+    /// Defaults to 5 seconds.
+    pub fn release_timeout(mut self, timeout: Duration) -> Self {
+        self.release_timeout = timeout;
+        self
+    }
+
+    /// Get the maximum amount of time `after_release` may take.
+    pub fn get_release_timeout(&self) -> Duration {
+        self.release_timeout
+    }
+
+    /// If set to `true`, [`Connection::rollback_pending`] is called on every connection returned
+    /// to the pool, so a transaction or similar scope abandoned by its last user, e.g. by
+    /// returning early with an error, doesn't carry over to the next one.
+    ///
+    /// Like [`after_release`][Self::after_release], it is bounded by
+    /// [`release_timeout`][Self::release_timeout], and the connection is closed with
+    /// [`Connection::close_hard`] if it fails. Depending on the adapter this may cost a round
+    /// trip per return, e.g. a `DISCARD` for Redis; disable it if connections never have
+    /// scopes left open.
+    ///
+    /// Defaults to `true`. Adapters without scopes don't implement `rollback_pending`, which then
+    /// does nothing.
+    pub fn rollback_on_release(mut self, rollback: bool) -> Self {
+        self.rollback_on_release = rollback;
+        self
+    }
+
+    /// Get whether pending scopes are rolled back when a connection is returned.
+    pub fn get_rollback_on_release(&self) -> bool {
+        self.rollback_on_release
+    }
+
+    /// Perform a synchronous action when the pool closes a connection.
+    ///
+    /// This fires exactly once for every pooled connection that the pool closes, whether
+    /// gracefully, with [`Connection::close_hard`], or because it was reaped by
+    /// [`idle_timeout`][Self::idle_timeout] or [`max_lifetime`][Self::max_lifetime]. It is invoked
+    /// right before the connection is handed to `close()`/`close_hard()`.
+    ///
+    /// It does *not* fire for connections taken out of the pool with
+    /// [`PoolConnection::detach`][crate::PoolConnection::detach] or
+    /// [`PoolConnection::leak`][crate::PoolConnection::leak], for connections rejected by
+    /// [`after_connect`][Self::after_connect] before ever being pooled, or for idle connections
+    /// simply dropped along with the last handle to the pool.
+    ///
+    /// Together with [`after_connect`][Self::after_connect] this gives a full view of a connection's
+    /// lifecycle, e.g. to keep an external gauge in sync.
+    ///
+    /// See [notification callbacks][Self#notification-callbacks] for panics.
+    pub fn on_close<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(PoolConnectionMetadata) + 'static + Send + Sync,
+    {
+        self.on_close = Some(Arc::new(callback));
+        self
+    }
+
+    /// Set how long an acquire may wait for the pool to have capacity before
+    /// [`on_slow_acquire`][Self::on_slow_acquire] is invoked.
+    ///
+    /// Defaults to `None`, in which case `on_slow_acquire` never fires.
+    pub fn slow_acquire_threshold(mut self, threshold: impl Into<Option<Duration>>) -> Self {
+        self.slow_acquire_threshold = threshold.into();
+        self
+    }
+
+    /// Get how long an acquire may wait before it's reported as slow.
+    pub fn get_slow_acquire_threshold(&self) -> Option<Duration> {
+        self.slow_acquire_threshold
+    }
+
+    /// Perform a synchronous action when an acquire waited longer than
+    /// [`slow_acquire_threshold`][Self::slow_acquire_threshold] for the pool to have capacity.
+    ///
+    /// The callback receives how long the acquire waited. Only the time spent waiting behind
+    /// other tasks counts, the same as [`AcquireTiming::wait`][crate::AcquireTiming::wait];
+    /// testing idle connections and opening new ones doesn't. Fast acquires just compare two
+    /// timestamps, so this costs next to nothing unless the pool is starved.
+    ///
+    /// This fires as soon as the wait is over, so it also fires for acquires that go on to
+    /// succeed. It does not fire for an acquire that times out or is cancelled while still
+    /// waiting; that returns [`Error::PoolTimedOut`] to the caller instead.
+    ///
+    /// See [notification callbacks][Self#notification-callbacks] for panics.
+    pub fn on_slow_acquire<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(Duration) + 'static + Send + Sync,
+    {
+        self.on_slow_acquire = Some(Arc::new(callback));
+        self
+    }
+
+    /// Perform a synchronous action when the pool becomes saturated, and again when it no longer
+    /// is.
+    ///
+    /// The pool is saturated while [`Pool::is_saturated()`] returns `true`, i.e. while it has
+    /// every connection it may open checked out, so that an acquire would have to wait. The
+    /// callback receives `true` when that starts and `false` when it ends, e.g. to scale out
+    /// without polling [`Pool::is_saturated()`].
+    ///
+    /// This is edge-triggered: the state is re-evaluated whenever a connection is handed out,
+    /// returned or closed, but the callback only fires when it differs from the last state
+    /// reported, so calls always alternate between `true` and `false`, starting with `true`.
+    /// A pool running at about its capacity flips on nearly every acquire, so set
+    /// [`saturation_debounce`][Self::saturation_debounce] to only hear about lasting changes.
+    ///
+    /// The callback is called with a lock held to keep the calls in order, so it should return
+    /// quickly, and must not acquire or return connections of the pool, which would deadlock.
+    /// See [notification callbacks][Self#notification-callbacks] for panics.
+    pub fn on_saturation_change<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(bool) + 'static + Send + Sync,
+    {
+        self.on_saturation_change = Some(Arc::new(callback));
+        self
+    }
+
+    /// Set how long the pool has to stay saturated, or unsaturated, before
+    /// [`on_saturation_change`][Self::on_saturation_change] reports it.
+    ///
+    /// A change that is undone sooner is never reported. One that lasts is reported this long
+    /// after it happened, from a task spawned on the pool's runtime.
+    ///
+    /// Defaults to zero, reporting every change right away.
+    pub fn saturation_debounce(mut self, debounce: Duration) -> Self {
+        self.saturation_debounce = debounce;
+        self
+    }
+
+    /// Get how long a saturation change has to last before it's reported.
+    pub fn get_saturation_debounce(&self) -> Duration {
+        self.saturation_debounce
+    }
+
+    /// Report connections that have been checked out for longer than this, as likely leaks.
+    ///
+    /// A connection that is checked out and never returned silently takes away from the pool's
+    /// capacity. With this set, a background task periodically looks for connections that have
+    /// been checked out for longer than `max`, logs a warning for each and invokes
+    /// [`on_leak`][Self::on_leak], once per checkout.
+    ///
+    /// This is purely a diagnostic: the connection is left alone, as taking it back from
+    /// whoever holds it would be unsound. Connections taken out of the pool with
+    /// [`PoolConnection::detach`][crate::PoolConnection::detach] or
+    /// [`PoolConnection::leak`][crate::PoolConnection::leak] are no longer tracked.
+    ///
+    /// Defaults to `None`, in which case checkouts aren't tracked at all.
+    pub fn max_checkout_duration(mut self, max: impl Into<Option<Duration>>) -> Self {
+        self.max_checkout_duration = max.into();
+        self
+    }
+
+    /// Get how long a connection may be checked out before it's reported as a likely leak.
+    pub fn get_max_checkout_duration(&self) -> Option<Duration> {
+        self.max_checkout_duration
+    }
+
+    /// Perform a synchronous action when a connection has been checked out for longer than
+    /// [`max_checkout_duration`][Self::max_checkout_duration].
+    ///
+    /// The callback receives the connection's id (as in
+    /// [`ConnectionInfo::id`][crate::ConnectionInfo::id]) and how long it has been checked out
+    /// so far. It's invoked from a background task, not by the task holding the connection.
+    ///
+    /// See [notification callbacks][Self#notification-callbacks] for panics.
+    pub fn on_leak<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u64, Duration) + 'static + Send + Sync,
+    {
+        self.on_leak = Some(Arc::new(callback));
+        self
+    }
+
+    /// Perform a synchronous action each time an acquire hands out a connection, with whether it
+    /// was reused from the idle queue or newly opened for this acquire.
+    ///
+    /// This answers how often connections are reused more cheaply than
+    /// [`Pool::acquire_instrumented()`][crate::Pool::acquire_instrumented]; the totals are
+    /// also counted in [`Pool::statistics()`][crate::Pool::statistics] without a callback.
+    ///
+    /// The callback runs in the acquiring task right before the connection is returned to it,
+    /// without any of the pool's locks held, so it adds to every acquire's latency and should
+    /// return quickly. It's not called for failed acquires. See
+    /// [notification callbacks][Self#notification-callbacks] for panics.
+    pub fn on_acquire<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(AcquireSource) + 'static + Send + Sync,
+    {
+        self.on_acquire = Some(Arc::new(callback));
+        self
+    }
+
+    /// Draw connection permits from `semaphore`, shared with other pools, instead of a
+    /// semaphore of the pool's own.
+    ///
+    /// This caps the total number of connections across every pool sharing the semaphore at its
+    /// capacity, e.g. to respect the `maxclients` of a server that several pools connect to.
+    /// Every open connection holds a permit, idle or not, until it is closed; set an
+    /// [`idle_timeout`][Self::idle_timeout] so one pool's idle connections don't keep the others
+    /// waiting for long.
+    ///
+    /// [`max_connections`][Self::max_connections] is ignored when a shared semaphore is given:
+    /// the pool may open as many connections as there are permits available, and
+    /// [`Pool::max_connections()`] returns the semaphore's capacity.
+    /// [`Pool::resize()`] has no effect on such a pool.
+    ///
+    /// Can't be combined with a parent pool.
+    ///
+    /// ```rust,ignore
+    /// let semaphore = Arc::new(AsyncSemaphore::new(100));
+    ///
+    /// let sessions = PoolOptions::new()
+    ///     .with_shared_semaphore(semaphore.clone())
+    ///     .connect_lazy("redis://cache:6379/0")?;
+    /// let jobs = PoolOptions::new()
+    ///     .with_shared_semaphore(semaphore)
+    ///     .connect_lazy("redis://cache:6379/1")?;
+    /// ```
+    pub fn with_shared_semaphore(mut self, semaphore: Arc<AsyncSemaphore>) -> Self {
+        self.shared_semaphore = Some(semaphore);
+        self
+    }
+
+    /// Get the semaphore shared with other pools, if set.
+    pub fn get_shared_semaphore(&self) -> Option<&Arc<AsyncSemaphore>> {
+        self.shared_semaphore.as_ref()
+    }
+
+    /// Report the pool's metrics to `meter`.
+    ///
+    /// The pool registers these instruments on it:
+    ///
+    /// * `poolx.connections`, `poolx.connections.idle` and `poolx.acquires.pending`: gauges of
+    ///   [`Pool::size()`], [`Pool::num_idle()`] and [`Pool::num_pending()`], observed whenever
+    ///   the meter's reader collects.
+    /// * `poolx.acquire.wait_time`: a histogram of how long successful acquires took, in seconds.
+    /// * `poolx.connect.duration`: a histogram of how long opening a new connection took, in
+    ///   seconds, including retries and the on-connect hooks.
+    /// * `poolx.connects`: a counter of the connections opened.
+    ///
+    /// To tell several pools apart, give each its own meter, e.g. with an instrumentation scope
+    /// named after the pool. Without a meter, the pool doesn't measure anything.
+    ///
+    /// Requires the `opentelemetry` feature.
+    ///
+    /// ```rust,ignore
+    /// let pool = PoolOptions::new()
+    ///     .with_meter(opentelemetry::global::meter("sessions"))
+    ///     .connect_lazy("redis://cache:6379/0")?;
+    /// ```
+    #[cfg(feature = "opentelemetry")]
+    pub fn with_meter(mut self, meter: opentelemetry::metrics::Meter) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Get the meter the pool reports its metrics to, if set.
+    #[cfg(feature = "opentelemetry")]
+    pub fn get_meter(&self) -> Option<&opentelemetry::metrics::Meter> {
+        self.meter.as_ref()
+    }
+
     /// Set the parent `Pool` from which the new pool will inherit its semaphore.
     ///
     /// This is currently an internal-only API.
@@ -436,8 +1546,10 @@ impl<C: Connection> PoolOptions<C> {
         self,
         options: <C as Connection>::Options,
     ) -> Result<Pool<C>, Error> {
+        self.validate()?;
+
         // Don't take longer than `acquire_timeout` starting from when this is called.
-        let deadline = Instant::now() + self.acquire_timeout;
+        let deadline = clock::now() + self.acquire_timeout;
 
         let inner = PoolInner::new_arc(self, options);
 
@@ -450,7 +1562,12 @@ impl<C: Connection> PoolOptions<C> {
         // If `min_connections` is nonzero then we'll likely just pull a connection
         // from the idle queue here, but it should at least get tested first.
         let conn = inner.acquire().await?;
-        inner.release(conn);
+
+        if inner.options.disable_reuse {
+            conn.close().await;
+        } else {
+            inner.release(conn);
+        }
 
         Ok(Pool(inner))
     }
@@ -467,6 +1584,7 @@ impl<C: Connection> PoolOptions<C> {
     /// * SQLite: [`SqliteConnectOptions`][crate::sqlite::SqliteConnectOptions]
     /// * MSSQL: [`MssqlConnectOptions`][crate::mssql::MssqlConnectOptions]
     pub fn connect_lazy(self, url: &str) -> Result<Pool<C>, Error> {
+        self.validate()?;
         Ok(self.connect_lazy_with(url.parse()?))
     }
 
@@ -474,21 +1592,190 @@ impl<C: Connection> PoolOptions<C> {
     ///
     /// If [`min_connections`][Self::min_connections] is set, a background task will be spawned to
     /// optimistically establish that many connections for the pool.
+    ///
+    /// # Panics
+    /// If the options are invalid, e.g. `min_connections > max_connections`.
+    /// [`connect_lazy()`][Self::connect_lazy] returns an error instead.
     pub fn connect_lazy_with(self, options: <C as Connection>::Options) -> Pool<C> {
+        if let Err(e) = self.validate() {
+            panic!("{e}");
+        }
+
         // `min_connections` is guaranteed by the idle reaper now.
         Pool(PoolInner::new_arc(self, options))
     }
+
+    /// Get the plain settings of these options, leaving out the callbacks.
+    ///
+    /// For a running pool, use [`Pool::describe()`], which also includes the adapter's
+    /// settings from its connect options. Note that it describes the options
+    /// the pool was built with: [`Pool::max_connections()`] may differ after a
+    /// [`Pool::resize()`].
+    pub fn describe(&self) -> PoolOptionsSummary {
+        PoolOptionsSummary {
+            max_connections: self.max_connections,
+            soft_max_connections: self.soft_max_connections,
+            soft_max_grace: self.soft_max_grace,
+            reserved_connections: self.reserved_connections,
+            min_connections: self.min_connections,
+            prefer_grow_below_min: self.prefer_grow_below_min,
+            acquire_timeout: self.acquire_timeout,
+            connect_timeout: self.connect_timeout,
+            release_timeout: self.release_timeout,
+            max_pending_acquires: self.max_pending_acquires,
+            max_connection_errors: self.max_connection_errors,
+            max_lifetime: self.max_lifetime,
+            idle_timeout: self.idle_timeout,
+            idle_timeout_jitter: self.idle_timeout_jitter,
+            keepalive_interval: self.keepalive_interval,
+            keepalive_concurrency: self.keepalive_concurrency,
+            idle_queue: self.idle_queue,
+            prefer_healthy: self.prefer_healthy,
+            prefer_healthy_window: self.prefer_healthy_window,
+            slow_acquire_threshold: self.slow_acquire_threshold,
+            saturation_debounce: self.saturation_debounce,
+            max_checkout_duration: self.max_checkout_duration,
+            test_before_acquire: self.test_before_acquire,
+            test_before_acquire_retries: self.test_before_acquire_retries,
+            disable_reuse: self.disable_reuse,
+            return_on_drop: self.return_on_drop,
+            rollback_on_release: self.rollback_on_release,
+            on_connect_commands: self
+                .on_connect_commands
+                .iter()
+                .map(|command| command.name().to_string())
+                .collect(),
+            log_settings: self.log_settings.clone(),
+            has_parent_pool: self.parent_pool.is_some(),
+            shared_semaphore_capacity: self.shared_semaphore.as_ref().map(|s| s.capacity()),
+            #[cfg(feature = "opentelemetry")]
+            has_meter: self.meter.is_some(),
+            connect_options: BTreeMap::new(),
+        }
+    }
+
+    /// Check for combinations of options the pool can't work with.
+    fn validate(&self) -> Result<(), Error> {
+        if self.max_connections == 0 {
+            return Err(Error::Configuration(
+                "invalid pool options: `max_connections` must be at least 1".into(),
+            ));
+        }
+
+        if let Some(semaphore) = &self.shared_semaphore {
+            if self.parent_pool.is_some() {
+                return Err(Error::Configuration(
+                    "invalid pool options: a shared semaphore can't be combined with a parent pool"
+                        .into(),
+                ));
+            }
+
+            if self.min_connections as usize > semaphore.capacity() {
+                return Err(Error::Configuration(
+                    format!(
+                        "invalid pool options: `min_connections` ({}) is greater than the capacity of the shared semaphore ({})",
+                        self.min_connections,
+                        semaphore.capacity()
+                    )
+                    .into(),
+                ));
+            }
+        } else if self.min_connections > self.max_connections {
+            return Err(Error::Configuration(
+                format!(
+                    "invalid pool options: `min_connections` ({}) is greater than `max_connections` ({})",
+                    self.min_connections, self.max_connections
+                )
+                .into(),
+            ));
+        }
+
+        if let Some(soft_max) = self.soft_max_connections {
+            if soft_max > self.max_connections {
+                return Err(Error::Configuration(
+                    format!(
+                        "invalid pool options: `soft_max_connections` ({}) is greater than `max_connections` ({})",
+                        soft_max, self.max_connections
+                    )
+                    .into(),
+                ));
+            }
+        }
+
+        if self.reserved_connections > 0 {
+            let capacity = match &self.shared_semaphore {
+                Some(semaphore) => u32::try_from(semaphore.capacity()).unwrap_or(u32::MAX),
+                None => self.max_connections,
+            };
+
+            if self.reserved_connections >= capacity {
+                return Err(Error::Configuration(
+                    format!(
+                        "invalid pool options: `reserved_connections` ({}) must be less than `max_connections` ({})",
+                        self.reserved_connections, capacity
+                    )
+                    .into(),
+                ));
+            }
+        }
+
+        if self.keepalive_concurrency == 0 {
+            return Err(Error::Configuration(
+                "invalid pool options: `keepalive_concurrency` must be at least 1".into(),
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.idle_timeout_jitter) {
+            return Err(Error::Configuration(
+                format!(
+                    "invalid pool options: `idle_timeout_jitter` ({}) must be between 0.0 and 1.0",
+                    self.idle_timeout_jitter
+                )
+                .into(),
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 impl<C: Connection> Debug for PoolOptions<C> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.debug_struct("PoolOptions")
-            .field("max_connections", &self.max_connections)
+        let mut debug = f.debug_struct("PoolOptions");
+        debug.field("max_connections", &self.max_connections)
+            .field("soft_max_connections", &self.soft_max_connections)
+            .field("soft_max_grace", &self.soft_max_grace)
+            .field("reserved_connections", &self.reserved_connections)
             .field("min_connections", &self.min_connections)
-            .field("connect_timeout", &self.acquire_timeout)
+            .field("prefer_grow_below_min", &self.prefer_grow_below_min)
+            .field("acquire_timeout", &self.acquire_timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("release_timeout", &self.release_timeout)
+            .field("max_pending_acquires", &self.max_pending_acquires)
+            .field("max_connection_errors", &self.max_connection_errors)
+            .field("slow_acquire_threshold", &self.slow_acquire_threshold)
+            .field("saturation_debounce", &self.saturation_debounce)
+            .field("max_checkout_duration", &self.max_checkout_duration)
             .field("max_lifetime", &self.max_lifetime)
             .field("idle_timeout", &self.idle_timeout)
+            .field("idle_timeout_jitter", &self.idle_timeout_jitter)
+            .field("keepalive_interval", &self.keepalive_interval)
+            .field("keepalive_concurrency", &self.keepalive_concurrency)
+            .field("idle_queue", &self.idle_queue)
+            .field("prefer_healthy", &self.prefer_healthy)
+            .field("prefer_healthy_window", &self.prefer_healthy_window)
             .field("test_before_acquire", &self.test_before_acquire)
-            .finish()
+            .field("test_before_acquire_retries", &self.test_before_acquire_retries)
+            .field("disable_reuse", &self.disable_reuse)
+            .field("return_on_drop", &self.return_on_drop)
+            .field("rollback_on_release", &self.rollback_on_release)
+            .field("on_connect_commands", &self.on_connect_commands)
+            .field("log_settings", &self.log_settings)
+            .field("shared_semaphore", &self.shared_semaphore.as_ref().map(|s| s.capacity()));
+
+        #[cfg(feature = "opentelemetry")]
+        debug.field("meter", &self.meter.is_some());
+
+        debug.finish()
     }
 }
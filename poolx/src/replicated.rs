@@ -0,0 +1,118 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::clock;
+use crate::conn::Connection;
+use crate::error::Error;
+use crate::{Pool, PoolConnection, PoolHealth};
+
+/// How [`ReplicatedPool::acquire_read`] picks a replica.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplicaSelection {
+    /// Cycle through the replicas in order.
+    #[default]
+    RoundRobin,
+    /// Pick the replica with the fewest checked-out connections.
+    LeastLoaded,
+}
+
+/// A primary [`Pool`] for writes together with any number of replica pools for reads.
+///
+/// Each host keeps its own `Pool`, with its own limits and options; this type only routes
+/// acquires between them. Replicas whose [`health`][Pool::health] is [`PoolHealth::Down`] are
+/// skipped, and reads fall back to the primary if no replica is up. A replica that is down
+/// because it failed to connect gets reads again once in a while, see
+/// [`retry_down_after`][Self::retry_down_after], so it can recover.
+pub struct ReplicatedPool<C: Connection> {
+    primary: Pool<C>,
+    replicas: Vec<Pool<C>>,
+    selection: ReplicaSelection,
+    retry_down_after: Duration,
+    next: AtomicUsize,
+}
+
+impl<C: Connection> ReplicatedPool<C> {
+    /// Create a new `ReplicatedPool` from a primary pool and its replicas.
+    pub fn new(primary: Pool<C>, replicas: Vec<Pool<C>>, selection: ReplicaSelection) -> Self {
+        Self {
+            primary,
+            replicas,
+            selection,
+            retry_down_after: Duration::from_secs(5),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Set how long a replica that failed to connect is skipped, before reads try it again.
+    ///
+    /// A skipped replica opens no connections, so without this it would stay down after its
+    /// host came back. Once its last connect error is this old, reads go to it like to any
+    /// other replica: the first successful connect brings it back up, and another failure
+    /// skips it for as long again. Closed replicas are always skipped.
+    ///
+    /// Defaults to 5 seconds.
+    pub fn retry_down_after(mut self, backoff: Duration) -> Self {
+        self.retry_down_after = backoff;
+        self
+    }
+
+    /// Get the pool for the primary.
+    pub fn primary(&self) -> &Pool<C> {
+        &self.primary
+    }
+
+    /// Get the pools for the replicas.
+    pub fn replicas(&self) -> &[Pool<C>] {
+        &self.replicas
+    }
+
+    /// Retrieves a connection to the primary.
+    pub fn acquire_write(&self) -> impl Future<Output=Result<PoolConnection<C>, Error>> + 'static {
+        self.primary.acquire()
+    }
+
+    /// Retrieves a connection to a replica, chosen according to the [`ReplicaSelection`] policy.
+    ///
+    /// Falls back to the primary if there are no replicas or none of them is up.
+    pub fn acquire_read(&self) -> impl Future<Output=Result<PoolConnection<C>, Error>> + 'static {
+        self.select_replica().unwrap_or(&self.primary).acquire()
+    }
+
+    fn select_replica(&self) -> Option<&Pool<C>> {
+        let up = |pool: &&Pool<C>| match pool.health() {
+            PoolHealth::Up => true,
+            PoolHealth::Down => {
+                !pool.is_closed()
+                    && pool
+                        .last_connect_error_at()
+                        .is_none_or(|at| clock::elapsed(at) >= self.retry_down_after)
+            }
+        };
+
+        match self.selection {
+            ReplicaSelection::RoundRobin => {
+                let len = self.replicas.len();
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+
+                (0..len)
+                    .map(|i| &self.replicas[(start + i) % len])
+                    .find(up)
+            }
+            ReplicaSelection::LeastLoaded => self
+                .replicas
+                .iter()
+                .filter(up)
+                .min_by_key(|pool| (pool.size() as usize).saturating_sub(pool.num_idle())),
+        }
+    }
+
+    /// Close the primary and all replica pools.
+    pub async fn close(&self) {
+        self.primary.close().await;
+
+        for replica in &self.replicas {
+            replica.close().await;
+        }
+    }
+}
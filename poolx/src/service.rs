@@ -0,0 +1,131 @@
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_core::future::BoxFuture;
+
+use crate::{Acquire, Connection, Error, Pool, PoolConnection};
+
+/// A [`tower::Service`][tower_service::Service] running each request on a connection from a
+/// [`Pool`].
+///
+/// `poll_ready()` acquires a connection and keeps it reserved for the next `call()`, which runs
+/// the function given to [`new()`][Self::new] on it and then returns it to the pool. So the
+/// service is only ready while the pool can hand out a connection, and middleware like
+/// load-shedding or concurrency limits sees the pool's backpressure. If the acquire fails, e.g.
+/// with [`Error::PoolTimedOut`], `poll_ready()` returns the error.
+///
+/// Like any tower service, each clone reserves its own connection, and a clone starts out
+/// not ready. Dropping a ready service returns its reserved connection to the pool.
+///
+/// The function has to return a boxed future borrowing the connection, which closures can't
+/// express, so pass a `fn` item.
+///
+/// Requires the `tower` feature.
+///
+/// ```rust,ignore
+/// fn get(conn: &mut RedisConnection, key: String) -> BoxFuture<'_, Result<Option<String>, MyError>> {
+///     Box::pin(async move { Ok(conn.get(key).await?) })
+/// }
+///
+/// let service = ServiceBuilder::new()
+///     .load_shed()
+///     .service(PoolService::new(pool, get));
+/// ```
+pub struct PoolService<C: Connection, F, Req> {
+    pool: Pool<C>,
+    f: Arc<F>,
+    /// The acquire started by `poll_ready()`, until it completes.
+    acquiring: Option<Acquire<C>>,
+    /// The connection reserved by `poll_ready()` for the next `call()`.
+    reserved: Option<PoolConnection<C>>,
+    _req: PhantomData<fn(Req)>,
+}
+
+impl<C: Connection, F, Req> PoolService<C, F, Req> {
+    /// Create a service calling `f` with a pooled connection for each request.
+    pub fn new(pool: Pool<C>, f: F) -> Self {
+        Self {
+            pool,
+            f: Arc::new(f),
+            acquiring: None,
+            reserved: None,
+            _req: PhantomData,
+        }
+    }
+
+    /// Get the pool the service takes its connections from.
+    pub fn pool(&self) -> &Pool<C> {
+        &self.pool
+    }
+}
+
+impl<C: Connection, F, Req> Clone for PoolService<C, F, Req> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            f: self.f.clone(),
+            acquiring: None,
+            reserved: None,
+            _req: PhantomData,
+        }
+    }
+}
+
+impl<C: Connection, F, Req> fmt::Debug for PoolService<C, F, Req> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolService")
+            .field("pool", &self.pool)
+            .field("ready", &self.reserved.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C, F, Req, Res, E> tower_service::Service<Req> for PoolService<C, F, Req>
+where
+    C: Connection,
+    F: for<'c> Fn(&'c mut C, Req) -> BoxFuture<'c, Result<Res, E>> + Send + Sync + 'static,
+    Req: Send + 'static,
+    Res: Send + 'static,
+    E: From<Error> + Send + 'static,
+{
+    type Response = Res;
+    type Error = E;
+    type Future = BoxFuture<'static, Result<Res, E>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        if self.reserved.is_some() {
+            return Poll::Ready(Ok(()));
+        }
+
+        let acquire = self.acquiring.get_or_insert_with(|| self.pool.acquire());
+
+        let res = match Pin::new(acquire).poll(cx) {
+            Poll::Ready(res) => res,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        self.acquiring = None;
+        self.reserved = Some(res?);
+        Poll::Ready(Ok(()))
+    }
+
+    /// # Panics
+    /// If `poll_ready()` wasn't called first and returned `Ready(Ok(()))`.
+    fn call(&mut self, req: Req) -> Self::Future {
+        let mut conn = self
+            .reserved
+            .take()
+            .expect("`PoolService::call()` called without `poll_ready()`");
+        let f = self.f.clone();
+
+        Box::pin(async move {
+            let res = f(&mut conn, req).await;
+            conn.return_now().await;
+            res
+        })
+    }
+}
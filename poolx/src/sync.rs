@@ -1,51 +1,101 @@
-pub struct AsyncSemaphoreReleaser<'a> {
+use crate::error::Error;
+
+pub(crate) struct AsyncSemaphoreReleaser<'a> {
     inner: tokio::sync::SemaphorePermit<'a>,
 }
 
 impl AsyncSemaphoreReleaser<'_> {
-    pub fn disarm(self) {
+    pub(crate) fn disarm(self) {
         self.inner.forget();
-        return;
+    }
+
+    /// Split into one releaser per permit held.
+    pub(crate) fn split_each(mut self) -> Vec<Self> {
+        std::iter::from_fn(|| {
+            Some(AsyncSemaphoreReleaser {
+                inner: self.inner.split(1)?,
+            })
+        })
+            .collect()
     }
 }
 
 
+/// A semaphore handing out permits for connections.
+///
+/// Share one between pools with
+/// [`PoolOptions::with_shared_semaphore`][crate::PoolOptions::with_shared_semaphore] to cap
+/// their total number of open connections.
+///
+/// Permits are granted in the order they were asked for. A released permit goes to the task
+/// that has been waiting the longest, even if it needs more permits than are available yet, so
+/// an attempt that doesn't wait, like [`Pool::try_acquire()`][crate::Pool::try_acquire], never
+/// takes one ahead of a waiting task.
 pub struct AsyncSemaphore {
     inner: tokio::sync::Semaphore,
+    capacity: usize,
 }
 
 impl AsyncSemaphore {
+    /// Create a semaphore with `permits` permits, i.e. for up to `permits` connections.
     pub fn new(permits: usize) -> Self {
         AsyncSemaphore {
             inner: {
                 tokio::sync::Semaphore::new(permits)
             },
+            capacity: permits,
         }
     }
 
+    /// The number of permits the semaphore was created with.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of permits currently available.
     pub fn permits(&self) -> usize {
-        return self.inner.available_permits();
+        self.inner.available_permits()
     }
 
-    pub async fn acquire(&self, permits: u32) -> AsyncSemaphoreReleaser<'_> {
-        return AsyncSemaphoreReleaser {
+    /// Wait for `permits` permits.
+    ///
+    /// Asking for 0 permits is treated as asking for 1, see [`at_least_one`].
+    ///
+    /// Returns [`Error::PoolClosed`] if the semaphore is closed, either before or while waiting.
+    pub(crate) async fn acquire(&self, permits: u32) -> Result<AsyncSemaphoreReleaser<'_>, Error> {
+        let permits = at_least_one(permits);
+        return Ok(AsyncSemaphoreReleaser {
             inner: self
                 .inner
                 // Weird quirk: `tokio::sync::Semaphore` mostly uses `usize` for permit counts,
                 // but `u32` for this and `try_acquire_many()`.
                 .acquire_many(permits)
                 .await
-                .expect("BUG: we do not expose the `.close()` method"),
-        };
+                .map_err(|_| Error::PoolClosed)?,
+        });
     }
 
-    pub fn try_acquire(&self, permits: u32) -> Option<AsyncSemaphoreReleaser<'_>> {
+    /// Take `permits` permits if they're available right now.
+    ///
+    /// This doesn't barge: while tasks are waiting in `acquire()`, released permits are handed
+    /// to them first and never counted as available, so this returns `None`.
+    ///
+    /// Asking for 0 permits is treated as asking for 1, see [`at_least_one`].
+    pub(crate) fn try_acquire(&self, permits: u32) -> Option<AsyncSemaphoreReleaser<'_>> {
         return Some(AsyncSemaphoreReleaser {
-            inner: self.inner.try_acquire_many(permits).ok()?,
+            inner: self.inner.try_acquire_many(at_least_one(permits)).ok()?,
         });
     }
 
-    pub fn release(&self, permits: usize) {
-        return self.inner.add_permits(permits);
+    pub(crate) fn release(&self, permits: usize) {
+        self.inner.add_permits(permits)
     }
-}
\ No newline at end of file
+}
+
+/// Clamp a permit count to at least 1.
+///
+/// `tokio::sync::Semaphore` grants 0 permits right away, even when it has none left, which would
+/// let a caller go ahead without reserving a connection slot.
+fn at_least_one(permits: u32) -> u32 {
+    permits.max(1)
+}
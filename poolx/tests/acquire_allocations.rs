@@ -0,0 +1,117 @@
+//! Checks that `Pool::acquire()` hands out a ready idle connection without allocating.
+//!
+//! This lives in its own test binary because it installs a counting `#[global_allocator]`,
+//! which would otherwise apply to every other test of the crate.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures_util::FutureExt;
+use poolx::futures_core::future::BoxFuture;
+use poolx::url::Url;
+use poolx::{ConnectOptions, Connection, Error, Pool, PoolOptions};
+
+thread_local! {
+    /// The number of allocations made by the current thread.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts allocations, so the test can check that the hot path doesn't make any.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// A connection that never touches the network.
+struct MockConn {
+    id: usize,
+    pings: Arc<AtomicUsize>,
+}
+
+#[derive(Debug, Default)]
+struct MockConnectOptions {
+    connects: AtomicUsize,
+    pings: Arc<AtomicUsize>,
+}
+
+impl Connection for MockConn {
+    type Options = MockConnectOptions;
+
+    fn close(self) -> BoxFuture<'static, Result<(), Error>> {
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn close_hard(self) -> BoxFuture<'static, Result<(), Error>> {
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn ping(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            self.pings.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+    }
+}
+
+impl FromStr for MockConnectOptions {
+    type Err = Error;
+
+    fn from_str(_: &str) -> Result<Self, Self::Err> {
+        Ok(Self::default())
+    }
+}
+
+impl ConnectOptions for MockConnectOptions {
+    type Connection = MockConn;
+
+    fn from_url(_: &Url) -> Result<Self, Error> {
+        Ok(Self::default())
+    }
+
+    fn connect(&self) -> BoxFuture<'_, Result<Self::Connection, Error>> {
+        Box::pin(async move {
+            Ok(MockConn {
+                id: self.connects.fetch_add(1, Ordering::SeqCst),
+                pings: self.pings.clone(),
+            })
+        })
+    }
+}
+
+#[tokio::test]
+async fn acquire_hands_out_an_idle_connection_without_allocating() {
+    let pool: Pool<MockConn> = PoolOptions::new()
+        .test_before_acquire(false)
+        .connect_lazy_with(MockConnectOptions::default());
+    pool.acquire().await.unwrap().return_now().await;
+    assert_eq!(pool.num_idle(), 1);
+
+    let before = ALLOCATIONS.with(|count| count.get());
+    let conn = pool.acquire().now_or_never().expect("not ready on the first poll");
+    let allocations = ALLOCATIONS.with(|count| count.get()) - before;
+
+    assert_eq!(conn.unwrap().id, 0);
+    assert_eq!(allocations, 0);
+
+    // testing the connection has to wait for the ping, which takes the boxed path
+    let options = MockConnectOptions::default();
+    let pings = options.pings.clone();
+    let pool: Pool<MockConn> = PoolOptions::new().connect_lazy_with(options);
+    pool.acquire().await.unwrap().return_now().await;
+    assert_eq!(pool.acquire().await.unwrap().id, 0);
+    assert_eq!(pings.load(Ordering::SeqCst), 1);
+}